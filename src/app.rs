@@ -1,28 +1,52 @@
+#[cfg(feature = "websocket")]
+use crate::websocket::{self, WebSocketHandler, WebSocketRoute};
 use crate::{
 	context::Context,
 	error::Error,
+	error_handler::{BoundErrorHandler, DefaultErrorHandler, ErrorHandler, StatefulErrorHandler},
 	http_method::HttpMethod,
+	listener::BoundAddr,
 	middleware::Middleware,
-	middleware_handler::MiddlewareHandler,
+	middleware_handler::{MiddlewareHandler, PathError},
+	named_routes::{get_mounted_name, NamedRoutes, UrlForError},
+	route_error::RouteError,
+	route_group::RouteGroup,
+	route_info::{format_route_table, RouteInfo, ShadowedRoute},
+	router::{covers, RouteMatch, Router},
+	sub_app::SubAppRoute,
 	Request,
 	Response,
 };
 
-use std::{
-	collections::HashMap,
-	error::Error as StdError,
-	fmt::Debug,
-	future::Future,
-	pin::Pin,
-	sync::Arc,
-};
+use std::{any::Any, collections::BTreeMap, fmt::Debug, future::Future, pin::Pin, sync::Arc};
 
 type ContextGeneratorFn<TContext, TState> = fn(Request, &TState) -> TContext;
-type ErrorHandlerFn = fn(Response, Box<dyn StdError>) -> Response;
+type StartHookFn<TState> =
+	for<'a> fn(&'a [BoundAddr], &'a TState) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+type LifecycleHookFn<TState> = fn(&TState) -> Pin<Box<dyn Future<Output = ()> + Send + '_>>;
+
+type MiddlewareRouter<TContext, TMiddleware> = Router<MiddlewareHandler<TContext, TMiddleware>>;
+type MiddlewareMatch<TContext, TMiddleware> = RouteMatch<MiddlewareHandler<TContext, TMiddleware>>;
+
+// Everything that was resolved for a request, before running it
+struct MiddlewareStack<TContext, TMiddleware>
+where
+	TContext: Context + Debug + Send + Sync,
+	TMiddleware: Middleware<TContext> + Clone + Send + Sync,
+{
+	nodes: Vec<MiddlewareMatch<TContext, TMiddleware>>,
+	allowed_methods: Vec<HttpMethod>,
+	// Whether anything at all is registered for the method of the request
+	is_method_implemented: bool,
+	// The state of the app, for its own middlewares
+	state: Arc<dyn Any + Send + Sync>,
+	#[cfg(feature = "websocket")]
+	websocket: Option<RouteMatch<WebSocketRoute>>,
+}
 
 fn chained_run<TContext, TMiddleware>(
 	mut context: TContext,
-	nodes: Arc<Vec<MiddlewareHandler<TContext, TMiddleware>>>,
+	stack: Arc<MiddlewareStack<TContext, TMiddleware>>,
 	i: usize,
 ) -> Pin<Box<dyn Future<Output = Result<TContext, Error<TContext>>> + Send>>
 where
@@ -30,40 +54,77 @@ where
 	TMiddleware: 'static + Middleware<TContext> + Clone + Send + Sync,
 {
 	Box::pin(async move {
-		if let Some(m) = nodes.clone().get(i) {
-			// add populating the url parameters here
-			let mut url_params = HashMap::new();
-			if let Some(captures) = m.path_match.captures(&context.get_path()) {
-				for var in m.path_match.capture_names() {
-					if var.is_none() {
-						continue;
-					}
-					let var = var.unwrap();
-					let value = captures.name(var);
-					if let Some(value) = value {
-						url_params.insert(var.to_string(), value.as_str().to_string());
-					}
-				}
-			}
-			context.get_request_mut().params = url_params;
+		if let Some(m) = stack.clone().nodes.get(i) {
+			context.get_request_mut().params = m.params.clone();
+			context.get_request_mut().state = Some(
+				m.handler
+					.state
+					.clone()
+					.unwrap_or_else(|| stack.state.clone()),
+			);
 			m.handler
+				.handler
 				.run_middleware(
 					context,
-					Box::new(move |context| chained_run(context, nodes.clone(), i + 1)),
+					Box::new(move |context| chained_run(context, stack.clone(), i + 1)),
 				)
 				.await
 		} else {
-			let method = context.get_method().to_string();
-			let path = context.get_path();
-			context
-				.status(404)
-				.body(&format!("Cannot {} route {}", method, path));
-			Ok(context)
+			Ok(end_chain(context, &stack))
 		}
 	})
 }
 
-#[derive(Clone)]
+// Called when every middleware in the stack has passed the request on to the next one
+fn end_chain<TContext, TMiddleware>(
+	mut context: TContext,
+	stack: &MiddlewareStack<TContext, TMiddleware>,
+) -> TContext
+where
+	TContext: Context + Debug + Send + Sync,
+	TMiddleware: Middleware<TContext> + Clone + Send + Sync,
+{
+	#[cfg(feature = "websocket")]
+	{
+		if let Some(websocket) = &stack.websocket {
+			context.get_request_mut().params = websocket.params.clone();
+			return websocket::upgrade(context, websocket.handler.handler);
+		}
+	}
+
+	let method = context.get_method().to_string();
+	let path = context.get_path();
+	if !stack.is_method_implemented {
+		context
+			.status(501)
+			.body(&format!("Cannot {} route {}", method, path));
+		return context;
+	}
+
+	let allow = stack
+		.allowed_methods
+		.iter()
+		.map(HttpMethod::to_string)
+		.collect::<Vec<_>>()
+		.join(", ");
+
+	if !stack.allowed_methods.is_empty() && context.get_method() == &HttpMethod::Options {
+		// Nothing handled the OPTIONS request, so just list what's allowed
+		context.status(204).header("Allow", &allow);
+	} else if !stack.allowed_methods.is_empty() {
+		// The route exists, just not for this method
+		context
+			.status(405)
+			.header("Allow", &allow)
+			.body(&format!("Cannot {} route {}", method, path));
+	} else {
+		context
+			.status(404)
+			.body(&format!("Cannot {} route {}", method, path));
+	}
+	context
+}
+
 pub struct App<TContext, TMiddleware, TState>
 where
 	TContext: Context + Debug + Send + Sync,
@@ -71,41 +132,101 @@ where
 	TState: Send + Sync,
 {
 	context_generator: ContextGeneratorFn<TContext, TState>,
-	state: TState,
-	pub(crate) error_handler: Option<ErrorHandlerFn>,
-
-	get_stack: Vec<MiddlewareHandler<TContext, TMiddleware>>,
-	post_stack: Vec<MiddlewareHandler<TContext, TMiddleware>>,
-	put_stack: Vec<MiddlewareHandler<TContext, TMiddleware>>,
-	delete_stack: Vec<MiddlewareHandler<TContext, TMiddleware>>,
-	head_stack: Vec<MiddlewareHandler<TContext, TMiddleware>>,
-	options_stack: Vec<MiddlewareHandler<TContext, TMiddleware>>,
-	connect_stack: Vec<MiddlewareHandler<TContext, TMiddleware>>,
-	patch_stack: Vec<MiddlewareHandler<TContext, TMiddleware>>,
-	trace_stack: Vec<MiddlewareHandler<TContext, TMiddleware>>,
+	// Shared with every request, see Context::state
+	state: Arc<TState>,
+	error_handler: Option<Arc<dyn ErrorHandler<TContext, TState> + Send + Sync>>,
+	// The context generators and error handlers of mounted sub apps,
+	// each with the state of its own sub app
+	sub_app_stack: Router<SubAppRoute<TContext>>,
+	named_routes: NamedRoutes,
+	pub(crate) start_hook: Option<StartHookFn<TState>>,
+	pub(crate) shutdown_hook: Option<LifecycleHookFn<TState>>,
+	pub(crate) drain_complete_hook: Option<LifecycleHookFn<TState>>,
+
+	get_stack: MiddlewareRouter<TContext, TMiddleware>,
+	post_stack: MiddlewareRouter<TContext, TMiddleware>,
+	put_stack: MiddlewareRouter<TContext, TMiddleware>,
+	delete_stack: MiddlewareRouter<TContext, TMiddleware>,
+	head_stack: MiddlewareRouter<TContext, TMiddleware>,
+	options_stack: MiddlewareRouter<TContext, TMiddleware>,
+	connect_stack: MiddlewareRouter<TContext, TMiddleware>,
+	patch_stack: MiddlewareRouter<TContext, TMiddleware>,
+	trace_stack: MiddlewareRouter<TContext, TMiddleware>,
+	// Extension methods, like PURGE, by name
+	custom_stacks: BTreeMap<String, MiddlewareRouter<TContext, TMiddleware>>,
+	// Everything registered through use_middleware, to start off the stacks of extension methods
+	// registered later on with
+	use_middleware_stack: MiddlewareRouter<TContext, TMiddleware>,
+	#[cfg(feature = "websocket")]
+	websocket_stack: Router<WebSocketRoute>,
+}
+
+// A clone gets named routes of its own, so that routes registered on it don't show up on the app
+// it was cloned from. Named routes are only shared on purpose, through set_named_routes
+impl<TContext, TMiddleware, TState> Clone for App<TContext, TMiddleware, TState>
+where
+	TContext: Context + Debug + Send + Sync,
+	TMiddleware: Middleware<TContext> + Clone + Send + Sync,
+	TState: Send + Sync,
+{
+	fn clone(&self) -> Self {
+		App {
+			context_generator: self.context_generator,
+			state: self.state.clone(),
+			error_handler: self.error_handler.clone(),
+			sub_app_stack: self.sub_app_stack.clone(),
+			named_routes: self.named_routes.copy(),
+			start_hook: self.start_hook,
+			shutdown_hook: self.shutdown_hook,
+			drain_complete_hook: self.drain_complete_hook,
+
+			get_stack: self.get_stack.clone(),
+			post_stack: self.post_stack.clone(),
+			put_stack: self.put_stack.clone(),
+			delete_stack: self.delete_stack.clone(),
+			head_stack: self.head_stack.clone(),
+			options_stack: self.options_stack.clone(),
+			connect_stack: self.connect_stack.clone(),
+			patch_stack: self.patch_stack.clone(),
+			trace_stack: self.trace_stack.clone(),
+			custom_stacks: self.custom_stacks.clone(),
+			use_middleware_stack: self.use_middleware_stack.clone(),
+			#[cfg(feature = "websocket")]
+			websocket_stack: self.websocket_stack.clone(),
+		}
+	}
 }
 
 impl<TContext, TMiddleware, TState> App<TContext, TMiddleware, TState>
 where
 	TContext: 'static + Context + Debug + Send + Sync,
 	TMiddleware: 'static + Middleware<TContext> + Clone + Send + Sync,
-	TState: Send + Sync,
+	TState: 'static + Send + Sync,
 {
 	pub fn create(context_generator: ContextGeneratorFn<TContext, TState>, state: TState) -> Self {
 		App {
 			context_generator,
-			state,
+			state: Arc::new(state),
 			error_handler: None,
-
-			get_stack: vec![],
-			post_stack: vec![],
-			put_stack: vec![],
-			delete_stack: vec![],
-			head_stack: vec![],
-			options_stack: vec![],
-			connect_stack: vec![],
-			patch_stack: vec![],
-			trace_stack: vec![],
+			sub_app_stack: Router::new(),
+			named_routes: NamedRoutes::new(),
+			start_hook: None,
+			shutdown_hook: None,
+			drain_complete_hook: None,
+
+			get_stack: Router::new(),
+			post_stack: Router::new(),
+			put_stack: Router::new(),
+			delete_stack: Router::new(),
+			head_stack: Router::new(),
+			options_stack: Router::new(),
+			connect_stack: Router::new(),
+			patch_stack: Router::new(),
+			trace_stack: Router::new(),
+			custom_stacks: BTreeMap::new(),
+			use_middleware_stack: Router::new(),
+			#[cfg(feature = "websocket")]
+			websocket_stack: Router::new(),
 		}
 	}
 
@@ -113,79 +234,347 @@ where
 		&self.state
 	}
 
-	pub fn set_error_handler(&mut self, error_handler: ErrorHandlerFn) {
-		self.error_handler = Some(error_handler);
+	pub fn set_error_handler<TErrorHandler>(&mut self, error_handler: TErrorHandler)
+	where
+		TErrorHandler: 'static + ErrorHandler<TContext, TState> + Send + Sync,
+	{
+		self.error_handler = Some(Arc::new(error_handler));
 	}
 
 	pub fn remove_error_handler(&mut self) {
 		self.error_handler = None;
 	}
 
+	// Called once the server is bound, with the addresses it's actually listening on,
+	// before any connection is accepted
+	pub fn on_start(&mut self, hook: StartHookFn<TState>) {
+		self.start_hook = Some(hook);
+	}
+
+	// Called as soon as the shutdown signal resolves. No new connections are accepted by then,
+	// but the open ones are still being drained
+	pub fn on_shutdown(&mut self, hook: LifecycleHookFn<TState>) {
+		self.shutdown_hook = Some(hook);
+	}
+
+	// Called once every connection has been closed, or aborted after the drain timeout
+	pub fn on_drain_complete(&mut self, hook: LifecycleHookFn<TState>) {
+		self.drain_complete_hook = Some(hook);
+	}
+
 	pub fn get(&mut self, path: &str, middlewares: &[TMiddleware]) {
-		middlewares.iter().for_each(|handler| {
-			self.get_stack
-				.push(MiddlewareHandler::new(path, handler.clone(), true));
-		});
-		middlewares.iter().for_each(|handler| {
-			self.trace_stack
-				.push(MiddlewareHandler::new(path, handler.clone(), true));
-		});
+		self.try_get(path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
 	}
 
 	pub fn post(&mut self, path: &str, middlewares: &[TMiddleware]) {
-		middlewares.iter().for_each(|handler| {
-			self.post_stack
-				.push(MiddlewareHandler::new(path, handler.clone(), true));
-		});
+		self.try_post(path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
 	}
 
 	pub fn put(&mut self, path: &str, middlewares: &[TMiddleware]) {
-		middlewares.iter().for_each(|handler| {
-			self.put_stack
-				.push(MiddlewareHandler::new(path, handler.clone(), true));
-		});
+		self.try_put(path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
 	}
 
 	pub fn delete(&mut self, path: &str, middlewares: &[TMiddleware]) {
-		middlewares.iter().for_each(|handler| {
-			self.delete_stack
-				.push(MiddlewareHandler::new(path, handler.clone(), true));
-		});
+		self.try_delete(path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
 	}
 
 	pub fn head(&mut self, path: &str, middlewares: &[TMiddleware]) {
-		middlewares.iter().for_each(|handler| {
-			self.head_stack
-				.push(MiddlewareHandler::new(path, handler.clone(), true));
-		});
+		self.try_head(path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
 	}
 
 	pub fn options(&mut self, path: &str, middlewares: &[TMiddleware]) {
-		middlewares.iter().for_each(|handler| {
-			self.options_stack
-				.push(MiddlewareHandler::new(path, handler.clone(), true));
-		});
+		self.try_options(path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
 	}
 
 	pub fn connect(&mut self, path: &str, middlewares: &[TMiddleware]) {
-		middlewares.iter().for_each(|handler| {
-			self.connect_stack
-				.push(MiddlewareHandler::new(path, handler.clone(), true));
-		});
+		self.try_connect(path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
 	}
 
 	pub fn patch(&mut self, path: &str, middlewares: &[TMiddleware]) {
-		middlewares.iter().for_each(|handler| {
-			self.patch_stack
-				.push(MiddlewareHandler::new(path, handler.clone(), true));
-		});
+		self.try_patch(path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
 	}
 
 	pub fn trace(&mut self, path: &str, middlewares: &[TMiddleware]) {
-		middlewares.iter().for_each(|handler| {
-			self.trace_stack
-				.push(MiddlewareHandler::new(path, handler.clone(), true));
+		self.try_trace(path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
+	}
+
+	// Same as get, but the route can be referred to by its name, see url_for
+	pub fn get_named(&mut self, name: &str, path: &str, middlewares: &[TMiddleware]) {
+		self.try_get_named(name, path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
+	}
+
+	pub fn post_named(&mut self, name: &str, path: &str, middlewares: &[TMiddleware]) {
+		self.try_post_named(name, path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
+	}
+
+	pub fn put_named(&mut self, name: &str, path: &str, middlewares: &[TMiddleware]) {
+		self.try_put_named(name, path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
+	}
+
+	pub fn delete_named(&mut self, name: &str, path: &str, middlewares: &[TMiddleware]) {
+		self.try_delete_named(name, path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
+	}
+
+	pub fn patch_named(&mut self, name: &str, path: &str, middlewares: &[TMiddleware]) {
+		self.try_patch_named(name, path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
+	}
+
+	// Same as get, but an invalid path is returned as an error instead of panicking, and nothing
+	// is registered
+	pub fn try_get(&mut self, path: &str, middlewares: &[TMiddleware]) -> Result<(), RouteError> {
+		self.push_endpoint(&HttpMethod::Get, path, &[], middlewares, None)
+	}
+
+	pub fn try_post(&mut self, path: &str, middlewares: &[TMiddleware]) -> Result<(), RouteError> {
+		self.push_endpoint(&HttpMethod::Post, path, &[], middlewares, None)
+	}
+
+	pub fn try_put(&mut self, path: &str, middlewares: &[TMiddleware]) -> Result<(), RouteError> {
+		self.push_endpoint(&HttpMethod::Put, path, &[], middlewares, None)
+	}
+
+	pub fn try_delete(
+		&mut self,
+		path: &str,
+		middlewares: &[TMiddleware],
+	) -> Result<(), RouteError> {
+		self.push_endpoint(&HttpMethod::Delete, path, &[], middlewares, None)
+	}
+
+	pub fn try_head(&mut self, path: &str, middlewares: &[TMiddleware]) -> Result<(), RouteError> {
+		self.push_endpoint(&HttpMethod::Head, path, &[], middlewares, None)
+	}
+
+	pub fn try_options(
+		&mut self,
+		path: &str,
+		middlewares: &[TMiddleware],
+	) -> Result<(), RouteError> {
+		self.push_endpoint(&HttpMethod::Options, path, &[], middlewares, None)
+	}
+
+	pub fn try_connect(
+		&mut self,
+		path: &str,
+		middlewares: &[TMiddleware],
+	) -> Result<(), RouteError> {
+		self.push_endpoint(&HttpMethod::Connect, path, &[], middlewares, None)
+	}
+
+	pub fn try_patch(&mut self, path: &str, middlewares: &[TMiddleware]) -> Result<(), RouteError> {
+		self.push_endpoint(&HttpMethod::Patch, path, &[], middlewares, None)
+	}
+
+	pub fn try_trace(&mut self, path: &str, middlewares: &[TMiddleware]) -> Result<(), RouteError> {
+		self.push_endpoint(&HttpMethod::Trace, path, &[], middlewares, None)
+	}
+
+	pub fn try_get_named(
+		&mut self,
+		name: &str,
+		path: &str,
+		middlewares: &[TMiddleware],
+	) -> Result<(), RouteError> {
+		self.push_endpoint(&HttpMethod::Get, path, &[], middlewares, Some(name))
+	}
+
+	pub fn try_post_named(
+		&mut self,
+		name: &str,
+		path: &str,
+		middlewares: &[TMiddleware],
+	) -> Result<(), RouteError> {
+		self.push_endpoint(&HttpMethod::Post, path, &[], middlewares, Some(name))
+	}
+
+	pub fn try_put_named(
+		&mut self,
+		name: &str,
+		path: &str,
+		middlewares: &[TMiddleware],
+	) -> Result<(), RouteError> {
+		self.push_endpoint(&HttpMethod::Put, path, &[], middlewares, Some(name))
+	}
+
+	pub fn try_delete_named(
+		&mut self,
+		name: &str,
+		path: &str,
+		middlewares: &[TMiddleware],
+	) -> Result<(), RouteError> {
+		self.push_endpoint(&HttpMethod::Delete, path, &[], middlewares, Some(name))
+	}
+
+	pub fn try_patch_named(
+		&mut self,
+		name: &str,
+		path: &str,
+		middlewares: &[TMiddleware],
+	) -> Result<(), RouteError> {
+		self.push_endpoint(&HttpMethod::Patch, path, &[], middlewares, Some(name))
+	}
+
+	// Names a path without registering anything on it, for the routes registered some other way
+	pub fn name_route(&mut self, name: &str, path: &str) {
+		self.named_routes.insert(name, path);
+	}
+
+	// Builds the url of a named route, including the base paths of the sub apps it's mounted in
+	pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Result<String, UrlForError> {
+		self.named_routes.url_for(name, params)
+	}
+
+	// Can be registered with handlebars as a helper, see NamedRoutes
+	pub fn get_named_routes(&self) -> &NamedRoutes {
+		&self.named_routes
+	}
+
+	// Shares the named routes with ones that were created before the app, like the ones a
+	// handlebars registry in the app's state was given
+	pub fn set_named_routes(&mut self, named_routes: NamedRoutes) {
+		named_routes.extend("", &self.named_routes);
+		self.named_routes = named_routes;
+	}
+
+	// Registers routes under a common base path. Middlewares added to the group only run for the
+	// endpoints of the group, unlike use_middleware, which runs for anything under its path
+	pub fn group<TRegister>(&mut self, base_path: &str, register: TRegister)
+	where
+		TRegister: FnOnce(&mut RouteGroup<'_, TContext, TMiddleware, TState>),
+	{
+		register(&mut RouteGroup::new(
+			self,
+			format_base_path(base_path),
+			vec![],
+		));
+	}
+
+	// Registers an endpoint, with the middlewares of the route group it's in, if any, in front of
+	// it. They match just like the endpoint does, but are listed as middlewares and never shadow
+	// anything. Nothing is registered if the path is invalid
+	pub(crate) fn push_endpoint(
+		&mut self,
+		method: &HttpMethod,
+		path: &str,
+		group_middlewares: &[TMiddleware],
+		middlewares: &[TMiddleware],
+		name: Option<&str>,
+	) -> Result<(), RouteError> {
+		let group_handlers = group_middlewares.iter().map(|handler| {
+			let mut handler = MiddlewareHandler::try_new(path, handler.clone(), true)?;
+			handler.is_group_middleware = true;
+			Ok(handler)
 		});
+		let handlers = middlewares
+			.iter()
+			.map(|handler| MiddlewareHandler::try_new(path, handler.clone(), true));
+		let handlers = group_handlers
+			.chain(handlers)
+			.map(|handler| {
+				let mut handler = handler?;
+				handler.name = name.map(str::to_string);
+				Ok(handler)
+			})
+			.collect::<Result<Vec<_>, PathError>>()?;
+
+		if let Some(name) = name {
+			self.named_routes.insert(name, path);
+		}
+		let stack = self.get_route_stack_mut(method);
+		handlers.into_iter().for_each(|handler| stack.push(handler));
+		Ok(())
+	}
+
+	// Everything registered on the app, including what came from its sub apps. Middlewares come
+	// first, since they run for every method, followed by the endpoints of each method along with
+	// the middlewares of the route groups they're in
+	pub fn routes(&self) -> impl Iterator<Item = RouteInfo> + '_ {
+		let sub_apps = self.sub_app_stack.get_handlers();
+		let middlewares = self
+			.use_middleware_stack
+			.get_handlers()
+			.iter()
+			.map(move |handler| get_route_info(None, handler, sub_apps));
+		let endpoints = self
+			.get_method_stacks()
+			.into_iter()
+			.flat_map(move |(method, stack)| {
+				stack
+					.get_handlers()
+					.iter()
+					.filter(|handler| handler.is_endpoint)
+					.map(move |handler| get_route_info(Some(method.clone()), handler, sub_apps))
+			});
+		middlewares.chain(endpoints)
+	}
+
+	// The routes, lined up in a table that can go into the logs
+	pub fn get_route_table(&self) -> String {
+		format_route_table(&self.routes().collect::<Vec<_>>())
+	}
+
+	// Endpoints that can never be reached, because an endpoint registered before them on another
+	// path matches every url they would. Endpoints on the very same path are left out, since
+	// those are chained on purpose
+	pub fn get_shadowed_routes(&self) -> Vec<ShadowedRoute> {
+		let sub_apps = self.sub_app_stack.get_handlers();
+		let mut shadowed_routes = vec![];
+		for (method, stack) in self.get_method_stacks() {
+			let endpoints = stack
+				.get_handlers()
+				.iter()
+				.filter(|handler| handler.is_endpoint && !handler.is_group_middleware)
+				.collect::<Vec<_>>();
+			for (index, endpoint) in endpoints.iter().enumerate() {
+				let shadowed_by = endpoints[..index].iter().find(|previous| {
+					previous.mounted_url != endpoint.mounted_url &&
+						covers(&previous.segments, &endpoint.segments)
+				});
+				if let Some(shadowed_by) = shadowed_by {
+					shadowed_routes.push(ShadowedRoute {
+						route: get_route_info(Some(method.clone()), endpoint, sub_apps),
+						shadowed_by: get_route_info(Some(method.clone()), shadowed_by, sub_apps),
+					});
+				}
+			}
+		}
+		shadowed_routes
+	}
+
+	// Registers endpoints for any method, including extension methods like PURGE or PROPFIND.
+	// The method is case sensitive, and has to be a valid token. Like try_get, an invalid path is
+	// returned as an error instead of panicking, and nothing is registered
+	pub fn method(
+		&mut self,
+		method: &str,
+		path: &str,
+		middlewares: &[TMiddleware],
+	) -> Result<(), RouteError> {
+		let method = method
+			.parse::<HttpMethod>()
+			.map_err(|_| RouteError::InvalidMethod(method.to_string()))?;
+		self.push_endpoint(&method, path, &[], middlewares, None)
+	}
+
+	// Runs the GET middlewares for the path first, so that things like authentication still apply
+	#[cfg(feature = "websocket")]
+	pub fn websocket(&mut self, path: &str, handler: WebSocketHandler) {
+		self.websocket_stack
+			.push(WebSocketRoute::new(path, handler));
 	}
 
 	pub fn use_middleware(&mut self, path: &str, middlewares: &[TMiddleware]) {
@@ -208,6 +597,11 @@ where
 				.push(MiddlewareHandler::new(path, handler.clone(), false));
 			self.trace_stack
 				.push(MiddlewareHandler::new(path, handler.clone(), false));
+			self.custom_stacks.values_mut().for_each(|stack| {
+				stack.push(MiddlewareHandler::new(path, handler.clone(), false));
+			});
+			self.use_middleware_stack
+				.push(MiddlewareHandler::new(path, handler.clone(), false));
 		});
 	}
 
@@ -216,126 +610,280 @@ where
 		base_path: &str,
 		sub_app: App<TContext, TMiddleware, TSubAppState>,
 	) where
-		TSubAppState: Send + Sync,
+		TSubAppState: 'static + Send + Sync,
 	{
-		let base_path = {
-			if base_path == "/" {
-				"".to_string()
-			} else {
-				let mut formatted_base_path = base_path.to_string();
-
-				// If it ends with /, remove it
-				if let Some(stripped) = base_path.strip_suffix('/') {
-					formatted_base_path = stripped.to_string();
-				}
-
-				// If it doesn't begin with a /, add it
-				if !base_path.starts_with('/') {
-					formatted_base_path = format!("/{}", base_path);
-				}
+		let base_path = format_base_path(base_path);
+		let sub_app_index = self.sub_app_stack.get_handlers().len();
+
+		// The sub app's middlewares keep seeing the sub app's state
+		let state = sub_app.state;
+		let any_state: Arc<dyn Any + Send + Sync> = state.clone();
+
+		let mount = |stack| mount_handlers(&base_path, stack, &any_state, sub_app_index);
+
+		self.get_stack.extend(mount(sub_app.get_stack));
+		self.post_stack.extend(mount(sub_app.post_stack));
+		self.put_stack.extend(mount(sub_app.put_stack));
+		self.delete_stack.extend(mount(sub_app.delete_stack));
+		self.head_stack.extend(mount(sub_app.head_stack));
+		self.options_stack.extend(mount(sub_app.options_stack));
+		self.connect_stack.extend(mount(sub_app.connect_stack));
+		self.patch_stack.extend(mount(sub_app.patch_stack));
+		self.trace_stack.extend(mount(sub_app.trace_stack));
+
+		// The middlewares of either app apply to the extension methods of both
+		let sub_app_methods = sub_app.custom_stacks.keys().cloned().collect::<Vec<_>>();
+		for (method, stack) in sub_app.custom_stacks {
+			let mounted = mount(stack);
+			self.get_route_stack_mut(&HttpMethod::Custom(method))
+				.extend(mounted);
+		}
+		let sub_app_middlewares = mount(sub_app.use_middleware_stack);
+		self.custom_stacks
+			.iter_mut()
+			.filter(|(method, _)| !sub_app_methods.contains(method))
+			.for_each(|(_, stack)| stack.extend(sub_app_middlewares.clone()));
+		self.use_middleware_stack.extend(sub_app_middlewares);
+
+		// The sub app's names now lead to its routes under the base path
+		self.named_routes.extend(&base_path, &sub_app.named_routes);
+
+		// The sub app's routes get their context from it, and have their errors handled by it if it
+		// knows how to. Both with the sub app's own state
+		let context_generator = sub_app.context_generator;
+		let generator_state = state.clone();
+		let error_handler = sub_app.error_handler.map(|error_handler| {
+			Arc::new(StatefulErrorHandler {
+				handler: error_handler,
+				state,
+			}) as Arc<dyn BoundErrorHandler<TContext> + Send + Sync>
+		});
+		self.sub_app_stack.push(SubAppRoute::new(
+			&base_path,
+			Arc::new(move |mut request: Request| {
+				request.state = Some(generator_state.clone());
+				(context_generator)(request, &generator_state)
+			}),
+			error_handler.clone(),
+		));
+		// Its own sub apps fall back to its error handler when they don't have one
+		self.sub_app_stack.extend(
+			sub_app
+				.sub_app_stack
+				.into_handlers()
+				.into_iter()
+				.map(|route| {
+					SubAppRoute::new(
+						&format!("{}{}", base_path, route.mounted_url),
+						route.context_generator,
+						route.error_handler.or_else(|| error_handler.clone()),
+					)
+				}),
+		);
+
+		#[cfg(feature = "websocket")]
+		self.websocket_stack
+			.extend(
+				sub_app
+					.websocket_stack
+					.into_handlers()
+					.into_iter()
+					.map(|route| {
+						WebSocketRoute::new(
+							&format!("{}{}", base_path, route.mounted_url),
+							route.handler,
+						)
+					}),
+			);
+	}
 
-				formatted_base_path
+	pub async fn resolve(&self, context: TContext) -> Result<TContext, Error<TContext>> {
+		let method = context.get_method().clone();
+		let path = context.get_path();
+		let mut stack = self.get_middleware_stack(&method, &path);
+
+		// HEAD requests are handled by the GET endpoints, unless there's an explicit HEAD endpoint
+		if method == HttpMethod::Head && !has_endpoint(&stack) {
+			let get_stack = self.get_middleware_stack(&HttpMethod::Get, &path);
+			if has_endpoint(&get_stack) {
+				stack = get_stack;
 			}
+		}
+
+		#[cfg(feature = "websocket")]
+		let websocket = if method == HttpMethod::Get {
+			self.websocket_stack.get_matches(&path).into_iter().next()
+		} else {
+			None
+		};
+		#[cfg(feature = "websocket")]
+		let is_handled = has_endpoint(&stack) || websocket.is_some();
+		#[cfg(not(feature = "websocket"))]
+		let is_handled = has_endpoint(&stack);
+
+		// Only look at the other methods if nothing can handle this one
+		let allowed_methods = if is_handled {
+			vec![]
+		} else {
+			self.get_allowed_methods(&path)
 		};
 
-		self.get_stack
-			.extend(sub_app.get_stack.into_iter().map(|handler| {
-				MiddlewareHandler::new(
-					&format!("{}{}", base_path, handler.mounted_url),
-					handler.handler,
-					handler.is_endpoint,
-				)
-			}));
-
-		self.post_stack
-			.extend(sub_app.post_stack.into_iter().map(|handler| {
-				MiddlewareHandler::new(
-					&format!("{}{}", base_path, handler.mounted_url),
-					handler.handler,
-					handler.is_endpoint,
-				)
-			}));
-
-		self.put_stack
-			.extend(sub_app.put_stack.into_iter().map(|handler| {
-				MiddlewareHandler::new(
-					&format!("{}{}", base_path, handler.mounted_url),
-					handler.handler,
-					handler.is_endpoint,
-				)
-			}));
-
-		self.delete_stack
-			.extend(sub_app.delete_stack.into_iter().map(|handler| {
-				MiddlewareHandler::new(
-					&format!("{}{}", base_path, handler.mounted_url),
-					handler.handler,
-					handler.is_endpoint,
-				)
-			}));
-
-		self.head_stack
-			.extend(sub_app.head_stack.into_iter().map(|handler| {
-				MiddlewareHandler::new(
-					&format!("{}{}", base_path, handler.mounted_url),
-					handler.handler,
-					handler.is_endpoint,
-				)
-			}));
-
-		self.options_stack
-			.extend(sub_app.options_stack.into_iter().map(|handler| {
-				MiddlewareHandler::new(
-					&format!("{}{}", base_path, handler.mounted_url),
-					handler.handler,
-					handler.is_endpoint,
-				)
-			}));
-
-		self.connect_stack
-			.extend(sub_app.connect_stack.into_iter().map(|handler| {
-				MiddlewareHandler::new(
-					&format!("{}{}", base_path, handler.mounted_url),
-					handler.handler,
-					handler.is_endpoint,
-				)
-			}));
-
-		self.patch_stack
-			.extend(sub_app.patch_stack.into_iter().map(|handler| {
-				MiddlewareHandler::new(
-					&format!("{}{}", base_path, handler.mounted_url),
-					handler.handler,
-					handler.is_endpoint,
-				)
-			}));
-
-		self.trace_stack
-			.extend(sub_app.trace_stack.into_iter().map(|handler| {
-				MiddlewareHandler::new(
-					&format!("{}{}", base_path, handler.mounted_url),
-					handler.handler,
-					handler.is_endpoint,
-				)
-			}));
+		let is_method_implemented = match &method {
+			HttpMethod::Custom(method) => self.custom_stacks.contains_key(method),
+			_ => true,
+		};
+
+		let stack = MiddlewareStack {
+			nodes: stack,
+			allowed_methods,
+			is_method_implemented,
+			state: self.state.clone(),
+			#[cfg(feature = "websocket")]
+			websocket,
+		};
+		let mut context = chained_run(context, Arc::new(stack), 0).await?;
+		if method == HttpMethod::Head {
+			// Keep the headers (including the Content-Length), but never send a body
+			context.get_response_mut().take_body();
+		}
+		Ok(context)
 	}
 
-	pub async fn resolve(&self, context: TContext) -> Result<TContext, Error<TContext>> {
-		let stack = self.get_middleware_stack(context.get_method(), context.get_path());
-		chained_run(context, Arc::new(stack), 0).await
+	/// Returns the methods that have an endpoint registered for the given path
+	pub fn get_allowed_methods(&self, path: &str) -> Vec<HttpMethod> {
+		let mut allowed_methods = [
+			HttpMethod::Get,
+			HttpMethod::Post,
+			HttpMethod::Put,
+			HttpMethod::Delete,
+			HttpMethod::Head,
+			HttpMethod::Options,
+			HttpMethod::Connect,
+			HttpMethod::Patch,
+			HttpMethod::Trace,
+		]
+		.iter()
+		.filter(|method| self.has_endpoint(method, path))
+		.cloned()
+		.collect::<Vec<_>>();
+		allowed_methods.extend(
+			self.custom_stacks
+				.iter()
+				.filter(|(_, stack)| stack.has_endpoint(path))
+				.map(|(method, _)| HttpMethod::Custom(method.clone())),
+		);
+
+		if allowed_methods.is_empty() {
+			return allowed_methods;
+		}
+
+		// HEAD and OPTIONS are answered automatically for any route that exists
+		if self.get_stack.has_endpoint(path) && !allowed_methods.contains(&HttpMethod::Head) {
+			allowed_methods.push(HttpMethod::Head);
+		}
+		if !allowed_methods.contains(&HttpMethod::Options) {
+			allowed_methods.push(HttpMethod::Options);
+		}
+		allowed_methods
 	}
 
-	pub(crate) fn generate_context(&self, request: Request) -> TContext {
-		(self.context_generator)(request, self.get_state())
+	// Hands the error over to the error handler of the sub app that owns the route that failed,
+	// falling back to the app's own one
+	pub async fn handle_error(&self, request: Request, error: Error<TContext>) -> Response {
+		let mounted_handler = self
+			.get_sub_app(&request)
+			.and_then(|sub_app| sub_app.error_handler);
+		if let Some(mounted_handler) = mounted_handler {
+			mounted_handler.handle_error(request, error).await
+		} else if let Some(error_handler) = &self.error_handler {
+			error_handler
+				.handle_error(request, error, &self.state)
+				.await
+		} else {
+			DefaultErrorHandler
+				.handle_error(request, error, &self.state)
+				.await
+		}
+	}
+
+	// The sub app the request is meant for gets to generate the context, if there is one
+	pub(crate) fn generate_context(&self, mut request: Request) -> TContext {
+		if let Some(sub_app) = self.get_sub_app(&request) {
+			(sub_app.context_generator)(request)
+		} else {
+			request.state = Some(self.state.clone());
+			(self.context_generator)(request, self.get_state())
+		}
+	}
+
+	// The sub app that owns the endpoint the request goes to. When there's no such endpoint,
+	// it's the innermost sub app the path is under
+	fn get_sub_app(&self, request: &Request) -> Option<SubAppRoute<TContext>> {
+		let sub_apps = self.sub_app_stack.get_handlers();
+		if sub_apps.is_empty() {
+			return None;
+		}
+
+		let path = request.get_path();
+		let method = request.get_method();
+		let mut stack = self.get_middleware_stack(method, &path);
+		if method == &HttpMethod::Head && !has_endpoint(&stack) {
+			stack = self.get_middleware_stack(&HttpMethod::Get, &path);
+		}
+		match stack.into_iter().find(|route| route.handler.is_endpoint) {
+			Some(endpoint) => endpoint
+				.handler
+				.sub_app
+				.map(|index| sub_apps[index].clone()),
+			None => self
+				.sub_app_stack
+				.get_matches(&path)
+				.into_iter()
+				.max_by_key(|route| route.handler.segments.len())
+				.map(|route| route.handler),
+		}
 	}
 
 	fn get_middleware_stack(
 		&self,
 		method: &HttpMethod,
-		path: String,
-	) -> Vec<MiddlewareHandler<TContext, TMiddleware>> {
-		let mut stack: Vec<MiddlewareHandler<TContext, TMiddleware>> = vec![];
-		let route_stack = match method {
+		path: &str,
+	) -> Vec<MiddlewareMatch<TContext, TMiddleware>> {
+		self.get_route_stack(method).get_matches(path)
+	}
+
+	fn has_endpoint(&self, method: &HttpMethod, path: &str) -> bool {
+		#[cfg(feature = "websocket")]
+		{
+			if method == &HttpMethod::Get && self.websocket_stack.has_endpoint(path) {
+				return true;
+			}
+		}
+		self.get_route_stack(method).has_endpoint(path)
+	}
+
+	fn get_method_stacks(&self) -> Vec<(HttpMethod, &MiddlewareRouter<TContext, TMiddleware>)> {
+		let mut stacks = vec![
+			(HttpMethod::Get, &self.get_stack),
+			(HttpMethod::Post, &self.post_stack),
+			(HttpMethod::Put, &self.put_stack),
+			(HttpMethod::Delete, &self.delete_stack),
+			(HttpMethod::Head, &self.head_stack),
+			(HttpMethod::Options, &self.options_stack),
+			(HttpMethod::Connect, &self.connect_stack),
+			(HttpMethod::Patch, &self.patch_stack),
+			(HttpMethod::Trace, &self.trace_stack),
+		];
+		stacks.extend(
+			self.custom_stacks
+				.iter()
+				.map(|(method, stack)| (HttpMethod::Custom(method.clone()), stack)),
+		);
+		stacks
+	}
+
+	fn get_route_stack(&self, method: &HttpMethod) -> &MiddlewareRouter<TContext, TMiddleware> {
+		match method {
 			HttpMethod::Get => &self.get_stack,
 			HttpMethod::Post => &self.post_stack,
 			HttpMethod::Put => &self.put_stack,
@@ -345,21 +893,120 @@ where
 			HttpMethod::Connect => &self.connect_stack,
 			HttpMethod::Patch => &self.patch_stack,
 			HttpMethod::Trace => &self.trace_stack,
-		};
-		for handler in route_stack {
-			if handler.is_match(&path) {
-				stack.push(handler.clone());
+			// Unknown methods still go through the app's middlewares
+			HttpMethod::Custom(method) => self
+				.custom_stacks
+				.get(method)
+				.unwrap_or(&self.use_middleware_stack),
+		}
+	}
+
+	fn get_route_stack_mut(
+		&mut self,
+		method: &HttpMethod,
+	) -> &mut MiddlewareRouter<TContext, TMiddleware> {
+		match method {
+			HttpMethod::Get => &mut self.get_stack,
+			HttpMethod::Post => &mut self.post_stack,
+			HttpMethod::Put => &mut self.put_stack,
+			HttpMethod::Delete => &mut self.delete_stack,
+			HttpMethod::Head => &mut self.head_stack,
+			HttpMethod::Options => &mut self.options_stack,
+			HttpMethod::Connect => &mut self.connect_stack,
+			HttpMethod::Patch => &mut self.patch_stack,
+			HttpMethod::Trace => &mut self.trace_stack,
+			HttpMethod::Custom(method) => {
+				let use_middleware_stack = &self.use_middleware_stack;
+				self.custom_stacks
+					.entry(method.clone())
+					.or_insert_with(|| use_middleware_stack.clone())
 			}
 		}
-		stack
 	}
 }
 
+// Makes sure the base path begins with a / and doesn't end with one, so that the paths
+// mounted under it can just be appended. The root becomes an empty string
+pub(crate) fn format_base_path(base_path: &str) -> String {
+	// If it ends with /, remove it
+	let base_path = base_path.strip_suffix('/').unwrap_or(base_path);
+
+	// If it doesn't begin with a /, add it
+	if base_path.is_empty() || base_path.starts_with('/') {
+		base_path.to_string()
+	} else {
+		format!("/{}", base_path)
+	}
+}
+
+// Moves the handlers of a sub app under its base path. Handlers that came from
+// a sub app of the sub app keep their own state
+fn mount_handlers<TContext, TMiddleware>(
+	base_path: &str,
+	stack: MiddlewareRouter<TContext, TMiddleware>,
+	state: &Arc<dyn Any + Send + Sync>,
+	sub_app: usize,
+) -> Vec<MiddlewareHandler<TContext, TMiddleware>>
+where
+	TContext: Context + Debug + Send + Sync,
+	TMiddleware: Middleware<TContext> + Clone + Send + Sync,
+{
+	stack
+		.into_handlers()
+		.into_iter()
+		.map(|handler| {
+			let mut mounted = MiddlewareHandler::new(
+				&format!("{}{}", base_path, handler.mounted_url),
+				handler.handler,
+				handler.is_endpoint,
+			);
+			mounted.is_group_middleware = handler.is_group_middleware;
+			mounted.state = handler.state.or_else(|| Some(state.clone()));
+			mounted.name = handler.name.map(|name| get_mounted_name(base_path, &name));
+			// The sub apps of the sub app are mounted right after it, in the same order
+			mounted.sub_app = Some(
+				handler
+					.sub_app
+					.map_or(sub_app, |nested| sub_app + 1 + nested),
+			);
+			mounted
+		})
+		.collect()
+}
+
+fn get_route_info<TContext, TMiddleware>(
+	method: Option<HttpMethod>,
+	handler: &MiddlewareHandler<TContext, TMiddleware>,
+	sub_apps: &[SubAppRoute<TContext>],
+) -> RouteInfo
+where
+	TContext: Context + Debug + Send + Sync,
+	TMiddleware: Middleware<TContext> + Clone + Send + Sync,
+{
+	RouteInfo {
+		method,
+		path: handler.mounted_url.clone(),
+		is_endpoint: handler.is_endpoint && !handler.is_group_middleware,
+		name: handler.name.clone(),
+		sub_app: handler
+			.sub_app
+			.map(|index| sub_apps[index].mounted_url.clone()),
+	}
+}
+
+fn has_endpoint<TContext, TMiddleware>(stack: &[MiddlewareMatch<TContext, TMiddleware>]) -> bool
+where
+	TContext: Context + Debug + Send + Sync,
+	TMiddleware: Middleware<TContext> + Clone + Send + Sync,
+{
+	stack.iter().any(|node| node.handler.is_endpoint)
+}
+
 impl<TContext, TMiddleware, TState> Default for App<TContext, TMiddleware, TState>
 where
 	TContext: 'static + Context + Default + Debug + Send + Sync,
 	TMiddleware: 'static + Middleware<TContext> + Clone + Send + Sync,
-	TState: Default + Send + Sync,
+	TState: 'static + Default + Send + Sync,
 {
 	fn default() -> Self {
 		Self::create(|_, _| TContext::default(), TState::default())
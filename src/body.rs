@@ -0,0 +1,174 @@
+use futures::{
+	future::ready,
+	stream::{self, Stream},
+};
+use hyper::{body::Bytes, Body};
+use std::{
+	error::Error as StdError,
+	fmt::{Debug, Display, Formatter, Result as FmtResult},
+	io::Error as IoError,
+	pin::Pin,
+	str::Utf8Error,
+	sync::{Arc, Mutex},
+	task::{Context as TaskContext, Poll},
+};
+use tokio::{
+	fs::File,
+	io::{stream_reader, AsyncReadExt, StreamReader},
+};
+
+pub type BodyReader = StreamReader<BodyStream, Bytes>;
+
+// Why the body of a request couldn't be loaded, or given as a string
+#[derive(Debug)]
+pub enum BodyError {
+	// Request::load_body has to be awaited first
+	NotLoaded,
+	// The body stream was taken before load_body could read it
+	StreamTaken,
+	InvalidUtf8(Utf8Error),
+	Read(IoError),
+}
+
+impl Display for BodyError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		match self {
+			BodyError::NotLoaded => write!(f, "The body of the request hasn't been loaded"),
+			BodyError::StreamTaken => {
+				write!(f, "The body of the request was already taken as a stream")
+			}
+			BodyError::InvalidUtf8(err) => {
+				write!(f, "The body of the request isn't utf-8: {}", err)
+			}
+			BodyError::Read(err) => write!(f, "The body of the request couldn't be read: {}", err),
+		}
+	}
+}
+
+impl StdError for BodyError {}
+
+impl From<Utf8Error> for BodyError {
+	fn from(err: Utf8Error) -> Self {
+		BodyError::InvalidUtf8(err)
+	}
+}
+
+impl From<IoError> for BodyError {
+	fn from(err: IoError) -> Self {
+		BodyError::Read(err)
+	}
+}
+
+// The body of a request, read chunk by chunk as it arrives from the client
+pub struct BodyStream {
+	body: Body,
+}
+
+impl BodyStream {
+	pub(crate) fn new(body: Body) -> Self {
+		BodyStream { body }
+	}
+
+	pub fn into_reader(self) -> BodyReader {
+		stream_reader(self)
+	}
+
+	#[cfg(feature = "websocket")]
+	pub(crate) fn into_inner(self) -> Body {
+		self.body
+	}
+}
+
+impl Stream for BodyStream {
+	type Item = Result<Bytes, IoError>;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+		Pin::new(&mut self.body)
+			.poll_next(cx)
+			.map(|chunk| chunk.map(|chunk| chunk.map_err(IoError::other)))
+	}
+}
+
+pub type ChunkStream = Pin<Box<dyn Stream<Item = Result<Bytes, IoError>> + Send>>;
+
+const FILE_CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Clone)]
+pub enum ResponseBody {
+	Bytes(Vec<u8>),
+	Stream(SharedBody<ChunkStream>),
+	File(SharedBody<File>),
+}
+
+impl ResponseBody {
+	pub fn is_empty(&self) -> bool {
+		match self {
+			ResponseBody::Bytes(bytes) => bytes.is_empty(),
+			_ => false,
+		}
+	}
+
+	// Turns any kind of body into a stream of chunks. A stream or a file
+	// that has already been sent by a clone of this body is empty
+	pub fn into_stream(self) -> ChunkStream {
+		match self {
+			ResponseBody::Bytes(bytes) => Box::pin(stream::once(ready(Ok(Bytes::from(bytes))))),
+			ResponseBody::Stream(body_stream) => body_stream
+				.take()
+				.unwrap_or_else(|| Box::pin(stream::empty())),
+			ResponseBody::File(file) => {
+				if let Some(file) = file.take() {
+					Box::pin(stream::unfold(Some(file), |file| async move {
+						let mut file = file?;
+						let mut buffer = vec![0; FILE_CHUNK_SIZE];
+						match file.read(&mut buffer).await {
+							Ok(0) => None,
+							Ok(read) => {
+								buffer.truncate(read);
+								Some((Ok(Bytes::from(buffer)), Some(file)))
+							}
+							Err(err) => Some((Err(err), None)),
+						}
+					}))
+				} else {
+					Box::pin(stream::empty())
+				}
+			}
+		}
+	}
+}
+
+impl Default for ResponseBody {
+	fn default() -> Self {
+		ResponseBody::Bytes(vec![])
+	}
+}
+
+impl Debug for ResponseBody {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		match self {
+			ResponseBody::Bytes(bytes) => f.debug_tuple("Bytes").field(bytes).finish(),
+			ResponseBody::Stream(_) => write!(f, "Stream"),
+			ResponseBody::File(_) => write!(f, "File"),
+		}
+	}
+}
+
+// Streams and files can only be sent once, so clones of a response share the same one
+pub struct SharedBody<T>(Arc<Mutex<Option<T>>>);
+
+impl<T> SharedBody<T> {
+	pub(crate) fn new(body: T) -> Self {
+		SharedBody(Arc::new(Mutex::new(Some(body))))
+	}
+
+	pub fn take(&self) -> Option<T> {
+		self.0.lock().ok()?.take()
+	}
+}
+
+impl<T> Clone for SharedBody<T> {
+	fn clone(&self) -> Self {
+		SharedBody(self.0.clone())
+	}
+}
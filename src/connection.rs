@@ -0,0 +1,219 @@
+use crate::listener::ConnectionIo;
+use futures::{
+	channel::{mpsc, oneshot},
+	future::Shared,
+	task::{Context as TaskContext, Poll},
+	Future,
+};
+use std::{
+	io::{Error as IoError, ErrorKind},
+	mem::MaybeUninit,
+	pin::Pin,
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+use tokio::{
+	io::{AsyncRead, AsyncWrite},
+	sync::OwnedSemaphorePermit,
+	time::{delay_until, Delay, Instant},
+};
+
+struct Activity {
+	// Requests that haven't been completely responded to yet
+	in_flight: usize,
+	idle_since: Instant,
+	// When the first bytes of the next request came in
+	head_started: Option<Instant>,
+	// HTTP/2 clients send frames of their own even when idle, so they can't be told apart from
+	// the start of a request. Only the keep alive timeout applies to them
+	http2: bool,
+	upgraded: bool,
+}
+
+// Keeps track of what a connection is doing, to know which of the timeouts applies to it
+#[derive(Clone)]
+pub(crate) struct ConnectionTracker {
+	activity: Arc<Mutex<Activity>>,
+}
+
+impl ConnectionTracker {
+	pub(crate) fn new() -> Self {
+		ConnectionTracker {
+			activity: Arc::new(Mutex::new(Activity {
+				in_flight: 0,
+				idle_since: Instant::now(),
+				head_started: None,
+				http2: false,
+				upgraded: false,
+			})),
+		}
+	}
+
+	// The connection is busy until the returned guard is dropped
+	pub(crate) fn start_request(&self, http2: bool) -> RequestGuard {
+		if let Ok(mut activity) = self.activity.lock() {
+			activity.in_flight += 1;
+			activity.head_started = None;
+			activity.http2 = http2;
+		}
+		RequestGuard {
+			tracker: self.clone(),
+		}
+	}
+
+	// Once a connection has been upgraded, it's no longer speaking HTTP, so the timeouts don't apply
+	pub(crate) fn set_upgraded(&self) {
+		if let Ok(mut activity) = self.activity.lock() {
+			activity.upgraded = true;
+		}
+	}
+
+	fn on_read(&self) {
+		if let Ok(mut activity) = self.activity.lock() {
+			if activity.in_flight == 0 && activity.head_started.is_none() && !activity.http2 {
+				activity.head_started = Some(Instant::now());
+			}
+		}
+	}
+
+	fn get_deadline(
+		&self,
+		header_read_timeout: Option<Duration>,
+		keep_alive_timeout: Option<Duration>,
+	) -> Option<Instant> {
+		let activity = self.activity.lock().ok()?;
+		if activity.upgraded || activity.in_flight > 0 {
+			None
+		} else if let Some(head_started) = activity.head_started {
+			Some(head_started + header_read_timeout?)
+		} else {
+			Some(activity.idle_since + keep_alive_timeout?)
+		}
+	}
+}
+
+pub(crate) struct RequestGuard {
+	tracker: ConnectionTracker,
+}
+
+impl Drop for RequestGuard {
+	fn drop(&mut self) {
+		if let Ok(mut activity) = self.tracker.activity.lock() {
+			activity.in_flight -= 1;
+			if activity.in_flight == 0 {
+				activity.idle_since = Instant::now();
+			}
+		}
+	}
+}
+
+// Keeps the server from finishing its drain while it's around, for whatever outlives the
+// connection it came from, like the task of an upgraded websocket
+#[derive(Clone)]
+pub(crate) struct DrainGuard {
+	_drain: mpsc::Sender<()>,
+	#[cfg_attr(not(feature = "websocket"), allow(dead_code))]
+	abort: Shared<oneshot::Receiver<()>>,
+}
+
+impl DrainGuard {
+	pub(crate) fn new(drain: mpsc::Sender<()>, abort: Shared<oneshot::Receiver<()>>) -> Self {
+		DrainGuard {
+			_drain: drain,
+			abort,
+		}
+	}
+
+	// Resolves once the drain timeout is up, and whatever is left has to stop
+	#[cfg(feature = "websocket")]
+	pub(crate) async fn aborted(&self) {
+		let _ = self.abort.clone().await;
+	}
+}
+
+// An accepted connection, ready to be served. Reading from it fails once
+// the client has been idle, or has been sending headers, for too long
+pub(crate) struct Connection {
+	io: ConnectionIo,
+	tracker: ConnectionTracker,
+	header_read_timeout: Option<Duration>,
+	keep_alive_timeout: Option<Duration>,
+	timer: Option<Delay>,
+	// Counts towards the maximum number of connections, for as long as the connection is open
+	_permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Connection {
+	pub(crate) fn new(
+		io: ConnectionIo,
+		tracker: ConnectionTracker,
+		header_read_timeout: Option<Duration>,
+		keep_alive_timeout: Option<Duration>,
+		permit: Option<OwnedSemaphorePermit>,
+	) -> Self {
+		Connection {
+			io,
+			tracker,
+			header_read_timeout,
+			keep_alive_timeout,
+			timer: None,
+			_permit: permit,
+		}
+	}
+}
+
+impl AsyncRead for Connection {
+	unsafe fn prepare_uninitialized_buffer(&self, buf: &mut [MaybeUninit<u8>]) -> bool {
+		self.io.prepare_uninitialized_buffer(buf)
+	}
+
+	fn poll_read(
+		self: Pin<&mut Self>,
+		cx: &mut TaskContext<'_>,
+		buf: &mut [u8],
+	) -> Poll<Result<usize, IoError>> {
+		let this = self.get_mut();
+
+		let deadline = this
+			.tracker
+			.get_deadline(this.header_read_timeout, this.keep_alive_timeout);
+		if let Some(deadline) = deadline {
+			let timer = this.timer.get_or_insert_with(|| delay_until(deadline));
+			if timer.deadline() != deadline {
+				timer.reset(deadline);
+			}
+			if Pin::new(timer).poll(cx).is_ready() {
+				return Poll::Ready(Err(IoError::new(
+					ErrorKind::TimedOut,
+					"the connection timed out",
+				)));
+			}
+		}
+
+		let result = Pin::new(&mut this.io).poll_read(cx, buf);
+		if let Poll::Ready(Ok(read)) = result {
+			if read > 0 {
+				this.tracker.on_read();
+			}
+		}
+		result
+	}
+}
+
+impl AsyncWrite for Connection {
+	fn poll_write(
+		self: Pin<&mut Self>,
+		cx: &mut TaskContext<'_>,
+		buf: &[u8],
+	) -> Poll<Result<usize, IoError>> {
+		Pin::new(&mut self.get_mut().io).poll_write(cx, buf)
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Result<(), IoError>> {
+		Pin::new(&mut self.get_mut().io).poll_flush(cx)
+	}
+
+	fn poll_shutdown(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Result<(), IoError>> {
+		Pin::new(&mut self.get_mut().io).poll_shutdown(cx)
+	}
+}
@@ -1,10 +1,21 @@
-use crate::{cookie::Cookie, request::Request, response::Response, HttpMethod};
+use crate::{
+	body::{BodyError, BodyReader, BodyStream},
+	cookie::Cookie,
+	error::Error,
+	extensions::Extensions,
+	listener::RemoteAddr,
+	request::Request,
+	response::Response,
+	sse::{self, SseSender, DEFAULT_KEEP_ALIVE_INTERVAL},
+	HttpMethod,
+};
 
+use futures::Stream;
+use hyper::body::Bytes;
+use serde::de::DeserializeOwned;
 use serde_json::Value;
-use std::{
-	net::IpAddr,
-	str::{self, Utf8Error},
-};
+use std::{fmt::Debug, io::Error as IoError, net::IpAddr, time::Duration};
+use tokio::fs::File;
 
 pub trait Context {
 	fn get_request(&self) -> &Request;
@@ -13,9 +24,15 @@ pub trait Context {
 	fn take_response(self) -> Response;
 	fn get_response_mut(&mut self) -> &mut Response;
 
-	fn get_body(&self) -> Result<String, Utf8Error> {
+	fn get_body(&self) -> Result<String, BodyError> {
 		self.get_request().get_body()
 	}
+	fn take_body_stream(&mut self) -> Option<BodyStream> {
+		self.get_request_mut().take_body_stream()
+	}
+	fn get_body_reader(&mut self) -> Option<BodyReader> {
+		self.get_request_mut().get_body_reader()
+	}
 	fn json(&mut self, body: Value) -> &mut Self {
 		self.content_type("application/json")
 			.body(&body.to_string())
@@ -28,6 +45,31 @@ pub trait Context {
 		self.get_response_mut().set_body_bytes(bytes);
 		self
 	}
+	fn stream<TStream>(&mut self, stream: TStream) -> &mut Self
+	where
+		TStream: 'static + Stream<Item = Result<Bytes, IoError>> + Send,
+	{
+		self.get_response_mut().set_body_stream(stream);
+		self
+	}
+	fn file(&mut self, file: File, length: Option<u64>) -> &mut Self {
+		self.get_response_mut().set_body_file(file, length);
+		self
+	}
+	fn sse(&mut self) -> SseSender {
+		self.sse_with_keep_alive(Some(DEFAULT_KEEP_ALIVE_INTERVAL))
+	}
+	fn sse_with_keep_alive(&mut self, keep_alive: Option<Duration>) -> SseSender {
+		let (sender, stream) = sse::event_stream(keep_alive);
+		self.content_type("text/event-stream")
+			.header("Cache-Control", "no-cache")
+			.stream(stream);
+		sender
+	}
+	// The id of the last event the client received, if it's reconnecting to an event stream
+	fn get_last_event_id(&self) -> Option<String> {
+		self.get_request().get_header("Last-Event-ID")
+	}
 
 	fn get_method(&self) -> &HttpMethod {
 		self.get_request().get_method()
@@ -80,6 +122,32 @@ pub trait Context {
 		self.get_request().get_query_string()
 	}
 
+	fn get_query_all(&self, key: &str) -> Vec<&str> {
+		self.get_request().get_query_all(key)
+	}
+
+	// The query string parsed into the given type. Fails with a 400 if it doesn't fit
+	fn query<TQuery>(&self) -> Result<TQuery, Error<Self>>
+	where
+		Self: Sized + Debug + Send + Sync,
+		TQuery: DeserializeOwned,
+	{
+		self.get_request()
+			.get_query_as()
+			.map_err(|err| Error::bad_request(&format!("Invalid query string: {}", err)))
+	}
+
+	// The route's params parsed into the given type. Fails with a 400 if they don't fit
+	fn params<TParams>(&self) -> Result<TParams, Error<Self>>
+	where
+		Self: Sized + Debug + Send + Sync,
+		TParams: DeserializeOwned,
+	{
+		self.get_request()
+			.get_params_as()
+			.map_err(|err| Error::bad_request(&format!("Invalid route params: {}", err)))
+	}
+
 	fn get_host(&self) -> String {
 		self.get_request().get_host()
 	}
@@ -104,16 +172,35 @@ pub trait Context {
 		self.get_request().is_secure()
 	}
 
-	fn get_ip(&self) -> IpAddr {
+	fn get_remote_addr(&self) -> &RemoteAddr {
+		self.get_request().get_remote_addr()
+	}
+
+	fn get_ip(&self) -> Option<IpAddr> {
 		self.get_request().get_ip()
 	}
 
+	fn get_extensions(&self) -> &Extensions {
+		self.get_request().get_extensions()
+	}
+	fn get_extensions_mut(&mut self) -> &mut Extensions {
+		self.get_request_mut().get_extensions_mut()
+	}
+
+	fn state<TState>(&self) -> Option<&TState>
+	where
+		TState: 'static,
+	{
+		self.get_request().get_state()
+	}
+
 	fn is(&self, mimes: &[&str]) -> bool {
 		self.get_request().is(mimes)
 	}
 
-	// TODO content negotiation
-	// See: https://koajs.com/#request content negotiation
+	fn accepts<'a>(&self, mimes: &[&'a str]) -> Option<&'a str> {
+		self.get_request().accepts(mimes)
+	}
 
 	fn get_header(&self, key: &str) -> Option<String> {
 		self.get_request().get_header(key)
@@ -157,23 +244,22 @@ pub trait Context {
 pub struct DefaultContext {
 	request: Request,
 	response: Response,
-	body: Option<Value>,
 }
 
 impl DefaultContext {
+	// The parsed body is kept in the request's extensions, see the default body parsers
 	pub fn get_body_object(&self) -> Option<&Value> {
-		self.body.as_ref()
+		self.request.get_extensions().get::<Value>()
 	}
 
 	pub fn set_body_object(&mut self, body: Value) {
-		self.body = Some(body);
+		self.request.get_extensions_mut().insert(body);
 	}
 
 	pub fn new(request: Request) -> Self {
 		DefaultContext {
 			request,
 			response: Default::default(),
-			body: None,
 		}
 	}
 }
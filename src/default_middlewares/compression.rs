@@ -1,20 +1,60 @@
-use crate::{Context, DefaultMiddleware};
-use flate2::{Compress, Compression, FlushCompress, GzBuilder, Status};
-use std::{fmt::Debug, io::prelude::*};
+use crate::{ChunkStream, Context, DefaultMiddleware, ResponseBody};
+use flate2::{
+	write::{GzEncoder, ZlibEncoder},
+	Compression,
+};
+use futures::stream::{self, StreamExt};
+use hyper::body::Bytes;
+use std::{
+	fmt::Debug,
+	io::{prelude::*, Error as IoError},
+};
 
 pub const DEFAULT_COMPRESSION_LEVEL: u32 = 6;
 
 pub struct CompressionHandler {
-	zlib_compressor: Compress,
 	compression_level: Compression,
 }
 
+enum Encoder {
+	Gzip(GzEncoder<Vec<u8>>),
+	Deflate(ZlibEncoder<Vec<u8>>),
+}
+
+impl Encoder {
+	fn write(&mut self, data: &[u8]) -> Result<(), IoError> {
+		match self {
+			Encoder::Gzip(encoder) => encoder.write_all(data),
+			Encoder::Deflate(encoder) => encoder.write_all(data),
+		}
+	}
+
+	// Flushes whatever has been compressed so far, so that streamed chunks aren't held back
+	fn take_output(&mut self) -> Result<Vec<u8>, IoError> {
+		match self {
+			Encoder::Gzip(encoder) => {
+				encoder.flush()?;
+				Ok(std::mem::take(encoder.get_mut()))
+			}
+			Encoder::Deflate(encoder) => {
+				encoder.flush()?;
+				Ok(std::mem::take(encoder.get_mut()))
+			}
+		}
+	}
+
+	fn finish(self) -> Result<Vec<u8>, IoError> {
+		match self {
+			Encoder::Gzip(encoder) => encoder.finish(),
+			Encoder::Deflate(encoder) => encoder.finish(),
+		}
+	}
+}
+
 impl CompressionHandler {
 	pub fn create(compression_level: u32) -> CompressionHandler {
-		let compression_level = Compression::new(compression_level);
 		CompressionHandler {
-			zlib_compressor: Compress::new(compression_level, false),
-			compression_level,
+			compression_level: Compression::new(compression_level),
 		}
 	}
 
@@ -25,38 +65,70 @@ impl CompressionHandler {
 		let allowed_encodings = context
 			.get_request()
 			.get_header("Accept-Encoding")
-			.unwrap_or_else(String::new);
+			.unwrap_or_default();
 		let allowed_encodings = allowed_encodings
 			.split(',')
 			.map(str::trim)
 			.collect::<Vec<&str>>();
 
-		if allowed_encodings.contains(&"gzip") {
-			let data = context.get_response().get_body();
-			let mut output = vec![];
-			let result = GzBuilder::new()
-				.buf_read(data.as_ref(), self.compression_level)
-				.read_to_end(&mut output);
-			if result.is_ok() {
-				context
-					.body_bytes(&output)
-					.header("Content-Encoding", "gzip");
-			}
+		let (mut encoder, encoding) = if allowed_encodings.contains(&"gzip") {
+			(
+				Encoder::Gzip(GzEncoder::new(vec![], self.compression_level)),
+				"gzip",
+			)
 		} else if allowed_encodings.contains(&"deflate") {
-			let data = context.get_response().get_body();
-			let mut output = [];
-			if let Ok(Status::Ok) =
-				self.zlib_compressor
-					.compress(&data, &mut output, FlushCompress::None)
-			{
+			(
+				Encoder::Deflate(ZlibEncoder::new(vec![], self.compression_level)),
+				"deflate",
+			)
+		} else {
+			return;
+		};
+
+		match context.get_response_mut().take_body() {
+			ResponseBody::Bytes(data) => {
+				let output = encoder.write(&data).and_then(|_| encoder.finish());
+				if let Ok(output) = output {
+					context
+						.body_bytes(&output)
+						.header("Content-Encoding", encoding);
+				} else {
+					context.body_bytes(&data);
+				}
+			}
+			body => {
 				context
-					.body_bytes(&output)
-					.header("Content-Encoding", "deflate");
+					.stream(compress_stream(body.into_stream(), encoder))
+					.header("Content-Encoding", encoding);
 			}
 		}
 	}
 }
 
+fn compress_stream(body: ChunkStream, encoder: Encoder) -> ChunkStream {
+	Box::pin(stream::unfold(
+		(body, Some(encoder)),
+		|(mut body, encoder)| async move {
+			let mut encoder = encoder?;
+			loop {
+				match body.next().await {
+					Some(Ok(chunk)) => {
+						match encoder.write(&chunk).and_then(|_| encoder.take_output()) {
+							Ok(output) if output.is_empty() => continue,
+							Ok(output) => {
+								return Some((Ok(Bytes::from(output)), (body, Some(encoder))))
+							}
+							Err(err) => return Some((Err(err), (body, None))),
+						}
+					}
+					Some(Err(err)) => return Some((Err(err), (body, None))),
+					None => return Some((encoder.finish().map(Bytes::from), (body, None))),
+				}
+			}
+		},
+	))
+}
+
 pub fn compression() -> CompressionHandler {
 	CompressionHandler::create(DEFAULT_COMPRESSION_LEVEL)
 }
@@ -67,7 +139,7 @@ pub fn compression_with_level(compression_level: u32) -> CompressionHandler {
 
 pub fn default_compression<TData>() -> DefaultMiddleware<TData>
 where
-	TData: Default + Clone + Send + Sync,
+	TData: 'static + Default + Clone + Send + Sync,
 {
 	DefaultMiddleware::new(|mut context, next| {
 		Box::pin(async move {
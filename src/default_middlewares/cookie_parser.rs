@@ -73,7 +73,7 @@ where
 
 pub fn default_parser<TData>() -> DefaultMiddleware<TData>
 where
-	TData: Default + Clone + Send + Sync,
+	TData: 'static + Default + Clone + Send + Sync,
 {
 	DefaultMiddleware::new(|mut context, next| {
 		Box::pin(async move {
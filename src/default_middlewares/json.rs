@@ -20,14 +20,18 @@ where
 
 pub fn default_parser<TData>() -> DefaultMiddleware<TData>
 where
-	TData: Default + Clone + Send + Sync,
+	TData: 'static + Default + Clone + Send + Sync,
 {
 	DefaultMiddleware::new(|mut context, next| {
 		Box::pin(async move {
+			// Other bodies are left alone, so that they can still be streamed
+			if context.is(&["application/json"]) {
+				context.get_request_mut().load_body().await?;
+			}
 			let json = parser(&context)?;
 
 			if let Some(json) = json {
-				context.set_body_object(json);
+				context.get_extensions_mut().insert(json);
 			}
 
 			next(context).await
@@ -47,30 +47,32 @@ where
 		}
 		let elapsed_time = Instant::now().duration_since(self.measurer.unwrap());
 
-		if (self.should_skip)(&context) {
+		if (self.should_skip)(context) {
 			return None;
 		}
 		let reqs = self
 			.log_format
 			.match_indices(":req[")
-			.into_iter()
 			.filter_map(|(index, _)| {
 				let header_end_index = self.log_format[index..].chars().position(|c| c == ']')?;
 				let header_name = &self.log_format[(index + 5)..header_end_index];
-				Some((header_name.to_string(), context.get_header(&header_name)?))
+				Some((header_name.to_string(), context.get_header(header_name)?))
 			})
 			.collect::<Vec<(String, String)>>();
 
 		let ress = self
 			.log_format
 			.match_indices(":res[")
-			.into_iter()
 			.filter_map(|(index, _)| {
 				let header_end_index = self.log_format[index..].chars().position(|c| c == ']')?;
 				let header_name = &self.log_format[(index + 5)..(index + header_end_index)];
+				// Streamed responses might not have headers like the content-length
 				Some((
 					header_name.to_string(),
-					context.get_response().get_header(&header_name)?,
+					context
+						.get_response()
+						.get_header(header_name)
+						.unwrap_or_else(|| "-".to_string()),
 				))
 			})
 			.collect::<Vec<(String, String)>>();
@@ -96,9 +98,15 @@ where
 				":referrer",
 				&context
 					.get_header("Referer")
-					.unwrap_or_else(|| context.get_header("Referrer").unwrap_or_else(String::new)),
+					.unwrap_or_else(|| context.get_header("Referrer").unwrap_or_default()),
+			)
+			.replace(
+				":remote-addr",
+				&context
+					.get_ip()
+					.map(|ip| ip.to_string())
+					.unwrap_or_else(|| context.get_remote_addr().to_string()),
 			)
-			.replace(":remote-addr", &context.get_ip().to_string())
 			.replace(
 				":response-time",
 				&if elapsed_time.as_millis() > 0 {
@@ -121,7 +129,7 @@ where
 			.replace(":url", &context.get_path())
 			.replace(
 				":user-agent",
-				&context.get_header("User-Agent").unwrap_or_else(String::new),
+				&context.get_header("User-Agent").unwrap_or_default(),
 			)
 			.replace(
 				":content-length",
@@ -27,12 +27,13 @@ impl StaticFileServer {
 		TContext: Context + Debug + Send + Sync,
 	{
 		let file_location = format!("{}{}", self.folder_path, context.get_path());
-		if is_file(&file_location).await {
-			let content = fs::read(file_location).await?;
-			context.body_bytes(&content);
-			Ok(context)
-		} else {
-			next(context).await
+		match fs::metadata(&file_location).await {
+			Ok(metadata) if metadata.is_file() => {
+				let file = fs::File::open(file_location).await?;
+				context.file(file, Some(metadata.len()));
+				Ok(context)
+			}
+			_ => next(context).await,
 		}
 	}
 }
@@ -54,11 +55,3 @@ where
 pub fn static_server(folder_path: &str) -> StaticFileServer {
 	StaticFileServer::create(folder_path)
 }
-
-async fn is_file(path: &str) -> bool {
-	let metadata = fs::metadata(path).await;
-	if metadata.is_err() {
-		return false;
-	}
-	metadata.unwrap().is_file()
-}
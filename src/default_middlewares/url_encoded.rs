@@ -19,14 +19,18 @@ where
 
 pub fn default_parser<TData>() -> DefaultMiddleware<TData>
 where
-	TData: Default + Send + Clone + Sync,
+	TData: 'static + Default + Send + Clone + Sync,
 {
 	DefaultMiddleware::new(|mut context, next| {
 		Box::pin(async move {
+			// Other bodies are left alone, so that they can still be streamed
+			if context.is(&["application/x-www-form-urlencoded"]) {
+				context.get_request_mut().load_body().await?;
+			}
 			let json = parser(&context)?;
 
 			if let Some(json) = json {
-				context.set_body_object(json);
+				context.get_extensions_mut().insert(json);
 			}
 
 			next(context).await
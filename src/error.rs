@@ -1,6 +1,6 @@
-use std::{error::Error as StdError, fmt::Debug};
+use std::{any::Any, error::Error as StdError, fmt::Debug};
 
-use crate::Context;
+use crate::{Context, HttpError, Response};
 
 #[derive(Debug)]
 pub struct Error<TContext>
@@ -31,9 +31,94 @@ where
 		}
 	}
 
+	pub fn from_http_error(context: Option<TContext>, error: HttpError) -> Self {
+		Error {
+			context,
+			message: error.to_string(),
+			status: error.get_status(),
+			error: Box::new(error),
+		}
+	}
+
+	pub fn bad_request(detail: &str) -> Self {
+		Error::from_http_error(
+			None,
+			HttpError::BadRequest {
+				detail: Some(detail.to_string()),
+				details: None,
+			},
+		)
+	}
+
+	pub fn unauthorized(challenge: &str) -> Self {
+		Error::from_http_error(
+			None,
+			HttpError::Unauthorized {
+				challenge: challenge.to_string(),
+				detail: None,
+			},
+		)
+	}
+
+	pub fn forbidden() -> Self {
+		Error::from_http_error(None, HttpError::Forbidden { detail: None })
+	}
+
+	pub fn not_found() -> Self {
+		Error::from_http_error(None, HttpError::NotFound { detail: None })
+	}
+
+	pub fn conflict(detail: &str) -> Self {
+		Error::from_http_error(
+			None,
+			HttpError::Conflict {
+				detail: Some(detail.to_string()),
+			},
+		)
+	}
+
+	pub fn internal_server_error() -> Self {
+		Error::from_http_error(None, HttpError::InternalServerError { detail: None })
+	}
+
+	// Keeps the response as it is so far, so that its headers are still sent with the error
+	pub fn with_context(mut self, context: TContext) -> Self {
+		self.context = Some(context);
+		self
+	}
+
 	pub fn get_context(&mut self) -> Option<&mut TContext> {
 		self.context.as_mut()
 	}
+
+	pub fn get_message(&self) -> &str {
+		&self.message
+	}
+
+	pub fn get_status(&self) -> u16 {
+		self.status
+	}
+
+	pub fn get_error(&self) -> &(dyn StdError + Send) {
+		self.error.as_ref()
+	}
+
+	pub fn get_http_error(&self) -> Option<&HttpError> {
+		self.error.downcast_ref::<HttpError>()
+	}
+
+	// The response as it was when the error happened, with the error's status. Only the headers
+	// are kept, since whatever body was there is probably incomplete
+	pub fn take_response(&mut self) -> Response {
+		let mut response = match self.context.take() {
+			Some(context) => context.take_response(),
+			None => Response::new(),
+		};
+		response.take_body();
+		response.remove_header("content-length");
+		response.set_status(self.status);
+		response
+	}
 }
 
 impl<TContext, StdErr> From<StdErr> for Error<TContext>
@@ -42,11 +127,16 @@ where
 	StdErr: 'static + StdError + Send,
 {
 	fn from(err: StdErr) -> Self {
+		// HttpErrors know what they should be responded with
+		let err = match (Box::new(err) as Box<dyn Any>).downcast::<HttpError>() {
+			Ok(http_error) => return Error::from_http_error(None, *http_error),
+			Err(err) => err.downcast::<StdErr>().unwrap(),
+		};
 		Error {
 			context: None,
 			message: String::from("Internal Server Error"),
 			status: 500,
-			error: Box::new(err),
+			error: err,
 		}
 	}
 }
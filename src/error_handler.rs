@@ -0,0 +1,100 @@
+use crate::{context::Context, error::Error, Request, Response};
+use std::{fmt::Debug, future::Future, pin::Pin, sync::Arc};
+
+pub type ErrorHandlerFn<TContext, TState> =
+	for<'a> fn(
+		Request,
+		Error<TContext>,
+		&'a TState,
+	) -> Pin<Box<dyn Future<Output = Response> + Send + 'a>>;
+
+// Turns an error that made it all the way out of the middlewares into a response.
+// The request is always given, even when the error doesn't carry the context anymore
+#[async_trait::async_trait]
+pub trait ErrorHandler<TContext, TState>
+where
+	TContext: Context + Debug + Send + Sync,
+	TState: Send + Sync,
+{
+	async fn handle_error(
+		&self,
+		request: Request,
+		error: Error<TContext>,
+		state: &TState,
+	) -> Response;
+}
+
+#[async_trait::async_trait]
+impl<TContext, TState> ErrorHandler<TContext, TState> for ErrorHandlerFn<TContext, TState>
+where
+	TContext: 'static + Context + Debug + Send + Sync,
+	TState: Send + Sync,
+{
+	async fn handle_error(
+		&self,
+		request: Request,
+		error: Error<TContext>,
+		state: &TState,
+	) -> Response {
+		(self)(request, error, state).await
+	}
+}
+
+// Used when the app doesn't have an error handler of its own. Keeps the headers of the
+// response the error was raised with, and renders the message in whichever format the
+// request accepts. HttpErrors are rendered as problem details
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultErrorHandler;
+
+#[async_trait::async_trait]
+impl<TContext, TState> ErrorHandler<TContext, TState> for DefaultErrorHandler
+where
+	TContext: 'static + Context + Debug + Send + Sync,
+	TState: Send + Sync,
+{
+	async fn handle_error(
+		&self,
+		request: Request,
+		mut error: Error<TContext>,
+		_: &TState,
+	) -> Response {
+		let mut response = error.take_response();
+		let accept = request.get_header("Accept");
+		if let Some(http_error) = error.get_http_error() {
+			response.set_http_error(http_error, Some(&request.get_path()), accept.as_deref());
+		} else {
+			response.set_error(error.get_status(), error.get_message(), accept.as_deref());
+		}
+		response
+	}
+}
+
+// An error handler that brings its own state along, so that it can be used by an app with a
+// different state than the one it was registered with, like when mounting a sub app
+#[async_trait::async_trait]
+pub(crate) trait BoundErrorHandler<TContext>
+where
+	TContext: Context + Debug + Send + Sync,
+{
+	async fn handle_error(&self, request: Request, error: Error<TContext>) -> Response;
+}
+
+pub(crate) struct StatefulErrorHandler<TContext, TState>
+where
+	TContext: Context + Debug + Send + Sync,
+	TState: Send + Sync,
+{
+	pub(crate) handler: Arc<dyn ErrorHandler<TContext, TState> + Send + Sync>,
+	pub(crate) state: Arc<TState>,
+}
+
+#[async_trait::async_trait]
+impl<TContext, TState> BoundErrorHandler<TContext> for StatefulErrorHandler<TContext, TState>
+where
+	TContext: 'static + Context + Debug + Send + Sync,
+	TState: 'static + Send + Sync,
+{
+	async fn handle_error(&self, request: Request, error: Error<TContext>) -> Response {
+		self.handler.handle_error(request, error, &self.state).await
+	}
+}
@@ -0,0 +1,120 @@
+use std::{
+	any::{Any, TypeId},
+	collections::HashMap,
+	fmt::{Debug, Formatter, Result as FmtResult},
+};
+
+// Anything that can be stored in the extensions. It has to be cloneable, since requests are cloned,
+// like when one is handed to the error handler
+trait Extension: Any + Send + Sync {
+	fn clone_box(&self) -> Box<dyn Extension>;
+	fn as_any(&self) -> &dyn Any;
+	fn as_any_mut(&mut self) -> &mut dyn Any;
+	fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<T> Extension for T
+where
+	T: 'static + Clone + Send + Sync,
+{
+	fn clone_box(&self) -> Box<dyn Extension> {
+		Box::new(self.clone())
+	}
+
+	fn as_any(&self) -> &dyn Any {
+		self
+	}
+
+	fn as_any_mut(&mut self) -> &mut dyn Any {
+		self
+	}
+
+	fn into_any(self: Box<Self>) -> Box<dyn Any> {
+		self
+	}
+}
+
+impl Clone for Box<dyn Extension> {
+	fn clone(&self) -> Self {
+		(**self).clone_box()
+	}
+}
+
+// Data attached to a request by the middlewares, for the ones that come after them.
+// Holds at most one value of every type
+#[derive(Clone, Default)]
+pub struct Extensions {
+	map: HashMap<TypeId, Box<dyn Extension>>,
+}
+
+impl Extensions {
+	pub fn new() -> Self {
+		Extensions::default()
+	}
+
+	// Returns the value of the same type that was there before, if any
+	pub fn insert<T>(&mut self, value: T) -> Option<T>
+	where
+		T: 'static + Clone + Send + Sync,
+	{
+		self.map
+			.insert(TypeId::of::<T>(), Box::new(value))
+			.and_then(|previous| previous.into_any().downcast::<T>().ok())
+			.map(|previous| *previous)
+	}
+
+	pub fn get<T>(&self) -> Option<&T>
+	where
+		T: 'static + Clone + Send + Sync,
+	{
+		self.map
+			.get(&TypeId::of::<T>())
+			.and_then(|value| (**value).as_any().downcast_ref::<T>())
+	}
+
+	pub fn get_mut<T>(&mut self) -> Option<&mut T>
+	where
+		T: 'static + Clone + Send + Sync,
+	{
+		self.map
+			.get_mut(&TypeId::of::<T>())
+			.and_then(|value| (**value).as_any_mut().downcast_mut::<T>())
+	}
+
+	pub fn remove<T>(&mut self) -> Option<T>
+	where
+		T: 'static + Clone + Send + Sync,
+	{
+		self.map
+			.remove(&TypeId::of::<T>())
+			.and_then(|value| value.into_any().downcast::<T>().ok())
+			.map(|value| *value)
+	}
+
+	pub fn contains<T>(&self) -> bool
+	where
+		T: 'static + Clone + Send + Sync,
+	{
+		self.map.contains_key(&TypeId::of::<T>())
+	}
+
+	pub fn clear(&mut self) {
+		self.map.clear();
+	}
+
+	pub fn len(&self) -> usize {
+		self.map.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.map.is_empty()
+	}
+}
+
+impl Debug for Extensions {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		f.debug_struct("Extensions")
+			.field("len", &self.map.len())
+			.finish()
+	}
+}
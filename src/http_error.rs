@@ -0,0 +1,168 @@
+use crate::HttpMethod;
+use hyper::StatusCode;
+use serde_json::{json, Map, Value};
+use std::{
+	error::Error as StdError,
+	fmt::{Display, Formatter, Result as FmtResult},
+	time::Duration,
+};
+
+// The errors a client can be told about, rendered as problem details (RFC 7807)
+#[derive(Clone, Debug)]
+pub enum HttpError {
+	// The details are added to the problem as is, like a list of invalid fields
+	BadRequest {
+		detail: Option<String>,
+		details: Option<Value>,
+	},
+	// The challenge is sent as the WWW-Authenticate header, like `Bearer realm="api"`
+	Unauthorized {
+		challenge: String,
+		detail: Option<String>,
+	},
+	Forbidden {
+		detail: Option<String>,
+	},
+	NotFound {
+		detail: Option<String>,
+	},
+	MethodNotAllowed {
+		allowed: Vec<HttpMethod>,
+	},
+	Conflict {
+		detail: Option<String>,
+	},
+	PayloadTooLarge {
+		detail: Option<String>,
+	},
+	UnprocessableEntity {
+		detail: Option<String>,
+		details: Option<Value>,
+	},
+	TooManyRequests {
+		retry_after: Option<Duration>,
+	},
+	InternalServerError {
+		detail: Option<String>,
+	},
+	NotImplemented {
+		detail: Option<String>,
+	},
+	ServiceUnavailable {
+		retry_after: Option<Duration>,
+	},
+	// Anything that doesn't have a variant of its own
+	Other {
+		status: u16,
+		detail: Option<String>,
+	},
+}
+
+impl HttpError {
+	pub fn get_status(&self) -> u16 {
+		match self {
+			HttpError::BadRequest { .. } => 400,
+			HttpError::Unauthorized { .. } => 401,
+			HttpError::Forbidden { .. } => 403,
+			HttpError::NotFound { .. } => 404,
+			HttpError::MethodNotAllowed { .. } => 405,
+			HttpError::Conflict { .. } => 409,
+			HttpError::PayloadTooLarge { .. } => 413,
+			HttpError::UnprocessableEntity { .. } => 422,
+			HttpError::TooManyRequests { .. } => 429,
+			HttpError::InternalServerError { .. } => 500,
+			HttpError::NotImplemented { .. } => 501,
+			HttpError::ServiceUnavailable { .. } => 503,
+			HttpError::Other { status, .. } => *status,
+		}
+	}
+
+	pub fn get_title(&self) -> &'static str {
+		StatusCode::from_u16(self.get_status())
+			.ok()
+			.and_then(|status| status.canonical_reason())
+			.unwrap_or("Unknown Error")
+	}
+
+	pub fn get_detail(&self) -> Option<&str> {
+		match self {
+			HttpError::BadRequest { detail, .. } |
+			HttpError::Unauthorized { detail, .. } |
+			HttpError::Forbidden { detail } |
+			HttpError::NotFound { detail } |
+			HttpError::Conflict { detail } |
+			HttpError::PayloadTooLarge { detail } |
+			HttpError::UnprocessableEntity { detail, .. } |
+			HttpError::InternalServerError { detail } |
+			HttpError::NotImplemented { detail } |
+			HttpError::Other { detail, .. } => detail.as_deref(),
+			HttpError::MethodNotAllowed { .. } |
+			HttpError::TooManyRequests { .. } |
+			HttpError::ServiceUnavailable { .. } => None,
+		}
+	}
+
+	// The headers that have to go along with the status, like the Allow header of a 405
+	pub fn get_headers(&self) -> Vec<(&'static str, String)> {
+		match self {
+			HttpError::Unauthorized { challenge, .. } => {
+				vec![("WWW-Authenticate", challenge.clone())]
+			}
+			HttpError::MethodNotAllowed { allowed } => vec![(
+				"Allow",
+				allowed
+					.iter()
+					.map(HttpMethod::to_string)
+					.collect::<Vec<_>>()
+					.join(", "),
+			)],
+			HttpError::TooManyRequests {
+				retry_after: Some(retry_after),
+			} |
+			HttpError::ServiceUnavailable {
+				retry_after: Some(retry_after),
+			} => vec![("Retry-After", retry_after.as_secs().to_string())],
+			_ => vec![],
+		}
+	}
+
+	// The problem details object. The instance is usually the path of the request
+	pub fn to_problem_json(&self, instance: Option<&str>) -> Value {
+		let mut problem = Map::new();
+		problem.insert("type".to_string(), json!("about:blank"));
+		problem.insert("title".to_string(), json!(self.get_title()));
+		problem.insert("status".to_string(), json!(self.get_status()));
+		if let Some(detail) = self.get_detail() {
+			problem.insert("detail".to_string(), json!(detail));
+		}
+		if let Some(instance) = instance {
+			problem.insert("instance".to_string(), json!(instance));
+		}
+		match self {
+			HttpError::BadRequest {
+				details: Some(details),
+				..
+			} |
+			HttpError::UnprocessableEntity {
+				details: Some(details),
+				..
+			} => {
+				problem.insert("details".to_string(), details.clone());
+			}
+			_ => (),
+		}
+		Value::Object(problem)
+	}
+}
+
+impl Display for HttpError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		write!(
+			f,
+			"{}",
+			self.get_detail().unwrap_or_else(|| self.get_title())
+		)
+	}
+}
+
+impl StdError for HttpError {}
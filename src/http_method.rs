@@ -15,6 +15,8 @@ pub enum HttpMethod {
 	Connect,
 	Patch,
 	Trace,
+	// Extension methods, like PURGE or WebDAV's PROPFIND. Methods are case sensitive
+	Custom(String),
 }
 
 impl Display for HttpMethod {
@@ -32,6 +34,7 @@ impl Display for HttpMethod {
 				HttpMethod::Post => "POST",
 				HttpMethod::Put => "PUT",
 				HttpMethod::Trace => "TRACE",
+				HttpMethod::Custom(method) => method,
 			}
 		)
 	}
@@ -40,21 +43,26 @@ impl Display for HttpMethod {
 impl FromStr for HttpMethod {
 	type Err = String;
 
+	// Method names are case sensitive, so `get` is an extension method rather than GET
 	fn from_str(method: &str) -> Result<Self, String> {
-		match method.to_lowercase().as_ref() {
-			"get" => Ok(HttpMethod::Get),
-			"post" => Ok(HttpMethod::Post),
-			"put" => Ok(HttpMethod::Put),
-			"delete" => Ok(HttpMethod::Delete),
-			"head" => Ok(HttpMethod::Head),
-			"options" => Ok(HttpMethod::Options),
-			"connect" => Ok(HttpMethod::Connect),
-			"patch" => Ok(HttpMethod::Patch),
-			"trace" => Ok(HttpMethod::Trace),
-			_ => Err(format!(
-				"Could not parse a suitable HTTP Method for string: '{}'",
-				method
-			)),
+		match method {
+			"GET" => Ok(HttpMethod::Get),
+			"POST" => Ok(HttpMethod::Post),
+			"PUT" => Ok(HttpMethod::Put),
+			"DELETE" => Ok(HttpMethod::Delete),
+			"HEAD" => Ok(HttpMethod::Head),
+			"OPTIONS" => Ok(HttpMethod::Options),
+			"CONNECT" => Ok(HttpMethod::Connect),
+			"PATCH" => Ok(HttpMethod::Patch),
+			"TRACE" => Ok(HttpMethod::Trace),
+			// Anything else is fine, as long as it's a valid token
+			_ => match Method::from_bytes(method.as_bytes()) {
+				Ok(_) => Ok(HttpMethod::Custom(method.to_string())),
+				Err(_) => Err(format!(
+					"Could not parse a suitable HTTP Method for string: '{}'",
+					method
+				)),
+			},
 		}
 	}
 }
@@ -71,7 +79,7 @@ impl From<Method> for HttpMethod {
 			Method::CONNECT => HttpMethod::Connect,
 			Method::PATCH => HttpMethod::Patch,
 			Method::TRACE => HttpMethod::Trace,
-			method => panic!("Could not recognise suitable HTTP method for {}", method),
+			method => HttpMethod::Custom(method.as_str().to_string()),
 		}
 	}
 }
@@ -1,12 +1,32 @@
 mod app;
+mod body;
+mod connection;
 mod context;
 mod cookie;
 mod error;
+mod error_handler;
+mod extensions;
+mod http_error;
 mod http_method;
+mod listener;
 mod middleware;
 mod middleware_handler;
+mod named_routes;
 mod request;
 mod response;
+mod route_error;
+mod route_group;
+mod route_info;
+mod router;
+mod server;
+mod server_config;
+mod server_stats;
+mod sse;
+mod sub_app;
+#[cfg(feature = "tls")]
+mod tls;
+#[cfg(feature = "websocket")]
+mod websocket;
 //mod headers;
 #[cfg(feature = "render")]
 mod renderer;
@@ -14,103 +34,94 @@ mod renderer;
 pub mod default_middlewares;
 
 pub use app::App;
+pub use body::{BodyError, BodyReader, BodyStream, ChunkStream, ResponseBody, SharedBody};
 pub use context::{default_context_generator, Context, DefaultContext};
 pub use cookie::{Cookie, CookieOptions, SameSite};
 pub use error::Error;
+pub use error_handler::{DefaultErrorHandler, ErrorHandler, ErrorHandlerFn};
+pub use extensions::Extensions;
+pub use http_error::HttpError;
 pub use http_method::HttpMethod;
-pub use middleware::{DefaultMiddleware, Middleware, NextHandler};
+pub use listener::{BoundAddr, Listener, RemoteAddr};
+pub use middleware::{DefaultMiddleware, DefaultMiddlewareFuture, Middleware, NextHandler};
+pub use middleware_handler::{PathError, PathErrorKind};
+pub use named_routes::{NamedRoutes, UrlForError};
 pub use renderer::RenderEngine;
 pub use request::Request;
 pub use response::Response;
+pub use route_error::RouteError;
+pub use route_group::RouteGroup;
+pub use route_info::{RouteInfo, ShadowedRoute};
+pub use server_config::ServerConfig;
+pub use server_stats::ServerStats;
+pub use sse::{ClientDisconnected, SseEvent, SseSender, DEFAULT_KEEP_ALIVE_INTERVAL};
+#[cfg(feature = "tls")]
+pub use tls::{TlsConfig, DEFAULT_CERT_WATCH_INTERVAL};
+#[cfg(feature = "websocket")]
+pub use websocket::{
+	WebSocket,
+	WebSocketCloseCode,
+	WebSocketError,
+	WebSocketHandler,
+	WebSocketMessage,
+};
 
 pub use handlebars;
+pub use hyper::body::Bytes;
 
 use futures::Future;
-use hyper::{
-	server::conn::AddrStream,
-	service::{make_service_fn, service_fn},
-	Body,
-	Error as HyperError,
-	Request as HyperRequest,
-	Response as HyperResponse,
-	Server,
-};
-use std::{fmt::Debug, net::SocketAddr, sync::Arc};
+use std::{fmt::Debug, io::Error as IoError};
 
-pub async fn listen<TContext, TMiddleware, TState, TShutdownSignal>(
+pub async fn listen<TContext, TMiddleware, TState, TListener, TShutdownSignal>(
 	app: App<TContext, TMiddleware, TState>,
-	bind_addr: ([u8; 4], u16),
+	listener: TListener,
 	shutdown_signal: Option<TShutdownSignal>,
-) where
+) -> Result<(), IoError>
+where
 	TContext: 'static + Context + Debug + Send + Sync,
 	TMiddleware: 'static + Middleware<TContext> + Clone + Send + Sync,
 	TState: 'static + Send + Sync,
+	TListener: Into<Listener>,
 	TShutdownSignal: Future<Output = ()>,
 {
-	let bind_addr = SocketAddr::from(bind_addr);
-
-	let app_arc = Arc::new(app);
-
-	async move {
-		let service = make_service_fn(|conn: &AddrStream| {
-			let app = app_arc.clone();
-			let remote_addr = conn.remote_addr();
-
-			async move {
-				Ok::<_, HyperError>(service_fn(move |req: HyperRequest<Body>| {
-					let app = app.clone();
-					async move {
-						let request = Request::from_hyper(remote_addr, req).await;
-						let mut context = app.generate_context(request);
-						context.header("Server", "Eve");
-
-						// execute app's middlewares
-						let result = app.resolve(context).await;
-						let response = match result {
-							Ok(context) => context.take_response(),
-							Err(err) => {
-								// return a proper formatted error, if an error handler exists
-								if app.error_handler.is_none() {
-									return Ok::<_, HyperError>(HyperResponse::new(Body::from(
-										err.message,
-									)));
-								} else {
-									let response = Response::new();
-									(app.error_handler.as_ref().unwrap())(response, err.error)
-								}
-							}
-						};
-
-						let mut hyper_response = HyperResponse::builder();
-
-						// Set the appropriate headers
-						for (key, values) in &response.headers {
-							for value in values {
-								hyper_response = hyper_response.header(key, value);
-							}
-						}
-
-						Ok::<HyperResponse<Body>, HyperError>(
-							hyper_response
-								.status(response.status)
-								.body(Body::from(response.body))
-								.unwrap(),
-						)
-					}
-				}))
-			}
-		});
-
-		let server = Server::bind(&bind_addr).serve(service);
+	server::serve(app, listener.into(), ServerConfig::new(), shutdown_signal).await
+}
 
-		if let Some(shutdown_signal) = shutdown_signal {
-			server
-				.with_graceful_shutdown(shutdown_signal)
-				.await
-				.unwrap();
-		} else {
-			server.await.unwrap();
-		}
-	}
+#[cfg(feature = "tls")]
+pub async fn listen_tls<TContext, TMiddleware, TState, TListener, TShutdownSignal>(
+	app: App<TContext, TMiddleware, TState>,
+	listener: TListener,
+	tls_config: TlsConfig,
+	shutdown_signal: Option<TShutdownSignal>,
+) -> Result<(), IoError>
+where
+	TContext: 'static + Context + Debug + Send + Sync,
+	TMiddleware: 'static + Middleware<TContext> + Clone + Send + Sync,
+	TState: 'static + Send + Sync,
+	TListener: Into<Listener>,
+	TShutdownSignal: Future<Output = ()>,
+{
+	server::serve(
+		app,
+		listener.into(),
+		ServerConfig::new().tls(tls_config),
+		shutdown_signal,
+	)
 	.await
 }
+
+pub async fn listen_with_config<TContext, TMiddleware, TState, TListener, TShutdownSignal>(
+	app: App<TContext, TMiddleware, TState>,
+	listener: TListener,
+	config: ServerConfig,
+	shutdown_signal: Option<TShutdownSignal>,
+) -> Result<(), IoError>
+where
+	TContext: 'static + Context + Debug + Send + Sync,
+	TMiddleware: 'static + Middleware<TContext> + Clone + Send + Sync,
+	TState: 'static + Send + Sync,
+	TListener: Into<Listener>,
+	TShutdownSignal: Future<Output = ()>,
+{
+	server::serve(app, listener.into(), config, shutdown_signal).await
+}
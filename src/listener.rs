@@ -0,0 +1,455 @@
+use futures::task::{Context as TaskContext, Poll};
+use std::{
+	fmt::{Display, Formatter, Result as FmtResult},
+	io::Error as IoError,
+	mem::MaybeUninit,
+	net::{IpAddr, SocketAddr},
+	path::PathBuf,
+	pin::Pin,
+};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+use tokio::{
+	io::{AsyncRead, AsyncWrite},
+	net::{TcpListener, TcpStream},
+};
+
+// Where a request came from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RemoteAddr {
+	Ip(SocketAddr),
+	// Clients of a unix socket usually don't have a path of their own
+	Unix(Option<PathBuf>),
+}
+
+impl RemoteAddr {
+	pub fn get_ip(&self) -> Option<IpAddr> {
+		match self {
+			RemoteAddr::Ip(socket_addr) => Some(socket_addr.ip()),
+			RemoteAddr::Unix(_) => None,
+		}
+	}
+}
+
+impl From<SocketAddr> for RemoteAddr {
+	fn from(socket_addr: SocketAddr) -> Self {
+		RemoteAddr::Ip(socket_addr)
+	}
+}
+
+impl Display for RemoteAddr {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		match self {
+			RemoteAddr::Ip(socket_addr) => write!(f, "{}", socket_addr),
+			RemoteAddr::Unix(Some(path)) => write!(f, "unix:{}", path.display()),
+			RemoteAddr::Unix(None) => write!(f, "unix"),
+		}
+	}
+}
+
+// Where the server ended up listening, once it's bound. Useful when binding to port 0
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BoundAddr {
+	Ip(SocketAddr),
+	// Sockets taken over from a file descriptor might not have a path
+	Unix(Option<PathBuf>),
+}
+
+impl BoundAddr {
+	pub fn get_socket_addr(&self) -> Option<SocketAddr> {
+		match self {
+			BoundAddr::Ip(socket_addr) => Some(*socket_addr),
+			BoundAddr::Unix(_) => None,
+		}
+	}
+}
+
+impl Display for BoundAddr {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		match self {
+			BoundAddr::Ip(socket_addr) => write!(f, "{}", socket_addr),
+			BoundAddr::Unix(Some(path)) => write!(f, "unix:{}", path.display()),
+			BoundAddr::Unix(None) => write!(f, "unix"),
+		}
+	}
+}
+
+#[derive(Debug)]
+enum ListenerSource {
+	Tcp(SocketAddr),
+	#[cfg(unix)]
+	Unix {
+		path: PathBuf,
+		permissions: Option<u32>,
+	},
+	#[cfg(unix)]
+	Fd(std::os::unix::io::OwnedFd),
+}
+
+// Everything the server should accept connections on. Sources can be combined, so
+// that the same app can be served on, say, both IPv4 and IPv6, or a unix socket.
+// Not Clone, since the file descriptors it takes over can only be owned once
+#[derive(Debug, Default)]
+pub struct Listener {
+	sources: Vec<ListenerSource>,
+}
+
+impl Listener {
+	pub fn new() -> Self {
+		Listener::default()
+	}
+
+	pub fn tcp<TAddr>(mut self, addr: TAddr) -> Self
+	where
+		TAddr: Into<SocketAddr>,
+	{
+		self.sources.push(ListenerSource::Tcp(addr.into()));
+		self
+	}
+
+	// A stale socket file at the path is removed first, but binding fails if something is still
+	// listening on it. The permissions, if given, are a mode like 0o660, which the socket file
+	// has from the moment it shows up at the path
+	#[cfg(unix)]
+	pub fn unix<TPath>(mut self, path: TPath, permissions: Option<u32>) -> Self
+	where
+		TPath: Into<PathBuf>,
+	{
+		self.sources.push(ListenerSource::Unix {
+			path: path.into(),
+			permissions,
+		});
+		self
+	}
+
+	// Takes over a socket that's already bound and listening, either TCP or unix.
+	// Binding fails if it's anything else
+	#[cfg(unix)]
+	pub fn fd(mut self, fd: std::os::unix::io::OwnedFd) -> Self {
+		self.sources.push(ListenerSource::Fd(fd));
+		self
+	}
+
+	// Takes over the sockets passed by systemd through LISTEN_FDS, if they're meant for this
+	// process. The variables are cleared, so that child processes don't try to take them over too
+	#[cfg(unix)]
+	pub fn systemd(mut self) -> Self {
+		// See sd_listen_fds(3)
+		const SD_LISTEN_FDS_START: std::os::unix::io::RawFd = 3;
+
+		let is_for_this_process = std::env::var("LISTEN_PID")
+			.ok()
+			.and_then(|pid| pid.parse::<u32>().ok()) ==
+			Some(std::process::id());
+		let fds = std::env::var("LISTEN_FDS")
+			.ok()
+			.and_then(|fds| fds.parse::<i32>().ok())
+			.unwrap_or(0);
+		for name in &["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+			std::env::remove_var(name);
+		}
+		if is_for_this_process {
+			for fd in SD_LISTEN_FDS_START..(SD_LISTEN_FDS_START + fds) {
+				// systemd hands these over to the process, and nothing else in it knows about them
+				self = self.fd(unsafe { std::os::unix::io::FromRawFd::from_raw_fd(fd) });
+			}
+		}
+		self
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.sources.is_empty()
+	}
+
+	pub(crate) async fn bind(self) -> Result<Vec<BoundListener>, IoError> {
+		let mut listeners = vec![];
+		for source in self.sources {
+			listeners.push(match source {
+				ListenerSource::Tcp(addr) => BoundListener::Tcp(TcpListener::bind(addr).await?),
+				#[cfg(unix)]
+				ListenerSource::Unix { path, permissions } => {
+					BoundListener::Unix(bind_unix(&path, permissions)?, Some(path))
+				}
+				#[cfg(unix)]
+				ListenerSource::Fd(fd) => BoundListener::from_fd(fd)?,
+			});
+		}
+		Ok(listeners)
+	}
+}
+
+#[cfg(unix)]
+fn bind_unix(path: &std::path::Path, permissions: Option<u32>) -> Result<UnixListener, IoError> {
+	use std::{
+		fs::{self, Permissions},
+		io::ErrorKind,
+		os::unix::{
+			fs::{FileTypeExt, PermissionsExt},
+			net::UnixStream as StdUnixStream,
+		},
+	};
+
+	if let Ok(metadata) = fs::symlink_metadata(path) {
+		if !metadata.file_type().is_socket() {
+			return Err(IoError::new(
+				ErrorKind::AlreadyExists,
+				format!("{} already exists and isn't a socket", path.display()),
+			));
+		}
+		// Only a socket nobody is listening on anymore is stale
+		if StdUnixStream::connect(path).is_ok() {
+			return Err(IoError::new(
+				ErrorKind::AddrInUse,
+				format!("{} is already being listened on", path.display()),
+			));
+		}
+		fs::remove_file(path)?;
+	}
+
+	let permissions = if let Some(permissions) = permissions {
+		permissions
+	} else {
+		return UnixListener::bind(path);
+	};
+
+	// Changing the permissions after binding would leave the socket open to anyone for a
+	// moment, so it's bound somewhere else and only linked to the path once it's locked down.
+	// Unlike a rename, linking fails instead of replacing whatever showed up at the path since
+	let file_name = path
+		.file_name()
+		.map(|name| name.to_string_lossy().to_string())
+		.unwrap_or_default();
+	let temp_path = path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+	let _ = fs::remove_file(&temp_path);
+	let listener = UnixListener::bind(&temp_path)?;
+	let result = fs::set_permissions(&temp_path, Permissions::from_mode(permissions))
+		.and_then(|_| fs::hard_link(&temp_path, path));
+	let _ = fs::remove_file(&temp_path);
+	result?;
+	Ok(listener)
+}
+
+#[cfg(unix)]
+fn get_socket_option(
+	fd: std::os::unix::io::RawFd,
+	option: libc::c_int,
+) -> Result<libc::c_int, IoError> {
+	let mut value: libc::c_int = 0;
+	let mut length = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+	let result = unsafe {
+		libc::getsockopt(
+			fd,
+			libc::SOL_SOCKET,
+			option,
+			&mut value as *mut libc::c_int as *mut libc::c_void,
+			&mut length,
+		)
+	};
+	if result == 0 {
+		Ok(value)
+	} else {
+		Err(IoError::last_os_error())
+	}
+}
+
+impl From<SocketAddr> for Listener {
+	fn from(addr: SocketAddr) -> Self {
+		Listener::new().tcp(addr)
+	}
+}
+
+impl From<([u8; 4], u16)> for Listener {
+	fn from(addr: ([u8; 4], u16)) -> Self {
+		Listener::new().tcp(addr)
+	}
+}
+
+impl From<([u16; 8], u16)> for Listener {
+	fn from(addr: ([u16; 8], u16)) -> Self {
+		Listener::new().tcp(addr)
+	}
+}
+
+impl From<(IpAddr, u16)> for Listener {
+	fn from(addr: (IpAddr, u16)) -> Self {
+		Listener::new().tcp(addr)
+	}
+}
+
+impl From<&[SocketAddr]> for Listener {
+	fn from(addrs: &[SocketAddr]) -> Self {
+		addrs
+			.iter()
+			.fold(Listener::new(), |listener, addr| listener.tcp(*addr))
+	}
+}
+
+impl From<Vec<SocketAddr>> for Listener {
+	fn from(addrs: Vec<SocketAddr>) -> Self {
+		Listener::from(addrs.as_slice())
+	}
+}
+
+pub(crate) enum BoundListener {
+	Tcp(TcpListener),
+	// The path is only kept for sockets the server created itself, so that it can clean them up
+	#[cfg(unix)]
+	Unix(UnixListener, Option<PathBuf>),
+}
+
+impl BoundListener {
+	#[cfg(unix)]
+	fn from_fd(fd: std::os::unix::io::OwnedFd) -> Result<Self, IoError> {
+		use std::{
+			io::ErrorKind,
+			os::unix::{io::AsRawFd, net::UnixListener as StdUnixListener},
+		};
+
+		// Anything that isn't a listening stream socket would never accept a connection
+		let socket_type = get_socket_option(fd.as_raw_fd(), libc::SO_TYPE).map_err(|err| {
+			IoError::new(
+				ErrorKind::InvalidInput,
+				format!("fd {} isn't a socket: {}", fd.as_raw_fd(), err),
+			)
+		})?;
+		if socket_type != libc::SOCK_STREAM ||
+			get_socket_option(fd.as_raw_fd(), libc::SO_ACCEPTCONN)? == 0
+		{
+			return Err(IoError::new(
+				ErrorKind::InvalidInput,
+				format!("fd {} isn't a listening stream socket", fd.as_raw_fd()),
+			));
+		}
+
+		// A unix socket doesn't have an address std can make sense of
+		let listener = std::net::TcpListener::from(fd);
+		if listener.local_addr().is_ok() {
+			listener.set_nonblocking(true)?;
+			Ok(BoundListener::Tcp(TcpListener::from_std(listener)?))
+		} else {
+			let listener = StdUnixListener::from(std::os::unix::io::OwnedFd::from(listener));
+			listener.set_nonblocking(true)?;
+			Ok(BoundListener::Unix(UnixListener::from_std(listener)?, None))
+		}
+	}
+
+	pub(crate) fn get_addr(&self) -> Result<BoundAddr, IoError> {
+		match self {
+			BoundListener::Tcp(listener) => Ok(BoundAddr::Ip(listener.local_addr()?)),
+			// The socket might have been bound somewhere else and moved to the path afterwards
+			#[cfg(unix)]
+			BoundListener::Unix(_, Some(path)) => Ok(BoundAddr::Unix(Some(path.clone()))),
+			#[cfg(unix)]
+			BoundListener::Unix(listener, None) => Ok(BoundAddr::Unix(
+				listener.local_addr()?.as_pathname().map(PathBuf::from),
+			)),
+		}
+	}
+
+	pub(crate) async fn accept(&mut self) -> Result<(ConnectionIo, RemoteAddr), IoError> {
+		match self {
+			BoundListener::Tcp(listener) => {
+				let (stream, remote_addr) = listener.accept().await?;
+				Ok((ConnectionIo::Tcp(stream), RemoteAddr::Ip(remote_addr)))
+			}
+			#[cfg(unix)]
+			BoundListener::Unix(listener, _) => {
+				let (stream, remote_addr) = listener.accept().await?;
+				Ok((
+					ConnectionIo::Unix(stream),
+					RemoteAddr::Unix(remote_addr.as_pathname().map(PathBuf::from)),
+				))
+			}
+		}
+	}
+}
+
+#[cfg(unix)]
+impl Drop for BoundListener {
+	fn drop(&mut self) {
+		if let BoundListener::Unix(_, Some(path)) = self {
+			let _ = std::fs::remove_file(path);
+		}
+	}
+}
+
+// The underlying stream of a connection, whatever kind of socket it came in on
+pub(crate) enum ConnectionIo {
+	Tcp(TcpStream),
+	#[cfg(unix)]
+	Unix(UnixStream),
+	#[cfg(feature = "tls")]
+	Tls(Box<tokio_rustls::server::TlsStream<ConnectionIo>>),
+}
+
+impl ConnectionIo {
+	pub(crate) fn is_secure(&self) -> bool {
+		match self {
+			#[cfg(feature = "tls")]
+			ConnectionIo::Tls(_) => true,
+			_ => false,
+		}
+	}
+}
+
+impl AsyncRead for ConnectionIo {
+	unsafe fn prepare_uninitialized_buffer(&self, buf: &mut [MaybeUninit<u8>]) -> bool {
+		match self {
+			ConnectionIo::Tcp(stream) => stream.prepare_uninitialized_buffer(buf),
+			#[cfg(unix)]
+			ConnectionIo::Unix(stream) => stream.prepare_uninitialized_buffer(buf),
+			#[cfg(feature = "tls")]
+			ConnectionIo::Tls(stream) => stream.prepare_uninitialized_buffer(buf),
+		}
+	}
+
+	fn poll_read(
+		self: Pin<&mut Self>,
+		cx: &mut TaskContext<'_>,
+		buf: &mut [u8],
+	) -> Poll<Result<usize, IoError>> {
+		match self.get_mut() {
+			ConnectionIo::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+			#[cfg(unix)]
+			ConnectionIo::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+			#[cfg(feature = "tls")]
+			ConnectionIo::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+		}
+	}
+}
+
+impl AsyncWrite for ConnectionIo {
+	fn poll_write(
+		self: Pin<&mut Self>,
+		cx: &mut TaskContext<'_>,
+		buf: &[u8],
+	) -> Poll<Result<usize, IoError>> {
+		match self.get_mut() {
+			ConnectionIo::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+			#[cfg(unix)]
+			ConnectionIo::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+			#[cfg(feature = "tls")]
+			ConnectionIo::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+		}
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Result<(), IoError>> {
+		match self.get_mut() {
+			ConnectionIo::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+			#[cfg(unix)]
+			ConnectionIo::Unix(stream) => Pin::new(stream).poll_flush(cx),
+			#[cfg(feature = "tls")]
+			ConnectionIo::Tls(stream) => Pin::new(stream).poll_flush(cx),
+		}
+	}
+
+	fn poll_shutdown(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Result<(), IoError>> {
+		match self.get_mut() {
+			ConnectionIo::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+			#[cfg(unix)]
+			ConnectionIo::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+			#[cfg(feature = "tls")]
+			ConnectionIo::Tls(stream) => Pin::new(stream).poll_shutdown(cx),
+		}
+	}
+}
@@ -2,7 +2,7 @@ use crate::{
 	context::{Context, DefaultContext},
 	error::Error,
 };
-use std::{fmt::Debug, future::Future, pin::Pin};
+use std::{fmt::Debug, future::Future, pin::Pin, sync::Arc};
 
 pub type NextHandler<TContext> = Box<
 	dyn Fn(TContext) -> Pin<Box<dyn Future<Output = Result<TContext, Error<TContext>>> + Send>>
@@ -19,34 +19,72 @@ pub trait Middleware<TContext: Context + Debug + Send + Sync> {
 	) -> Result<TContext, Error<TContext>>;
 }
 
+pub type DefaultMiddlewareFuture =
+	Pin<Box<dyn Future<Output = Result<DefaultContext, Error<DefaultContext>>> + Send>>;
+
 type DefaultMiddlewareHandler =
-	fn(
-		DefaultContext,
-		NextHandler<DefaultContext>,
-	) -> Pin<Box<dyn Future<Output = Result<DefaultContext, Error<DefaultContext>>> + Send>>;
+	fn(DefaultContext, NextHandler<DefaultContext>) -> DefaultMiddlewareFuture;
+type DefaultMiddlewareDataHandler<TData> =
+	fn(DefaultContext, NextHandler<DefaultContext>, &TData) -> DefaultMiddlewareFuture;
+
+// Whatever the middleware was created with, closure or not
+type BoxedDefaultMiddlewareHandler<TData> = Arc<
+	dyn Fn(DefaultContext, NextHandler<DefaultContext>, &TData) -> DefaultMiddlewareFuture
+		+ Send
+		+ Sync,
+>;
 
 #[derive(Clone)]
 pub struct DefaultMiddleware<TData>
 where
 	TData: Default + Clone + Send + Sync,
 {
-	handler: DefaultMiddlewareHandler,
+	handler: BoxedDefaultMiddlewareHandler<TData>,
 	data: TData,
 }
 
 impl<TData> DefaultMiddleware<TData>
 where
-	TData: Default + Clone + Send + Sync,
+	TData: 'static + Default + Clone + Send + Sync,
 {
 	pub fn new(handler: DefaultMiddlewareHandler) -> Self {
+		DefaultMiddleware::from_fn(handler)
+	}
+
+	// The data is given to the handler every time it runs
+	pub fn new_with_data(handler: DefaultMiddlewareDataHandler<TData>, data: TData) -> Self {
+		DefaultMiddleware::from_fn_with_data(handler, data)
+	}
+
+	// Like new, but takes closures too, so that the handler can capture things like an Arc
+	pub fn from_fn<THandler>(handler: THandler) -> Self
+	where
+		THandler: 'static
+			+ Fn(DefaultContext, NextHandler<DefaultContext>) -> DefaultMiddlewareFuture
+			+ Send
+			+ Sync,
+	{
 		DefaultMiddleware {
-			handler,
+			handler: Arc::new(move |context, next, _: &TData| handler(context, next)),
 			data: Default::default(),
 		}
 	}
 
-	pub fn new_with_data(handler: DefaultMiddlewareHandler, data: TData) -> Self {
-		DefaultMiddleware { handler, data }
+	pub fn from_fn_with_data<THandler>(handler: THandler, data: TData) -> Self
+	where
+		THandler: 'static
+			+ Fn(DefaultContext, NextHandler<DefaultContext>, &TData) -> DefaultMiddlewareFuture
+			+ Send
+			+ Sync,
+	{
+		DefaultMiddleware {
+			handler: Arc::new(handler),
+			data,
+		}
+	}
+
+	pub fn get_data(&self) -> &TData {
+		&self.data
 	}
 }
 
@@ -60,6 +98,6 @@ where
 		context: DefaultContext,
 		next: NextHandler<DefaultContext>,
 	) -> Result<DefaultContext, Error<DefaultContext>> {
-		(self.handler)(context, next).await
+		(self.handler)(context, next, &self.data).await
 	}
 }
@@ -1,6 +1,54 @@
-use crate::{Context, Middleware};
+use crate::{named_routes::UrlForError, router::Route, Context, Middleware};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
 use regex::Regex;
-use std::{fmt::Debug, marker::PhantomData};
+use std::{
+	any::Any,
+	collections::HashSet,
+	error::Error as StdError,
+	fmt::{Debug, Display, Formatter, Result as FmtResult},
+	marker::PhantomData,
+	sync::Arc,
+};
+
+#[derive(Clone, Debug)]
+pub(crate) enum PathSegment {
+	// A plain piece of the url, matched exactly
+	Static(String),
+	// `:name`, matches one segment made of a-z, A-Z, 0-9, '_', '.', '-' and percent-encoded
+	// characters. With a constraint, like `:id(\d+)` or `:id<uuid>`, matches one segment that
+	// the constraint matches instead. An optional one, like `:lang?`, can match nothing at all
+	Param {
+		name: String,
+		constraint: Option<Regex>,
+		optional: bool,
+	},
+	// `*`, matches any one non-empty segment
+	Wildcard,
+	// `**` or `*name`, matches one or more segments, the first of which is not empty
+	CatchAll {
+		name: Option<String>,
+		optional: bool,
+	},
+	// A segment that mixes literals with variables, like `:name.json`
+	Pattern(Regex),
+}
+
+impl PathSegment {
+	pub(crate) fn get_param_names(&self) -> Vec<String> {
+		match self {
+			PathSegment::Param { name, .. } |
+			PathSegment::CatchAll {
+				name: Some(name), ..
+			} => vec![name.clone()],
+			PathSegment::Pattern(regex) => regex
+				.capture_names()
+				.flatten()
+				.map(str::to_string)
+				.collect(),
+			_ => vec![],
+		}
+	}
+}
 
 pub(crate) struct MiddlewareHandler<TContext, TMiddleware>
 where
@@ -8,9 +56,18 @@ where
 	TMiddleware: Middleware<TContext> + Clone + Send + Sync,
 {
 	pub(crate) is_endpoint: bool,
+	// Put in front of an endpoint by a route group. Matched as an endpoint, but it's a middleware
+	pub(crate) is_group_middleware: bool,
 	pub(crate) mounted_url: String,
-	pub(crate) path_match: Regex,
+	pub(crate) segments: Vec<PathSegment>,
 	pub(crate) handler: TMiddleware,
+	// The state of the sub app this was registered on. None for the app's own middlewares
+	pub(crate) state: Option<Arc<dyn Any + Send + Sync>>,
+	// The name given to the route, see url_for
+	pub(crate) name: Option<String>,
+	// The sub app this was registered on, as an index into the app's sub app stack.
+	// None for the app's own middlewares
+	pub(crate) sub_app: Option<usize>,
 	phantom: PhantomData<TContext>,
 }
 
@@ -22,9 +79,13 @@ where
 	fn clone(&self) -> Self {
 		MiddlewareHandler {
 			is_endpoint: self.is_endpoint,
+			is_group_middleware: self.is_group_middleware,
 			mounted_url: self.mounted_url.clone(),
-			path_match: self.path_match.clone(),
+			segments: self.segments.clone(),
 			handler: self.handler.clone(),
+			state: self.state.clone(),
+			name: self.name.clone(),
+			sub_app: self.sub_app,
 			phantom: PhantomData,
 		}
 	}
@@ -36,70 +97,545 @@ where
 	TMiddleware: Middleware<TContext> + Clone + Send + Sync,
 {
 	pub(crate) fn new(path: &str, handler: TMiddleware, is_endpoint: bool) -> Self {
-		let mut mounted_url = path.to_string();
+		MiddlewareHandler::try_new(path, handler, is_endpoint)
+			.unwrap_or_else(|err| panic!("{}", err))
+	}
+
+	pub(crate) fn try_new(
+		path: &str,
+		handler: TMiddleware,
+		is_endpoint: bool,
+	) -> Result<Self, PathError> {
+		let (mounted_url, segments) = try_parse_path(path)?;
+
+		Ok(MiddlewareHandler {
+			is_endpoint,
+			is_group_middleware: false,
+			mounted_url,
+			segments,
+			handler,
+			state: None,
+			name: None,
+			sub_app: None,
+			phantom: PhantomData,
+		})
+	}
+}
+
+impl<TContext, TMiddleware> Route for MiddlewareHandler<TContext, TMiddleware>
+where
+	TContext: Context + Debug + Send + Sync,
+	TMiddleware: Middleware<TContext> + Clone + Send + Sync,
+{
+	fn get_segments(&self) -> &[PathSegment] {
+		&self.segments
+	}
+
+	fn is_endpoint(&self) -> bool {
+		self.is_endpoint
+	}
+}
+
+// What's wrong with a path. The params are named without their `:`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathErrorKind {
+	// `:(\d+)`
+	MissingName,
+	// `:id(\d+`
+	MissingConstraintEnd { param: String },
+	// `:id()`
+	EmptyConstraint { param: String },
+	// `:id(a{2,1})`, along with what the regex crate had to say about it
+	InvalidConstraint { param: String, error: String },
+	// `:id<int`
+	MissingTypeEnd { param: String },
+	// `:id<number>`
+	UnknownType { param: String, kind: String },
+	// `users?`, since only params can be optional
+	NotOptional { segment: String },
+	// `/:id/posts/:id`
+	DuplicateParam { param: String },
+	// A segment mixing literals and params that doesn't make for a valid regex
+	InvalidPattern { error: String },
+}
+
+impl Display for PathErrorKind {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		match self {
+			PathErrorKind::MissingName => write!(f, "a parameter is missing its name"),
+			PathErrorKind::MissingConstraintEnd { param } => {
+				write!(f, "the constraint of `:{}` is missing a `)`", param)
+			}
+			PathErrorKind::EmptyConstraint { param } => {
+				write!(f, "the constraint of `:{}` is empty", param)
+			}
+			PathErrorKind::InvalidConstraint { param, error } => write!(
+				f,
+				"the constraint of `:{}` is not a valid regex: {}",
+				param, error
+			),
+			PathErrorKind::MissingTypeEnd { param } => {
+				write!(f, "the type of `:{}` is missing a `>`", param)
+			}
+			PathErrorKind::UnknownType { param, kind } => {
+				write!(f, "`{}` is not a known type for `:{}`", kind, param)
+			}
+			PathErrorKind::NotOptional { segment } => write!(
+				f,
+				"`{}?` can't be optional, only parameters can be",
+				segment
+			),
+			PathErrorKind::DuplicateParam { param } => {
+				write!(f, "the parameter `{}` is used more than once", param)
+			}
+			PathErrorKind::InvalidPattern { error } => {
+				write!(f, "the segment is not a valid pattern: {}", error)
+			}
+		}
+	}
+}
+
+// Why a path couldn't be parsed
+#[derive(Clone, Debug)]
+pub struct PathError {
+	path: String,
+	kind: PathErrorKind,
+}
+
+impl PathError {
+	fn new(path: &str, kind: PathErrorKind) -> Self {
+		PathError {
+			path: path.to_string(),
+			kind,
+		}
+	}
+
+	pub fn get_path(&self) -> &str {
+		&self.path
+	}
+
+	pub fn get_kind(&self) -> &PathErrorKind {
+		&self.kind
+	}
+}
+
+impl Display for PathError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		write!(f, "Invalid path `{}`: {}", self.path, self.kind)
+	}
+}
+
+impl StdError for PathError {}
+
+// Normalizes the path a handler is mounted on, and splits it into segments to be matched.
+// Panics if the path isn't valid, so that mistakes show up as soon as a route is registered
+pub(crate) fn parse_path(path: &str) -> (String, Vec<PathSegment>) {
+	try_parse_path(path).unwrap_or_else(|err| panic!("{}", err))
+}
+
+pub(crate) fn try_parse_path(path: &str) -> Result<(String, Vec<PathSegment>), PathError> {
+	let mut mounted_url = path.to_string();
+
+	// Make sure it always begins with a /
+	if mounted_url.starts_with("./") {
+		mounted_url = mounted_url[1..].to_string();
+	} else if !path.starts_with('/') {
+		mounted_url = format!("/{}", mounted_url);
+	}
+
+	// if there's a trailing /, remove it
+	if mounted_url.ends_with('/') {
+		mounted_url.pop();
+	}
+
+	// If there's nothing left, set the middleware to /
+	if mounted_url.is_empty() {
+		mounted_url.push('/');
+	}
+
+	let segments = if mounted_url == "/" {
+		vec![]
+	} else {
+		split_segments(&mounted_url[1..])
+			.into_iter()
+			.map(parse_segment)
+			.collect::<Result<Vec<_>, _>>()
+			.map_err(|kind| PathError::new(path, kind))?
+	};
 
-		// Make sure it always begins with a /
-		if mounted_url.starts_with("./") {
-			mounted_url = mounted_url[1..].to_string();
-		} else if !path.starts_with('/') {
-			mounted_url = format!("/{}", mounted_url);
+	let mut names = HashSet::new();
+	for name in segments.iter().flat_map(PathSegment::get_param_names) {
+		if !names.insert(name.clone()) {
+			return Err(PathError::new(
+				path,
+				PathErrorKind::DuplicateParam { param: name },
+			));
 		}
+	}
 
-		// if there's a trailing /, remove it
-		if mounted_url.ends_with('/') {
-			mounted_url = path[..(path.len() - 1)].to_owned();
+	Ok((mounted_url, segments))
+}
+
+// Splits on every / that isn't a part of a constraint, like the one in `:path([^/]+)`
+fn split_segments(path: &str) -> Vec<&str> {
+	let mut segments = vec![];
+	let mut start = 0;
+	let mut depth = 0;
+	let mut escaped = false;
+	for (index, c) in path.char_indices() {
+		match c {
+			_ if escaped => escaped = false,
+			'\\' if depth > 0 => escaped = true,
+			'(' | '<' => depth += 1,
+			')' | '>' if depth > 0 => depth -= 1,
+			'/' if depth == 0 => {
+				segments.push(&path[start..index]);
+				start = index + 1;
+			}
+			_ => (),
 		}
+	}
+	segments.push(&path[start..]);
+	segments
+}
 
-		// If there's nothing left, set the middleware to /
-		if mounted_url.is_empty() {
-			mounted_url.push('/');
+fn parse_segment(segment: &str) -> Result<PathSegment, PathErrorKind> {
+	match segment {
+		"*" => return Ok(PathSegment::Wildcard),
+		"**" => {
+			return Ok(PathSegment::CatchAll {
+				name: None,
+				optional: false,
+			})
 		}
+		_ => (),
+	}
+
+	// A trailing ? makes a parameter optional, like in `/:lang?/docs`
+	let (segment, optional) = match segment.strip_suffix('?') {
+		Some(segment) => (segment, true),
+		None => (segment, false),
+	};
 
-		let mut regex_path = mounted_url
-			.replace('\\', "\\\\")
-			.replace('[', "\\[")
-			.replace(']', "\\]")
-			.replace('?', "\\?")
-			.replace('+', "\\+")
-			.replace('{', "\\{")
-			.replace('}', "\\}")
-			.replace('(', "\\)")
-			.replace('(', "\\)")
-			.replace('|', "\\|")
-			.replace('^', "\\^")
-			.replace('$', "\\$")
-			.replace('.', "\\.") // Specifically, match the dot. This ain't a regex character
-			.replace('*', "([^\\/].)+") // Match anything that's not a /, but at least 1 character
-			.replace("**", "(.)+"); //Match anything
-
-		// Make a variable out of anything that begins with a : and has a-z, A-Z, 0-9, '_'
-		regex_path = Regex::new(":(?P<var>([a-zA-Z0-9_]+))")
-			.unwrap()
-			// Match that variable with anything that has a-z, A-Z, 0-9, '_', '.' and a '-'
-			.replace_all(&regex_path, "(?P<$var>([a-zA-Z0-9_\\.-]+))")
-			.to_string();
-
-		if regex_path != "/" {
-			// If there's something to match with,
-			// add the Regex to mention that both / and non / should match at the end of the url
-			regex_path.push_str("[/]?");
+	// `*name` swallows the rest of the url, like `**`, but keeps what it matched
+	if let Some(name) = segment.strip_prefix('*') {
+		if is_variable_name(name) {
+			return Ok(PathSegment::CatchAll {
+				name: Some(name.to_string()),
+				optional,
+			});
 		}
+	}
+
+	let tokens = tokenize(segment)?;
+	if let [Token::Param { name, constraint }] = tokens.as_slice() {
+		let constraint = constraint
+			.as_ref()
+			.map(|constraint| {
+				Regex::new(&format!("^(?:{})$", constraint)).map_err(|err| {
+					PathErrorKind::InvalidConstraint {
+						param: name.clone(),
+						error: err.to_string(),
+					}
+				})
+			})
+			.transpose()?;
+		return Ok(PathSegment::Param {
+			name: name.clone(),
+			constraint,
+			optional,
+		});
+	}
+	if optional {
+		return Err(PathErrorKind::NotOptional {
+			segment: segment.to_string(),
+		});
+	}
 
-		// If this is only supposed to match an endpoint URL, make sure the Regex only allows the end of the path
-		if is_endpoint {
-			regex_path.push('$');
+	match tokens.as_slice() {
+		[] => Ok(PathSegment::Static(String::new())),
+		[Token::Literal(literal)] => Ok(PathSegment::Static(literal.clone())),
+		_ => segment_regex(&tokens).map(PathSegment::Pattern),
+	}
+}
+
+enum Token {
+	Literal(String),
+	// The constraint is the source of a regex that the value has to match
+	Param {
+		name: String,
+		constraint: Option<String>,
+	},
+	Glob,
+}
+
+fn tokenize(segment: &str) -> Result<Vec<Token>, PathErrorKind> {
+	let mut tokens = vec![];
+	let mut literal = String::new();
+	let mut chars = segment.chars().peekable();
+
+	while let Some(c) = chars.next() {
+		match c {
+			':' if chars
+				.peek()
+				.map(|c| c.is_ascii_alphanumeric() || *c == '_')
+				.unwrap_or(false) =>
+			{
+				if !literal.is_empty() {
+					tokens.push(Token::Literal(literal.clone()));
+					literal.clear();
+				}
+
+				// Make a variable out of anything that begins with a : and has a-z, A-Z, 0-9, '_'
+				let mut name = String::new();
+				while let Some(c) = chars.peek() {
+					if !c.is_ascii_alphanumeric() && *c != '_' {
+						break;
+					}
+					name.push(*c);
+					chars.next();
+				}
+
+				let constraint = match chars.peek() {
+					Some('(') => {
+						chars.next();
+						Some(read_constraint(&mut chars, &name)?)
+					}
+					Some('<') => {
+						chars.next();
+						let mut kind = String::new();
+						loop {
+							match chars.next() {
+								Some('>') => break,
+								Some(c) => kind.push(c),
+								None => return Err(PathErrorKind::MissingTypeEnd { param: name }),
+							}
+						}
+						let regex =
+							type_regex(&kind).ok_or_else(|| PathErrorKind::UnknownType {
+								param: name.clone(),
+								kind: kind.clone(),
+							})?;
+						Some(regex.to_string())
+					}
+					_ => None,
+				};
+				tokens.push(Token::Param { name, constraint });
+			}
+			':' if matches!(chars.peek(), Some('(') | Some('<')) => {
+				return Err(PathErrorKind::MissingName);
+			}
+			'*' => {
+				if !literal.is_empty() {
+					tokens.push(Token::Literal(literal.clone()));
+					literal.clear();
+				}
+				tokens.push(Token::Glob);
+			}
+			c => literal.push(c),
 		}
+	}
+	if !literal.is_empty() {
+		tokens.push(Token::Literal(literal));
+	}
 
-		MiddlewareHandler {
-			is_endpoint,
-			mounted_url,
-			path_match: Regex::new(&regex_path).unwrap(),
-			handler,
-			phantom: PhantomData,
+	Ok(tokens)
+}
+
+// Reads a regex up to the ) that closes the one before it, skipping over nested
+// groups, escaped characters and character classes
+fn read_constraint<TChars>(chars: &mut TChars, name: &str) -> Result<String, PathErrorKind>
+where
+	TChars: Iterator<Item = char>,
+{
+	let mut constraint = String::new();
+	let mut depth = 0;
+	let mut in_class = false;
+	while let Some(c) = chars.next() {
+		match c {
+			'\\' => {
+				constraint.push(c);
+				if let Some(c) = chars.next() {
+					constraint.push(c);
+				}
+				continue;
+			}
+			'[' if !in_class => in_class = true,
+			']' if in_class => in_class = false,
+			'(' if !in_class => depth += 1,
+			')' if !in_class && depth == 0 => {
+				if constraint.is_empty() {
+					return Err(PathErrorKind::EmptyConstraint {
+						param: name.to_string(),
+					});
+				}
+				return Ok(constraint);
+			}
+			')' if !in_class => depth -= 1,
+			_ => (),
+		}
+		constraint.push(c);
+	}
+	Err(PathErrorKind::MissingConstraintEnd {
+		param: name.to_string(),
+	})
+}
+
+// The regexes behind the types that can be given to a parameter, like `:id<uuid>`
+fn type_regex(kind: &str) -> Option<&'static str> {
+	match kind {
+		"int" => Some("-?[0-9]+"),
+		"uint" => Some("[0-9]+"),
+		"alpha" => Some("[a-zA-Z]+"),
+		"alnum" => Some("[a-zA-Z0-9]+"),
+		"hex" => Some("[0-9a-fA-F]+"),
+		"slug" => Some("[a-z0-9]+(?:-[a-z0-9]+)*"),
+		"uuid" => {
+			Some("[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}")
+		}
+		_ => None,
+	}
+}
+
+// Whether the constraint is one of the types above, all of which only allow
+// what a param without a constraint does
+pub(crate) fn is_type_constraint(constraint: &Regex) -> bool {
+	["int", "uint", "alpha", "alnum", "hex", "slug", "uuid"]
+		.iter()
+		.filter_map(|kind| type_regex(kind))
+		.any(|regex| constraint.as_str() == format!("^(?:{})$", regex))
+}
+
+fn is_variable_name(name: &str) -> bool {
+	!name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn segment_regex(tokens: &[Token]) -> Result<Regex, PathErrorKind> {
+	let mut regex_path = String::from("^");
+	for token in tokens {
+		match token {
+			Token::Literal(literal) => regex_path.push_str(&regex::escape(literal)),
+			// Match a variable with its constraint, or with anything that has a-z, A-Z, 0-9,
+			// '_', '.', '-' and percent-encoded characters if it doesn't have one
+			Token::Param { name, constraint } => regex_path.push_str(&format!(
+				"(?P<{}>{})",
+				name,
+				constraint
+					.as_ref()
+					.map(|constraint| format!("(?:{})", constraint))
+					.unwrap_or_else(|| "[a-zA-Z0-9_\\.%-]+".to_string())
+			)),
+			// Match anything within the segment
+			Token::Glob => regex_path.push_str("[^/]*"),
+		}
+	}
+	regex_path.push('$');
+
+	Regex::new(&regex_path).map_err(|err| PathErrorKind::InvalidPattern {
+		error: err.to_string(),
+	})
+}
+
+// Everything but a-z, A-Z, 0-9, '_', '.' and '-' is encoded, so that the value always matches
+// a param without a constraint
+const PARAM_VALUE: &AsciiSet = &NON_ALPHANUMERIC.remove(b'_').remove(b'.').remove(b'-');
+
+// Fills the params into a path, which is the other way round from matching it. Params that
+// the path doesn't have are added as a query string
+pub(crate) fn build_path(path: &str, params: &[(&str, &str)]) -> Result<String, UrlForError> {
+	let (mounted_url, segments) = parse_path(path);
+	let mut used = HashSet::new();
+	let mut get_param = |name: &str| {
+		let value = params
+			.iter()
+			.find(|(key, _)| *key == name)
+			.map(|(_, value)| *value);
+		if value.is_some() {
+			used.insert(name.to_string());
+		}
+		value
+	};
+
+	let mut built = vec![];
+	let sources = if mounted_url == "/" {
+		vec![]
+	} else {
+		split_segments(&mounted_url[1..])
+	};
+	for (segment, source) in segments.iter().zip(sources) {
+		match segment {
+			PathSegment::Static(value) => built.push(value.clone()),
+			PathSegment::Param {
+				name,
+				constraint,
+				optional,
+			} => match get_param(name) {
+				Some(value) => {
+					let value = utf8_percent_encode(value, PARAM_VALUE).to_string();
+					if constraint
+						.as_ref()
+						.is_some_and(|constraint| !constraint.is_match(&value))
+					{
+						return Err(UrlForError::InvalidParam(name.clone()));
+					}
+					built.push(value);
+				}
+				None if *optional => (),
+				None => return Err(UrlForError::MissingParam(name.clone())),
+			},
+			PathSegment::CatchAll {
+				name: Some(name),
+				optional,
+			} => match get_param(name) {
+				Some(value) => built.push(
+					value
+						.split('/')
+						.map(|value| utf8_percent_encode(value, PARAM_VALUE).to_string())
+						.collect::<Vec<_>>()
+						.join("/"),
+				),
+				None if *optional => (),
+				None => return Err(UrlForError::MissingParam(name.clone())),
+			},
+			PathSegment::Wildcard | PathSegment::CatchAll { name: None, .. } => {
+				return Err(UrlForError::UnnamedWildcard);
+			}
+			PathSegment::Pattern(regex) => {
+				let mut value = String::new();
+				for token in tokenize(source).unwrap_or_default() {
+					match token {
+						Token::Literal(literal) => value.push_str(&literal),
+						Token::Param { name, .. } => match get_param(&name) {
+							Some(param) => {
+								value.push_str(&utf8_percent_encode(param, PARAM_VALUE).to_string())
+							}
+							None => return Err(UrlForError::MissingParam(name)),
+						},
+						Token::Glob => return Err(UrlForError::UnnamedWildcard),
+					}
+				}
+				if !regex.is_match(&value) {
+					return Err(UrlForError::InvalidParam(
+						regex
+							.capture_names()
+							.flatten()
+							.collect::<Vec<_>>()
+							.join(", "),
+					));
+				}
+				built.push(value);
+			}
 		}
 	}
 
-	pub(crate) fn is_match(&self, url: &str) -> bool {
-		self.path_match.is_match(url)
+	let mut url = format!("/{}", built.join("/"));
+	let query = params
+		.iter()
+		.filter(|(key, _)| !used.contains(*key))
+		.collect::<Vec<_>>();
+	if !query.is_empty() {
+		url.push('?');
+		url.push_str(&serde_urlencoded::to_string(query).unwrap_or_default());
 	}
+	Ok(url)
 }
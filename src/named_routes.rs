@@ -0,0 +1,199 @@
+use crate::middleware_handler::{build_path, parse_path, PathSegment};
+#[cfg(feature = "render")]
+use handlebars::{
+	Context as HandlebarsContext,
+	Handlebars,
+	Helper,
+	HelperDef,
+	HelperResult,
+	Output,
+	RenderContext,
+	RenderError,
+};
+#[cfg(feature = "render")]
+use serde_json::Value;
+use std::{
+	collections::BTreeMap,
+	error::Error as StdError,
+	fmt::{Display, Formatter, Result as FmtResult},
+	sync::{Arc, PoisonError, RwLock},
+};
+
+// Why a url couldn't be built for a named route
+#[derive(Clone, Debug)]
+pub enum UrlForError {
+	UnknownRoute(String),
+	MissingParam(String),
+	// The value doesn't match the constraint the param has in the route
+	InvalidParam(String),
+	// `*` and `**` can't be filled in, since there's no name to give their value under
+	UnnamedWildcard,
+}
+
+impl Display for UrlForError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		match self {
+			UrlForError::UnknownRoute(name) => write!(f, "No route is named `{}`", name),
+			UrlForError::MissingParam(name) => write!(f, "The param `{}` is missing", name),
+			UrlForError::InvalidParam(name) => {
+				write!(
+					f,
+					"The param `{}` doesn't match the route's constraint",
+					name
+				)
+			}
+			UrlForError::UnnamedWildcard => {
+				write!(
+					f,
+					"The route has a wildcard without a name, which can't be filled in"
+				)
+			}
+		}
+	}
+}
+
+impl StdError for UrlForError {}
+
+// The paths of the routes that were given a name, so that urls can be built from the name
+// instead of being hard coded. Clones share the same routes, so a clone taken before the routes
+// are registered (like the one given to handlebars as a helper) still knows about them. A sub
+// app's routes are copied into the app it's mounted on, under the base path and with the base
+// path as a prefix to their names, so the helper should get the named routes of the root app
+#[derive(Clone, Debug, Default)]
+pub struct NamedRoutes {
+	routes: Arc<RwLock<BTreeMap<String, String>>>,
+}
+
+impl NamedRoutes {
+	pub fn new() -> Self {
+		NamedRoutes::default()
+	}
+
+	// Panics if the name is taken, so that two routes can't be mixed up
+	pub(crate) fn insert(&self, name: &str, path: &str) {
+		let (mounted_url, _) = parse_path(path);
+		let mut routes = self.routes.write().unwrap_or_else(PoisonError::into_inner);
+		if routes.contains_key(name) {
+			panic!("A route named `{}` already exists", name);
+		}
+		routes.insert(name.to_string(), mounted_url);
+	}
+
+	// Unlike a clone, the copy has routes of its own from then on
+	pub(crate) fn copy(&self) -> Self {
+		let routes = self
+			.routes
+			.read()
+			.unwrap_or_else(PoisonError::into_inner)
+			.clone();
+		NamedRoutes {
+			routes: Arc::new(RwLock::new(routes)),
+		}
+	}
+
+	pub fn get_path(&self, name: &str) -> Option<String> {
+		self.routes
+			.read()
+			.unwrap_or_else(PoisonError::into_inner)
+			.get(name)
+			.cloned()
+	}
+
+	// Builds the url of the route with the given name. Params that the route doesn't have are
+	// added as a query string
+	pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Result<String, UrlForError> {
+		let path = self
+			.get_path(name)
+			.ok_or_else(|| UrlForError::UnknownRoute(name.to_string()))?;
+		build_path(&path, params)
+	}
+
+	// Copies in the routes of a sub app mounted on the base path, leaving the sub app's own
+	// untouched. A name that's already taken keeps the route it had
+	pub(crate) fn extend(&self, base_path: &str, other: &NamedRoutes) {
+		if Arc::ptr_eq(&self.routes, &other.routes) {
+			return;
+		}
+		let mounted = other
+			.routes
+			.read()
+			.unwrap_or_else(PoisonError::into_inner)
+			.iter()
+			.map(|(name, path)| {
+				(
+					get_mounted_name(base_path, name),
+					parse_path(&format!("{}{}", base_path, path)).0,
+				)
+			})
+			.collect::<Vec<_>>();
+
+		let mut routes = self.routes.write().unwrap_or_else(PoisonError::into_inner);
+		for (name, path) in mounted {
+			match routes.get(&name) {
+				Some(existing) if existing != &path => log::warn!(
+					"A route named `{}` already exists, so it isn't given to `{}`",
+					name,
+					path
+				),
+				Some(_) => (),
+				None => {
+					routes.insert(name, path);
+				}
+			}
+		}
+	}
+}
+
+// What a route is called once its app is mounted on the base path, like `api.index` for
+// `index` under `/api`. Params go by their name, so it's `users.id.index` under `/users/:id`,
+// and unnamed wildcards are left out. The name stays the same at the root
+pub(crate) fn get_mounted_name(base_path: &str, name: &str) -> String {
+	let (_, segments) = parse_path(base_path);
+	let parts = segments
+		.iter()
+		.flat_map(|segment| match segment {
+			PathSegment::Static(segment) => vec![segment.clone()],
+			segment => segment.get_param_names(),
+		})
+		.filter(|part| !part.is_empty())
+		.chain(std::iter::once(name.to_string()))
+		.collect::<Vec<_>>();
+	parts.join(".")
+}
+
+// Used as `{{url_for "user.show" id=user.id}}`, once registered with handlebars as `url_for`
+#[cfg(feature = "render")]
+impl HelperDef for NamedRoutes {
+	fn call<'reg: 'rc, 'rc>(
+		&self,
+		helper: &Helper<'reg, 'rc>,
+		_: &'reg Handlebars<'reg>,
+		_: &'rc HandlebarsContext,
+		_: &mut RenderContext<'reg, 'rc>,
+		out: &mut dyn Output,
+	) -> HelperResult {
+		let name = helper
+			.param(0)
+			.and_then(|param| param.value().as_str())
+			.ok_or_else(|| RenderError::new("url_for needs the name of a route"))?;
+		let params = helper
+			.hash()
+			.iter()
+			.filter_map(|(key, value)| match value.value() {
+				Value::Null => None,
+				Value::String(value) => Some((*key, value.clone())),
+				value => Some((*key, value.to_string())),
+			})
+			.collect::<Vec<_>>();
+		let params = params
+			.iter()
+			.map(|(key, value)| (*key, value.as_str()))
+			.collect::<Vec<_>>();
+
+		let url = self
+			.url_for(name, &params)
+			.map_err(|err| RenderError::new(err.to_string()))?;
+		out.write(&url)?;
+		Ok(())
+	}
+}
@@ -6,7 +6,7 @@ use serde::Serialize;
 use crate::Context;
 
 pub trait RenderEngine: Context {
-	fn get_register(&self) -> &Arc<Handlebars>;
+	fn get_register(&self) -> &Arc<Handlebars<'_>>;
 	fn set_register(&mut self, register: Arc<Handlebars<'static>>);
 
 	fn render<TParams>(
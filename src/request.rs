@@ -1,28 +1,56 @@
-use crate::{cookie::Cookie, HttpMethod};
-use hyper::{body, Body, Request as HyperRequest, Uri, Version};
+use crate::{
+	body::{BodyError, BodyReader, BodyStream},
+	connection::DrainGuard,
+	cookie::Cookie,
+	extensions::Extensions,
+	listener::RemoteAddr,
+	HttpMethod,
+};
+use futures::TryStreamExt;
+use hyper::{Body, Request as HyperRequest, Uri, Version};
+use serde::de::{DeserializeOwned, Error as _};
+use serde_urlencoded::de::Error as DeError;
 use std::{
+	any::Any,
 	collections::HashMap,
 	fmt::{Debug, Formatter, Result as FmtResult},
-	net::{IpAddr, SocketAddr},
-	str::{self, Utf8Error},
+	net::IpAddr,
+	str,
+	sync::{Arc, Mutex},
 };
 
 #[derive(Clone)]
 pub struct Request {
-	pub(crate) socket_addr: SocketAddr,
-	pub(crate) body: Vec<u8>,
+	pub(crate) remote_addr: RemoteAddr,
+	// Whether the request came in over a TLS connection
+	pub(crate) secure: bool,
+	// The body as it comes in from the client, until someone reads it
+	pub(crate) body_stream: Arc<Mutex<Option<Body>>>,
+	// Only there once load_body has read the body stream
+	pub(crate) body: Option<Vec<u8>>,
 	pub(crate) method: HttpMethod,
 	pub(crate) uri: Uri,
 	pub(crate) version: (u8, u8),
 	pub(crate) headers: HashMap<String, Vec<String>>,
+	// The first value of every key in the query string. The rest are only in the pairs
 	pub(crate) query: HashMap<String, String>,
+	pub(crate) query_pairs: Vec<(String, String)>,
 	pub(crate) params: HashMap<String, String>,
 	pub(crate) cookies: Vec<Cookie>,
+	pub(crate) extensions: Extensions,
+	// The state of the app whose middleware is handling the request
+	pub(crate) state: Option<Arc<dyn Any + Send + Sync>>,
+	// Set by the server, for when the connection is taken over by a websocket
+	pub(crate) drain_guard: Option<DrainGuard>,
 }
 
 impl Request {
-	pub async fn from_hyper(socket_addr: SocketAddr, req: HyperRequest<Body>) -> Self {
+	pub async fn from_hyper<TRemoteAddr>(remote_addr: TRemoteAddr, req: HyperRequest<Body>) -> Self
+	where
+		TRemoteAddr: Into<RemoteAddr>,
+	{
 		let (parts, body) = req.into_parts();
+		let query_pairs = parse_query(parts.uri.query().unwrap_or(""));
 		let mut headers = HashMap::<String, Vec<String>>::new();
 		parts.headers.iter().for_each(|(key, value)| {
 			let key = key.to_string();
@@ -40,8 +68,10 @@ impl Request {
 			}
 		});
 		Request {
-			socket_addr,
-			body: body::to_bytes(body).await.unwrap().to_vec(),
+			remote_addr: remote_addr.into(),
+			secure: false,
+			body_stream: Arc::new(Mutex::new(Some(body))),
+			body: None,
 			method: HttpMethod::from(parts.method),
 			uri: parts.uri.clone(),
 			version: match parts.version {
@@ -53,34 +83,53 @@ impl Request {
 				_ => (0, 0),
 			},
 			headers: headers.clone(),
-			query: if let Some(query) = parts.uri.query() {
-				query
-					.split('&')
-					.filter_map(|kv| {
-						if !kv.contains('=') {
-							None
-						} else {
-							let mut items = kv.split('=').map(String::from);
-							let key = items.next()?;
-							let value = items.next()?;
-							Some((key, value))
-						}
-					})
-					.collect()
-			} else {
-				HashMap::new()
-			},
+			query: query_pairs
+				.iter()
+				.rev()
+				.map(|(key, value)| (key.clone(), value.clone()))
+				.collect(),
+			query_pairs,
 			params: HashMap::new(),
+			extensions: Extensions::new(),
+			state: None,
 			cookies: vec![],
+			drain_guard: None,
+		}
+	}
+
+	pub fn take_body_stream(&mut self) -> Option<BodyStream> {
+		self.body_stream.lock().ok()?.take().map(BodyStream::new)
+	}
+
+	pub fn get_body_reader(&mut self) -> Option<BodyReader> {
+		self.take_body_stream().map(BodyStream::into_reader)
+	}
+
+	// Reads the entire body into memory, so that it can be used by get_body and get_body_bytes.
+	// Fails with BodyError::StreamTaken if the body stream was taken before it could be loaded
+	pub async fn load_body(&mut self) -> Result<&[u8], BodyError> {
+		if self.body.is_none() {
+			let stream = self.take_body_stream().ok_or(BodyError::StreamTaken)?;
+			let body = stream
+				.try_fold(vec![], |mut body, chunk| async move {
+					body.extend_from_slice(&chunk);
+					Ok(body)
+				})
+				.await?;
+			self.body = Some(body);
 		}
+		Ok(self.body.as_deref().unwrap_or_default())
 	}
 
-	pub fn get_body_bytes(&self) -> &[u8] {
-		&self.body
+	// None until load_body has been awaited, since the body isn't read up front
+	pub fn get_body_bytes(&self) -> Option<&[u8]> {
+		self.body.as_deref()
 	}
 
-	pub fn get_body(&self) -> Result<String, Utf8Error> {
-		Ok(str::from_utf8(&self.body)?.to_string())
+	// Fails with BodyError::NotLoaded until load_body has been awaited
+	pub fn get_body(&self) -> Result<String, BodyError> {
+		let body = self.get_body_bytes().ok_or(BodyError::NotLoaded)?;
+		Ok(str::from_utf8(body)?.to_string())
 	}
 
 	pub fn get_method(&self) -> &HttpMethod {
@@ -89,13 +138,13 @@ impl Request {
 
 	pub fn get_length(&self) -> u128 {
 		if let Some(length) = self.headers.get("Content-Length") {
-			if let Some(value) = length.get(0) {
+			if let Some(value) = length.first() {
 				if let Ok(value) = value.parse::<u128>() {
 					return value;
 				}
 			}
 		}
-		self.body.len() as u128
+		self.body.as_ref().map_or(0, Vec::len) as u128
 	}
 
 	pub fn get_path(&self) -> String {
@@ -122,7 +171,7 @@ impl Request {
 		self.uri
 			.host()
 			.map(String::from)
-			.unwrap_or_else(|| self.get_header("host").unwrap_or_else(|| "".to_string()))
+			.unwrap_or_else(|| self.get_header("host").unwrap_or_default())
 	}
 
 	pub fn get_host_and_port(&self) -> String {
@@ -148,20 +197,31 @@ impl Request {
 		let header = self.get_header("Content-Type")?;
 		let charset_index = header.find("charset=")?;
 		let data = &header[charset_index..];
-		Some(data[(charset_index + 8)..data.find(';').unwrap_or_else(|| data.len())].to_string())
+		Some(data[(charset_index + 8)..data.find(';').unwrap_or(data.len())].to_string())
 	}
 
 	pub fn get_protocol(&self) -> String {
 		// TODO support X-Forwarded-Proto
-		self.uri.scheme_str().unwrap_or("http").to_string()
+		if let Some(scheme) = self.uri.scheme_str() {
+			scheme.to_string()
+		} else if self.secure {
+			"https".to_string()
+		} else {
+			"http".to_string()
+		}
 	}
 
 	pub fn is_secure(&self) -> bool {
 		self.get_protocol() == "https"
 	}
 
-	pub fn get_ip(&self) -> IpAddr {
-		self.socket_addr.ip()
+	pub fn get_remote_addr(&self) -> &RemoteAddr {
+		&self.remote_addr
+	}
+
+	// None for clients connected over a unix socket
+	pub fn get_ip(&self) -> Option<IpAddr> {
+		self.remote_addr.get_ip()
 	}
 
 	pub fn is(&self, mimes: &[&str]) -> bool {
@@ -169,8 +229,11 @@ impl Request {
 		mimes.iter().any(|mime| mime == &given)
 	}
 
-	// TODO content negotiation
-	// See: https://koajs.com/#request content negotiation
+	// Returns whichever of the given types the client prefers, going by the Accept header,
+	// or None if it accepts none of them. Ties go to the type that's listed first
+	pub fn accepts<'a>(&self, mimes: &[&'a str]) -> Option<&'a str> {
+		negotiate(self.get_header("Accept").as_deref(), mimes)
+	}
 
 	pub fn get_version(&self) -> String {
 		format!("{}.{}", self.version.0, self.version.1)
@@ -215,10 +278,53 @@ impl Request {
 		&self.query
 	}
 
+	// Every value of a key that's repeated in the query string, like `?tag=a&tag=b`, in order
+	pub fn get_query_all(&self, key: &str) -> Vec<&str> {
+		self.query_pairs
+			.iter()
+			.filter(|(name, _)| name == key)
+			.map(|(_, value)| value.as_str())
+			.collect()
+	}
+
+	pub fn get_query_as<TQuery>(&self) -> Result<TQuery, DeError>
+	where
+		TQuery: DeserializeOwned,
+	{
+		serde_urlencoded::from_str(self.uri.query().unwrap_or(""))
+	}
+
 	pub fn get_params(&self) -> &HashMap<String, String> {
 		&self.params
 	}
 
+	pub fn get_params_as<TParams>(&self) -> Result<TParams, DeError>
+	where
+		TParams: DeserializeOwned,
+	{
+		// Going through the url encoding lets the values be parsed into numbers, bools, etc.
+		let params = serde_urlencoded::to_string(&self.params)
+			.map_err(|err| DeError::custom(err.to_string()))?;
+		serde_urlencoded::from_str(&params)
+	}
+
+	pub fn get_extensions(&self) -> &Extensions {
+		&self.extensions
+	}
+
+	pub fn get_extensions_mut(&mut self) -> &mut Extensions {
+		&mut self.extensions
+	}
+
+	// The state of the app (or sub app) the current middleware was registered on,
+	// if it's of the given type
+	pub fn get_state<TState>(&self) -> Option<&TState>
+	where
+		TState: 'static,
+	{
+		self.state.as_ref()?.downcast_ref::<TState>()
+	}
+
 	pub fn get_cookies(&self) -> &Vec<Cookie> {
 		&self.cookies
 	}
@@ -232,15 +338,17 @@ impl Request {
 impl Debug for Request {
 	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
 		f.debug_struct("Request")
-			.field("socket_addr", &self.socket_addr)
+			.field("remote_addr", &self.remote_addr)
+			.field("secure", &self.secure)
 			.field("body", &self.body)
 			.field("method", &self.method)
 			.field("uri", &self.uri)
 			.field("version", &self.version)
 			.field("headers", &self.headers)
-			.field("query", &self.query)
+			.field("query", &self.query_pairs)
 			.field("params", &self.params)
 			.field("cookies", &self.cookies)
+			.field("extensions", &self.extensions)
 			.finish()
 	}
 }
@@ -251,3 +359,61 @@ impl Debug for Request {
 		write!(f, "[Request {} {}]", self.method, self.get_path())
 	}
 }
+
+// Decodes the query string. Keys without a value get an empty one
+fn parse_query(query: &str) -> Vec<(String, String)> {
+	serde_urlencoded::from_str(query).unwrap_or_default()
+}
+
+pub(crate) fn negotiate<'a>(accept: Option<&str>, mimes: &[&'a str]) -> Option<&'a str> {
+	let accept = match accept {
+		Some(accept) if !accept.trim().is_empty() => accept,
+		_ => return mimes.first().copied(),
+	};
+	let ranges = accept
+		.split([',', '\n'])
+		.filter_map(|range| {
+			let mut parts = range.split(';');
+			let mime = parts.next()?.trim().to_lowercase();
+			let quality = parts
+				.filter_map(|param| {
+					let (key, value) = param.split_once('=')?;
+					if key.trim() == "q" {
+						value.trim().parse::<f32>().ok()
+					} else {
+						None
+					}
+				})
+				.next()
+				.unwrap_or(1.0);
+			Some((mime, quality))
+		})
+		.collect::<Vec<_>>();
+
+	let mut best: Option<(&str, f32)> = None;
+	for mime in mimes {
+		let lowercase = mime.to_lowercase();
+		let main_type = lowercase.split('/').next().unwrap_or("");
+		// The most specific range that matches decides the quality
+		let quality = ranges
+			.iter()
+			.filter_map(|(range, quality)| {
+				if range == &lowercase {
+					Some((2, *quality))
+				} else if range.strip_suffix("/*") == Some(main_type) {
+					Some((1, *quality))
+				} else if range == "*/*" {
+					Some((0, *quality))
+				} else {
+					None
+				}
+			})
+			.max_by_key(|(specificity, _)| *specificity)
+			.map(|(_, quality)| quality)
+			.unwrap_or(0.0);
+		if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+			best = Some((mime, quality));
+		}
+	}
+	best.map(|(mime, _)| mime)
+}
@@ -1,13 +1,23 @@
-use crate::Cookie;
+use crate::{
+	body::{ResponseBody, SharedBody},
+	request::negotiate,
+	Cookie,
+	HttpError,
+};
 use chrono::Local;
+use futures::Stream;
+use hyper::{body::Bytes, StatusCode};
+use serde_json::json;
 use std::{
 	collections::HashMap,
 	fmt::{Debug, Formatter, Result as FmtResult},
+	io::Error as IoError,
 };
+use tokio::fs::File;
 
 #[derive(Clone)]
 pub struct Response {
-	pub(crate) body: Vec<u8>,
+	pub(crate) body: ResponseBody,
 	pub(crate) status: u16,
 	pub(crate) headers: HashMap<String, Vec<String>>,
 }
@@ -15,7 +25,7 @@ pub struct Response {
 impl Response {
 	pub fn new() -> Self {
 		Response {
-			body: vec![],
+			body: ResponseBody::default(),
 			status: 200,
 			headers: HashMap::new(),
 		}
@@ -165,21 +175,122 @@ impl Response {
 		self.set_header("ETag", etag);
 	}
 
-	pub fn get_body(&self) -> &Vec<u8> {
+	// Returns the body if it's already in memory. Streamed bodies are empty here
+	pub fn get_body(&self) -> &[u8] {
+		match &self.body {
+			ResponseBody::Bytes(bytes) => bytes,
+			_ => &[],
+		}
+	}
+	pub fn get_response_body(&self) -> &ResponseBody {
 		&self.body
 	}
+	pub fn take_body(&mut self) -> ResponseBody {
+		std::mem::take(&mut self.body)
+	}
 	pub fn set_body(&mut self, data: &str) {
 		self.set_body_bytes(data.as_bytes());
 	}
 	pub fn set_body_bytes(&mut self, data: &[u8]) {
-		self.body = data.to_vec();
+		self.body = ResponseBody::Bytes(data.to_vec());
 		self.set_content_length(data.len());
 		self.set_header("date", &Local::now().to_rfc2822());
 	}
+	// The length of a stream isn't known, so it's sent with a chunked transfer encoding
+	pub fn set_body_stream<TStream>(&mut self, stream: TStream)
+	where
+		TStream: 'static + Stream<Item = Result<Bytes, IoError>> + Send,
+	{
+		self.body = ResponseBody::Stream(SharedBody::new(Box::pin(stream)));
+		self.remove_header("content-length");
+		self.set_header("date", &Local::now().to_rfc2822());
+	}
+	pub fn set_body_file(&mut self, file: File, length: Option<u64>) {
+		self.body = ResponseBody::File(SharedBody::new(file));
+		if let Some(length) = length {
+			self.set_header("content-length", &format!("{}", length));
+		} else {
+			self.remove_header("content-length");
+		}
+		self.set_header("date", &Local::now().to_rfc2822());
+	}
 
 	pub fn set_cookie(&mut self, cookie: Cookie) {
 		self.append_header("Set-Cookie", &cookie.to_header_string());
 	}
+
+	// Renders an error as plain text, JSON or problem+json (RFC 7807), whichever the given
+	// Accept header prefers. Plain text is used if the client doesn't care
+	pub fn set_error(&mut self, status: u16, message: &str, accept: Option<&str>) {
+		self.set_status(status);
+		let title = StatusCode::from_u16(status)
+			.ok()
+			.and_then(|status| status.canonical_reason())
+			.unwrap_or_else(|| self.get_status_message())
+			.to_string();
+		match negotiate(
+			accept,
+			&["text/plain", "application/json", "application/problem+json"],
+		) {
+			Some("application/json") => {
+				self.set_content_type("application/json");
+				self.set_body(
+					&json!({
+						"status": status,
+						"message": message,
+					})
+					.to_string(),
+				);
+			}
+			Some("application/problem+json") => {
+				self.set_content_type("application/problem+json");
+				self.set_body(
+					&json!({
+						"type": "about:blank",
+						"title": title,
+						"status": status,
+						"detail": message,
+					})
+					.to_string(),
+				);
+			}
+			_ => {
+				self.set_content_type("text/plain; charset=utf-8");
+				self.set_body(message);
+			}
+		}
+	}
+
+	// Renders an HttpError as problem details, along with the headers it needs. Clients that only
+	// accept plain text get the detail as is
+	pub fn set_http_error(
+		&mut self,
+		error: &HttpError,
+		instance: Option<&str>,
+		accept: Option<&str>,
+	) {
+		self.set_status(error.get_status());
+		for (key, value) in error.get_headers() {
+			self.set_header(key, &value);
+		}
+		match negotiate(
+			accept,
+			&["application/problem+json", "application/json", "text/plain"],
+		) {
+			Some("text/plain") => {
+				self.set_content_type("text/plain; charset=utf-8");
+				self.set_body(&error.to_string());
+			}
+			Some("application/json") => {
+				self.set_content_type("application/json");
+				self.set_body(&error.to_problem_json(instance).to_string());
+			}
+			_ => {
+				self.set_content_type("application/problem+json");
+				self.set_body(&error.to_problem_json(instance).to_string());
+			}
+		}
+	}
 }
 
 impl Debug for Response {
@@ -199,7 +310,7 @@ impl Debug for Response {
 impl Default for Response {
 	fn default() -> Self {
 		Response {
-			body: vec![],
+			body: ResponseBody::default(),
 			status: 200,
 			headers: HashMap::new(),
 		}
@@ -0,0 +1,32 @@
+use crate::middleware_handler::PathError;
+use std::{
+	error::Error as StdError,
+	fmt::{Display, Formatter, Result as FmtResult},
+};
+
+// Why a route couldn't be registered
+#[derive(Clone, Debug)]
+pub enum RouteError {
+	// Not a valid token, see HttpMethod::from_str
+	InvalidMethod(String),
+	InvalidPath(PathError),
+}
+
+impl Display for RouteError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		match self {
+			RouteError::InvalidMethod(method) => {
+				write!(f, "`{}` isn't a valid HTTP method", method)
+			}
+			RouteError::InvalidPath(err) => write!(f, "{}", err),
+		}
+	}
+}
+
+impl StdError for RouteError {}
+
+impl From<PathError> for RouteError {
+	fn from(err: PathError) -> Self {
+		RouteError::InvalidPath(err)
+	}
+}
@@ -0,0 +1,252 @@
+use crate::{app::format_base_path, route_error::RouteError, App, Context, HttpMethod, Middleware};
+use std::fmt::Debug;
+
+// Routes registered under a common base path, see App::group. The middlewares of the group are
+// put in front of every endpoint registered on it after them, so they don't run for anything else
+pub struct RouteGroup<'a, TContext, TMiddleware, TState>
+where
+	TContext: 'static + Context + Debug + Send + Sync,
+	TMiddleware: 'static + Middleware<TContext> + Clone + Send + Sync,
+	TState: 'static + Send + Sync,
+{
+	app: &'a mut App<TContext, TMiddleware, TState>,
+	base_path: String,
+	middlewares: Vec<TMiddleware>,
+}
+
+impl<'a, TContext, TMiddleware, TState> RouteGroup<'a, TContext, TMiddleware, TState>
+where
+	TContext: 'static + Context + Debug + Send + Sync,
+	TMiddleware: 'static + Middleware<TContext> + Clone + Send + Sync,
+	TState: 'static + Send + Sync,
+{
+	pub(crate) fn new(
+		app: &'a mut App<TContext, TMiddleware, TState>,
+		base_path: String,
+		middlewares: Vec<TMiddleware>,
+	) -> Self {
+		RouteGroup {
+			app,
+			base_path,
+			middlewares,
+		}
+	}
+
+	pub fn get_base_path(&self) -> &str {
+		if self.base_path.is_empty() {
+			"/"
+		} else {
+			&self.base_path
+		}
+	}
+
+	pub fn use_middleware(&mut self, middlewares: &[TMiddleware]) {
+		self.middlewares.extend_from_slice(middlewares);
+	}
+
+	// A group within the group, which starts off with the middlewares this one has so far
+	pub fn group<TRegister>(&mut self, base_path: &str, register: TRegister)
+	where
+		TRegister: FnOnce(&mut RouteGroup<'_, TContext, TMiddleware, TState>),
+	{
+		let base_path = self.get_path(base_path);
+		let middlewares = self.middlewares.clone();
+		register(&mut RouteGroup::new(self.app, base_path, middlewares));
+	}
+
+	pub fn get(&mut self, path: &str, middlewares: &[TMiddleware]) {
+		self.try_get(path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
+	}
+
+	pub fn post(&mut self, path: &str, middlewares: &[TMiddleware]) {
+		self.try_post(path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
+	}
+
+	pub fn put(&mut self, path: &str, middlewares: &[TMiddleware]) {
+		self.try_put(path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
+	}
+
+	pub fn delete(&mut self, path: &str, middlewares: &[TMiddleware]) {
+		self.try_delete(path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
+	}
+
+	pub fn head(&mut self, path: &str, middlewares: &[TMiddleware]) {
+		self.try_head(path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
+	}
+
+	pub fn options(&mut self, path: &str, middlewares: &[TMiddleware]) {
+		self.try_options(path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
+	}
+
+	pub fn connect(&mut self, path: &str, middlewares: &[TMiddleware]) {
+		self.try_connect(path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
+	}
+
+	pub fn patch(&mut self, path: &str, middlewares: &[TMiddleware]) {
+		self.try_patch(path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
+	}
+
+	pub fn trace(&mut self, path: &str, middlewares: &[TMiddleware]) {
+		self.try_trace(path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
+	}
+
+	pub fn method(
+		&mut self,
+		method: &str,
+		path: &str,
+		middlewares: &[TMiddleware],
+	) -> Result<(), RouteError> {
+		let method = method
+			.parse::<HttpMethod>()
+			.map_err(|_| RouteError::InvalidMethod(method.to_string()))?;
+		self.try_push(method, path, middlewares, None)
+	}
+
+	pub fn get_named(&mut self, name: &str, path: &str, middlewares: &[TMiddleware]) {
+		self.try_get_named(name, path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
+	}
+
+	pub fn post_named(&mut self, name: &str, path: &str, middlewares: &[TMiddleware]) {
+		self.try_post_named(name, path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
+	}
+
+	pub fn put_named(&mut self, name: &str, path: &str, middlewares: &[TMiddleware]) {
+		self.try_put_named(name, path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
+	}
+
+	pub fn delete_named(&mut self, name: &str, path: &str, middlewares: &[TMiddleware]) {
+		self.try_delete_named(name, path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
+	}
+
+	pub fn patch_named(&mut self, name: &str, path: &str, middlewares: &[TMiddleware]) {
+		self.try_patch_named(name, path, middlewares)
+			.unwrap_or_else(|err| panic!("{}", err));
+	}
+
+	// Same as the ones on App, an invalid path is returned as an error and nothing is registered
+	pub fn try_get(&mut self, path: &str, middlewares: &[TMiddleware]) -> Result<(), RouteError> {
+		self.try_push(HttpMethod::Get, path, middlewares, None)
+	}
+
+	pub fn try_post(&mut self, path: &str, middlewares: &[TMiddleware]) -> Result<(), RouteError> {
+		self.try_push(HttpMethod::Post, path, middlewares, None)
+	}
+
+	pub fn try_put(&mut self, path: &str, middlewares: &[TMiddleware]) -> Result<(), RouteError> {
+		self.try_push(HttpMethod::Put, path, middlewares, None)
+	}
+
+	pub fn try_delete(
+		&mut self,
+		path: &str,
+		middlewares: &[TMiddleware],
+	) -> Result<(), RouteError> {
+		self.try_push(HttpMethod::Delete, path, middlewares, None)
+	}
+
+	pub fn try_head(&mut self, path: &str, middlewares: &[TMiddleware]) -> Result<(), RouteError> {
+		self.try_push(HttpMethod::Head, path, middlewares, None)
+	}
+
+	pub fn try_options(
+		&mut self,
+		path: &str,
+		middlewares: &[TMiddleware],
+	) -> Result<(), RouteError> {
+		self.try_push(HttpMethod::Options, path, middlewares, None)
+	}
+
+	pub fn try_connect(
+		&mut self,
+		path: &str,
+		middlewares: &[TMiddleware],
+	) -> Result<(), RouteError> {
+		self.try_push(HttpMethod::Connect, path, middlewares, None)
+	}
+
+	pub fn try_patch(&mut self, path: &str, middlewares: &[TMiddleware]) -> Result<(), RouteError> {
+		self.try_push(HttpMethod::Patch, path, middlewares, None)
+	}
+
+	pub fn try_trace(&mut self, path: &str, middlewares: &[TMiddleware]) -> Result<(), RouteError> {
+		self.try_push(HttpMethod::Trace, path, middlewares, None)
+	}
+
+	pub fn try_get_named(
+		&mut self,
+		name: &str,
+		path: &str,
+		middlewares: &[TMiddleware],
+	) -> Result<(), RouteError> {
+		self.try_push(HttpMethod::Get, path, middlewares, Some(name))
+	}
+
+	pub fn try_post_named(
+		&mut self,
+		name: &str,
+		path: &str,
+		middlewares: &[TMiddleware],
+	) -> Result<(), RouteError> {
+		self.try_push(HttpMethod::Post, path, middlewares, Some(name))
+	}
+
+	pub fn try_put_named(
+		&mut self,
+		name: &str,
+		path: &str,
+		middlewares: &[TMiddleware],
+	) -> Result<(), RouteError> {
+		self.try_push(HttpMethod::Put, path, middlewares, Some(name))
+	}
+
+	pub fn try_delete_named(
+		&mut self,
+		name: &str,
+		path: &str,
+		middlewares: &[TMiddleware],
+	) -> Result<(), RouteError> {
+		self.try_push(HttpMethod::Delete, path, middlewares, Some(name))
+	}
+
+	pub fn try_patch_named(
+		&mut self,
+		name: &str,
+		path: &str,
+		middlewares: &[TMiddleware],
+	) -> Result<(), RouteError> {
+		self.try_push(HttpMethod::Patch, path, middlewares, Some(name))
+	}
+
+	fn get_path(&self, path: &str) -> String {
+		format!("{}{}", self.base_path, format_base_path(path))
+	}
+
+	fn try_push(
+		&mut self,
+		method: HttpMethod,
+		path: &str,
+		middlewares: &[TMiddleware],
+		name: Option<&str>,
+	) -> Result<(), RouteError> {
+		self.app.push_endpoint(
+			&method,
+			&self.get_path(path),
+			&self.middlewares,
+			middlewares,
+			name,
+		)
+	}
+}
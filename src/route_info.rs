@@ -0,0 +1,128 @@
+use crate::HttpMethod;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+// What's registered on a path, as listed by App::routes
+#[derive(Clone, Debug)]
+pub struct RouteInfo {
+	// None for middlewares, which run for every method. The middlewares of a route group have
+	// the method of the endpoint they're in front of
+	pub(crate) method: Option<HttpMethod>,
+	pub(crate) path: String,
+	pub(crate) is_endpoint: bool,
+	pub(crate) name: Option<String>,
+	// Where the sub app the route was registered on is mounted
+	pub(crate) sub_app: Option<String>,
+}
+
+impl RouteInfo {
+	pub fn get_method(&self) -> Option<&HttpMethod> {
+		self.method.as_ref()
+	}
+
+	pub fn get_path(&self) -> &str {
+		&self.path
+	}
+
+	pub fn is_endpoint(&self) -> bool {
+		self.is_endpoint
+	}
+
+	pub fn get_name(&self) -> Option<&str> {
+		self.name.as_deref()
+	}
+
+	pub fn get_sub_app(&self) -> Option<&str> {
+		self.sub_app.as_deref()
+	}
+}
+
+impl Display for RouteInfo {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		match &self.method {
+			Some(method) if self.is_endpoint => write!(f, "{} {}", method, self.path)?,
+			Some(method) => write!(f, "{} middleware {}", method, self.path)?,
+			None => write!(f, "middleware {}", self.path)?,
+		}
+		if let Some(name) = &self.name {
+			write!(f, " ({})", name)?;
+		}
+		Ok(())
+	}
+}
+
+// An endpoint that never runs, since one registered before it takes every request it would get
+#[derive(Clone, Debug)]
+pub struct ShadowedRoute {
+	pub(crate) route: RouteInfo,
+	pub(crate) shadowed_by: RouteInfo,
+}
+
+impl ShadowedRoute {
+	pub fn get_route(&self) -> &RouteInfo {
+		&self.route
+	}
+
+	pub fn get_shadowed_by(&self) -> &RouteInfo {
+		&self.shadowed_by
+	}
+}
+
+impl Display for ShadowedRoute {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		write!(
+			f,
+			"{} is unreachable, since {} was registered before it and matches everything it does",
+			self.route, self.shadowed_by
+		)
+	}
+}
+
+// Lines the routes up in columns, one per line
+pub(crate) fn format_route_table(routes: &[RouteInfo]) -> String {
+	let header = ["METHOD", "PATH", "KIND", "NAME", "SUB APP"];
+	let rows = routes
+		.iter()
+		.map(|route| {
+			[
+				route
+					.method
+					.as_ref()
+					.map(HttpMethod::to_string)
+					.unwrap_or_else(|| "ALL".to_string()),
+				route.path.clone(),
+				if route.is_endpoint {
+					"endpoint".to_string()
+				} else {
+					"middleware".to_string()
+				},
+				route.name.clone().unwrap_or_else(|| "-".to_string()),
+				route.sub_app.clone().unwrap_or_else(|| "-".to_string()),
+			]
+		})
+		.collect::<Vec<_>>();
+
+	let mut widths = header.map(str::len);
+	for row in &rows {
+		for (width, cell) in widths.iter_mut().zip(row) {
+			*width = (*width).max(cell.chars().count());
+		}
+	}
+
+	let format_row = |cells: &[&str]| {
+		cells
+			.iter()
+			.zip(&widths)
+			.map(|(cell, width)| format!("{:width$}", cell, width = width))
+			.collect::<Vec<_>>()
+			.join("  ")
+			.trim_end()
+			.to_string()
+	};
+
+	let mut table = vec![format_row(&header)];
+	table.extend(
+		rows.iter()
+			.map(|row| format_row(&row.iter().map(String::as_str).collect::<Vec<_>>())),
+	);
+	table.join("\n")
+}
@@ -0,0 +1,410 @@
+use crate::middleware_handler::{is_type_constraint, PathSegment};
+use percent_encoding::percent_decode_str;
+use regex::Regex;
+use std::{borrow::Cow, collections::HashMap};
+
+// Anything that can be mounted on a path and looked up by the router
+pub(crate) trait Route: Clone {
+	fn get_segments(&self) -> &[PathSegment];
+	fn is_endpoint(&self) -> bool;
+}
+
+pub(crate) struct RouteMatch<TRoute>
+where
+	TRoute: Route,
+{
+	pub(crate) handler: TRoute,
+	pub(crate) params: HashMap<String, String>,
+}
+
+#[derive(Clone, Default)]
+struct RouteNode {
+	statics: HashMap<String, RouteNode>,
+	// Params are grouped by their constraint, the unconstrained ones going under None
+	params: Vec<(Option<Regex>, RouteNode)>,
+	wildcard: Option<Box<RouteNode>>,
+	catch_all: Option<Box<RouteNode>>,
+	patterns: Vec<(Regex, RouteNode)>,
+
+	// The ones that can also match nothing at all
+	optional_params: Vec<(Option<Regex>, RouteNode)>,
+	optional_catch_all: Option<Box<RouteNode>>,
+
+	// Indices (in registration order) of the handlers that end at this node
+	endpoints: Vec<usize>,
+	middlewares: Vec<usize>,
+}
+
+impl RouteNode {
+	fn child_mut(&mut self, segment: &PathSegment) -> &mut RouteNode {
+		match segment {
+			PathSegment::Static(value) => self.statics.entry(value.clone()).or_default(),
+			PathSegment::Param {
+				constraint,
+				optional: false,
+				..
+			} => keyed_child(&mut self.params, constraint),
+			PathSegment::Param {
+				constraint,
+				optional: true,
+				..
+			} => keyed_child(&mut self.optional_params, constraint),
+			PathSegment::Wildcard => self.wildcard.get_or_insert_with(Default::default),
+			PathSegment::CatchAll {
+				optional: false, ..
+			} => self.catch_all.get_or_insert_with(Default::default),
+			PathSegment::CatchAll { optional: true, .. } => {
+				self.optional_catch_all.get_or_insert_with(Default::default)
+			}
+			PathSegment::Pattern(regex) => {
+				let position = self
+					.patterns
+					.iter()
+					.position(|(existing, _)| existing.as_str() == regex.as_str());
+				let position = position.unwrap_or_else(|| {
+					self.patterns.push((regex.clone(), Default::default()));
+					self.patterns.len() - 1
+				});
+				&mut self.patterns[position].1
+			}
+		}
+	}
+
+	// Optional segments that match nothing consume an empty span, and leave
+	// the current segment for whatever comes after them
+	fn skip_optionals(
+		&self,
+		segments: &[UrlSegment<'_>],
+		index: usize,
+		spans: &mut Vec<(usize, usize)>,
+		found: &mut Vec<(usize, Vec<(usize, usize)>)>,
+	) {
+		let optionals = self
+			.optional_params
+			.iter()
+			.map(|(_, node)| node)
+			.chain(self.optional_catch_all.as_deref());
+		for node in optionals {
+			spans.push((index, index));
+			node.collect(segments, index, spans, found);
+			spans.pop();
+		}
+	}
+
+	// Walks the tree, collecting every handler that matches the given segments,
+	// along with the span of segments consumed at each level of the tree
+	fn collect(
+		&self,
+		segments: &[UrlSegment<'_>],
+		index: usize,
+		spans: &mut Vec<(usize, usize)>,
+		found: &mut Vec<(usize, Vec<(usize, usize)>)>,
+	) {
+		// Middlewares match any url that begins with their mounted path
+		for handler in &self.middlewares {
+			found.push((*handler, spans.clone()));
+		}
+		// Endpoints only match if the entire url has been consumed
+		if index == segments.len() {
+			for handler in &self.endpoints {
+				found.push((*handler, spans.clone()));
+			}
+			self.skip_optionals(segments, index, spans, found);
+			return;
+		}
+
+		let segment = &segments[index];
+		let mut descend = |node: &RouteNode, end: usize, spans: &mut Vec<(usize, usize)>| {
+			spans.push((index, end));
+			node.collect(segments, end, spans, found);
+			spans.pop();
+		};
+
+		if let Some(node) = self.statics.get(segment.raw) {
+			descend(node, index + 1, spans);
+		}
+		for (constraint, node) in self.params.iter().chain(&self.optional_params) {
+			if is_param_value(segment, constraint.as_ref()) {
+				descend(node, index + 1, spans);
+			}
+		}
+		if let Some(node) = &self.wildcard {
+			if !segment.raw.is_empty() {
+				descend(node, index + 1, spans);
+			}
+		}
+		for (regex, node) in &self.patterns {
+			if regex.is_match(segment.raw) {
+				descend(node, index + 1, spans);
+			}
+		}
+		let catch_alls = self
+			.catch_all
+			.as_deref()
+			.into_iter()
+			.chain(self.optional_catch_all.as_deref());
+		for node in catch_alls {
+			// A catch all can swallow any number of segments, as long as it starts with something.
+			// Try the longest span first, so that ambiguous params are resolved greedily
+			if !segment.raw.is_empty() {
+				for end in ((index + 1)..=segments.len()).rev() {
+					descend(node, end, spans);
+				}
+			}
+		}
+
+		self.skip_optionals(segments, index, spans, found);
+	}
+}
+
+#[derive(Clone)]
+pub(crate) struct Router<TRoute>
+where
+	TRoute: Route,
+{
+	handlers: Vec<TRoute>,
+	root: RouteNode,
+}
+
+impl<TRoute> Router<TRoute>
+where
+	TRoute: Route,
+{
+	pub(crate) fn new() -> Self {
+		Router {
+			handlers: vec![],
+			root: RouteNode::default(),
+		}
+	}
+
+	pub(crate) fn push(&mut self, handler: TRoute) {
+		let index = self.handlers.len();
+		let node = handler
+			.get_segments()
+			.iter()
+			.fold(&mut self.root, |node, segment| node.child_mut(segment));
+		if handler.is_endpoint() {
+			node.endpoints.push(index);
+		} else {
+			node.middlewares.push(index);
+		}
+		self.handlers.push(handler);
+	}
+
+	pub(crate) fn extend<TIter>(&mut self, handlers: TIter)
+	where
+		TIter: IntoIterator<Item = TRoute>,
+	{
+		handlers.into_iter().for_each(|handler| self.push(handler));
+	}
+
+	pub(crate) fn into_handlers(self) -> Vec<TRoute> {
+		self.handlers
+	}
+
+	pub(crate) fn get_handlers(&self) -> &[TRoute] {
+		&self.handlers
+	}
+
+	/// Returns every handler that matches the given path, in the order they were registered
+	pub(crate) fn get_matches(&self, path: &str) -> Vec<RouteMatch<TRoute>> {
+		let segments = split_path(path);
+		self.find(&segments)
+			.into_iter()
+			.map(|(index, spans)| {
+				let handler = &self.handlers[index];
+				RouteMatch {
+					handler: handler.clone(),
+					params: get_params(handler.get_segments(), &segments, &spans),
+				}
+			})
+			.collect()
+	}
+
+	/// Checks if any endpoint (as opposed to a middleware) matches the given path
+	pub(crate) fn has_endpoint(&self, path: &str) -> bool {
+		self.find(&split_path(path))
+			.into_iter()
+			.any(|(index, _)| self.handlers[index].is_endpoint())
+	}
+
+	fn find(&self, segments: &[UrlSegment<'_>]) -> Vec<(usize, Vec<(usize, usize)>)> {
+		let mut found = vec![];
+		self.root.collect(segments, 0, &mut vec![], &mut found);
+
+		// A handler can match in more than one way (through a catch all),
+		// so keep only the first one for every handler
+		found.sort_by_key(|(index, _)| *index);
+		found.dedup_by_key(|(index, _)| *index);
+		found
+	}
+}
+
+// Urls are matched as they were sent, while the params taken out of them are decoded.
+// A segment of the requested url, as it was sent and percent-decoded
+struct UrlSegment<'a> {
+	raw: &'a str,
+	decoded: Cow<'a, str>,
+}
+
+fn split_path(path: &str) -> Vec<UrlSegment<'_>> {
+	let path = path.strip_prefix('/').unwrap_or(path);
+	// Both / and non / should match at the end of the url
+	let path = path.strip_suffix('/').unwrap_or(path);
+	if path.is_empty() {
+		vec![]
+	} else {
+		// Decoding comes after splitting, so that an encoded / stays within its segment
+		path.split('/')
+			.map(|raw| UrlSegment {
+				raw,
+				decoded: decode(raw),
+			})
+			.collect()
+	}
+}
+
+fn get_params(
+	pattern: &[PathSegment],
+	segments: &[UrlSegment<'_>],
+	spans: &[(usize, usize)],
+) -> HashMap<String, String> {
+	let mut params = HashMap::new();
+	for (segment, (start, end)) in pattern.iter().zip(spans) {
+		// Optional segments that didn't match anything aren't set at all
+		if start == end {
+			continue;
+		}
+		match segment {
+			PathSegment::Param { name, .. } => {
+				params.insert(name.clone(), segments[*start].decoded.to_string());
+			}
+			PathSegment::CatchAll {
+				name: Some(name), ..
+			} => {
+				let value = segments[*start..*end]
+					.iter()
+					.map(|segment| segment.decoded.as_ref())
+					.collect::<Vec<_>>()
+					.join("/");
+				params.insert(name.clone(), value);
+			}
+			PathSegment::Pattern(regex) => {
+				let captures = regex.captures(segments[*start].raw);
+				if let Some(captures) = captures {
+					for name in regex.capture_names().flatten() {
+						if let Some(value) = captures.name(name) {
+							params.insert(name.to_string(), decode(value.as_str()).to_string());
+						}
+					}
+				}
+			}
+			_ => (),
+		}
+	}
+	params
+}
+
+// Whether every url that the second path matches is also matched by the first one. This errs on the
+// side of saying no, when it can't easily tell
+pub(crate) fn covers(first: &[PathSegment], second: &[PathSegment]) -> bool {
+	match (first, second) {
+		([], []) => true,
+		// Optional segments left over in the second path can match more than the first one does
+		([], _) => false,
+		(_, []) => first.iter().all(is_optional),
+		// A catch all at the end takes anything, as long as there's something to take
+		([PathSegment::CatchAll { optional, .. }], _) => {
+			*optional || !second.iter().all(is_optional)
+		}
+		([PathSegment::CatchAll { .. }, ..], _) => false,
+		(_, [PathSegment::CatchAll { .. }, ..]) => false,
+		// Both with and without the optional segment have to be covered
+		(_, [segment @ PathSegment::Param { optional: true, .. }, rest @ ..]) => {
+			covers(first, rest) && covers_segment(&first[0], segment) && covers(&first[1..], rest)
+		}
+		([PathSegment::Param { optional: true, .. }, rest @ ..], _) => {
+			covers(rest, second) ||
+				(covers_segment(&first[0], &second[0]) && covers(rest, &second[1..]))
+		}
+		([segment, rest @ ..], [other, other_rest @ ..]) => {
+			covers_segment(segment, other) && covers(rest, other_rest)
+		}
+	}
+}
+
+fn covers_segment(first: &PathSegment, second: &PathSegment) -> bool {
+	match (first, second) {
+		(PathSegment::Static(value), PathSegment::Static(other)) => value == other,
+		(PathSegment::Wildcard, PathSegment::Static(other)) => !other.is_empty(),
+		(PathSegment::Wildcard, _) => true,
+		(PathSegment::Param { constraint, .. }, PathSegment::Static(other)) => is_param_value(
+			&UrlSegment {
+				raw: other,
+				decoded: decode(other),
+			},
+			constraint.as_ref(),
+		),
+		(
+			PathSegment::Param {
+				constraint: None, ..
+			},
+			PathSegment::Param { constraint, .. },
+		) => constraint.as_ref().is_none_or(is_type_constraint),
+		(
+			PathSegment::Param {
+				constraint: Some(constraint),
+				..
+			},
+			PathSegment::Param {
+				constraint: Some(other),
+				..
+			},
+		) |
+		(PathSegment::Pattern(constraint), PathSegment::Pattern(other)) => {
+			constraint.as_str() == other.as_str()
+		}
+		(PathSegment::Pattern(regex), PathSegment::Static(other)) => regex.is_match(other),
+		_ => false,
+	}
+}
+
+fn is_optional(segment: &PathSegment) -> bool {
+	matches!(
+		segment,
+		PathSegment::Param { optional: true, .. } | PathSegment::CatchAll { optional: true, .. }
+	)
+}
+
+fn keyed_child<'a>(
+	children: &'a mut Vec<(Option<Regex>, RouteNode)>,
+	constraint: &Option<Regex>,
+) -> &'a mut RouteNode {
+	let key = constraint.as_ref().map(Regex::as_str);
+	let position = children
+		.iter()
+		.position(|(existing, _)| existing.as_ref().map(Regex::as_str) == key);
+	let position = position.unwrap_or_else(|| {
+		children.push((constraint.clone(), Default::default()));
+		children.len() - 1
+	});
+	&mut children[position].1
+}
+
+fn decode(value: &str) -> Cow<'_, str> {
+	percent_decode_str(value).decode_utf8_lossy()
+}
+
+// Without a constraint, the value can also have anything else, as long as it's percent-encoded
+fn is_param_value(segment: &UrlSegment<'_>, constraint: Option<&Regex>) -> bool {
+	if segment.raw.is_empty() {
+		return false;
+	}
+	match constraint {
+		Some(constraint) => constraint.is_match(segment.raw),
+		None => segment
+			.raw
+			.chars()
+			.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-' || c == '%'),
+	}
+}
@@ -0,0 +1,497 @@
+use crate::{
+	connection::{Connection, ConnectionTracker, DrainGuard, RequestGuard},
+	listener::{BoundListener, ConnectionIo, Listener, RemoteAddr},
+	server_config::{HttpProtocols, ServerConfig},
+	server_stats::ServerStats,
+	App,
+	Context,
+	Error,
+	Middleware,
+	Request,
+	Response,
+	ResponseBody,
+};
+use futures::{
+	channel::{mpsc, oneshot},
+	future::{self, Either, FutureExt, Shared},
+	Future,
+	StreamExt,
+};
+use hyper::{
+	server::conn::Http,
+	service::service_fn,
+	Body,
+	Request as HyperRequest,
+	Response as HyperResponse,
+	StatusCode,
+	Version,
+};
+use std::{
+	any::Any,
+	convert::Infallible,
+	fmt::Debug,
+	io::Error as IoError,
+	panic::AssertUnwindSafe,
+	sync::Arc,
+	time::Duration,
+};
+use tokio::{
+	sync::{OwnedSemaphorePermit, Semaphore},
+	time::Instant,
+};
+
+// hyper won't buffer less than this
+const MIN_BUFFER_SIZE: usize = 8192;
+
+struct Server<TContext, TMiddleware, TState>
+where
+	TContext: 'static + Context + Debug + Send + Sync,
+	TMiddleware: 'static + Middleware<TContext> + Clone + Send + Sync,
+	TState: 'static + Send + Sync,
+{
+	app: App<TContext, TMiddleware, TState>,
+	config: ServerConfig,
+	http: Http,
+	#[cfg(feature = "tls")]
+	tls: Option<tokio_rustls::TlsAcceptor>,
+	shutdown: Shared<oneshot::Receiver<()>>,
+	// Resolves once the drain timeout is up, cutting short whatever is still going on
+	abort: Shared<oneshot::Receiver<()>>,
+}
+
+pub(crate) async fn serve<TContext, TMiddleware, TState, TShutdownSignal>(
+	app: App<TContext, TMiddleware, TState>,
+	listener: Listener,
+	config: ServerConfig,
+	shutdown_signal: Option<TShutdownSignal>,
+) -> Result<(), IoError>
+where
+	TContext: 'static + Context + Debug + Send + Sync,
+	TMiddleware: 'static + Middleware<TContext> + Clone + Send + Sync,
+	TState: 'static + Send + Sync,
+	TShutdownSignal: Future<Output = ()>,
+{
+	#[cfg(feature = "tls")]
+	let tls_server = match &config.tls {
+		Some(tls_config) => Some(crate::tls::TlsServer::new(
+			tls_config.clone(),
+			config.get_alpn_protocols(),
+		)?),
+		None => None,
+	};
+
+	for shadowed_route in app.get_shadowed_routes() {
+		log::warn!("{}", shadowed_route);
+	}
+
+	let listeners = listener.bind().await?;
+	if listeners.is_empty() {
+		return Err(IoError::other("there's nothing to listen on"));
+	}
+
+	let addrs = listeners
+		.iter()
+		.map(BoundListener::get_addr)
+		.collect::<Result<Vec<_>, _>>()?;
+	if let Some(start_hook) = app.start_hook {
+		(start_hook)(&addrs, app.get_state()).await;
+	}
+
+	let (shutdown_sender, shutdown) = oneshot::channel();
+	let (abort_sender, abort) = oneshot::channel();
+	// Every connection holds on to a sender, so the receiver ends once they're all closed
+	let (drain, mut drained) = mpsc::channel(1);
+	let connection_limit = config
+		.max_connections
+		.map(|max_connections| Arc::new(Semaphore::new(max_connections)));
+	let server = Arc::new(Server {
+		app,
+		http: get_http(&config),
+		config,
+		#[cfg(feature = "tls")]
+		tls: tls_server
+			.as_ref()
+			.map(|tls_server| tls_server.get_acceptor()),
+		shutdown: shutdown.shared(),
+		abort: abort.shared(),
+	});
+
+	let accept_connections = future::join_all(listeners.into_iter().map(|listener| {
+		accept_connections(
+			listener,
+			server.clone(),
+			connection_limit.clone(),
+			drain.clone(),
+		)
+	}));
+	#[cfg(feature = "tls")]
+	let watch_certs = match tls_server {
+		Some(tls_server) => Either::Left(tls_server.watch()),
+		None => Either::Right(future::pending()),
+	};
+	#[cfg(not(feature = "tls"))]
+	let watch_certs = future::pending::<()>();
+
+	let running = future::select(Box::pin(accept_connections), Box::pin(watch_certs));
+	if let Some(shutdown_signal) = shutdown_signal {
+		future::select(running, Box::pin(shutdown_signal)).await;
+	} else {
+		running.await;
+	}
+
+	// The listeners have been dropped by now. Let the open connections finish what they're doing
+	let drain_deadline = server
+		.config
+		.drain_timeout
+		.map(|drain_timeout| Instant::now() + drain_timeout);
+	let _ = shutdown_sender.send(());
+	drop(drain);
+	if let Some(shutdown_hook) = server.app.shutdown_hook {
+		(shutdown_hook)(server.app.get_state()).await;
+	}
+
+	if let Some(drain_deadline) = drain_deadline {
+		if tokio::time::timeout_at(drain_deadline, drained.next())
+			.await
+			.is_err()
+		{
+			log::warn!("Drain timeout reached, aborting the remaining connections");
+			let _ = abort_sender.send(());
+			drained.next().await;
+		}
+	} else {
+		drained.next().await;
+	}
+
+	if let Some(drain_complete_hook) = server.app.drain_complete_hook {
+		(drain_complete_hook)(server.app.get_state()).await;
+	}
+
+	Ok(())
+}
+
+async fn accept_connections<TContext, TMiddleware, TState>(
+	mut listener: BoundListener,
+	server: Arc<Server<TContext, TMiddleware, TState>>,
+	connection_limit: Option<Arc<Semaphore>>,
+	drain: mpsc::Sender<()>,
+) where
+	TContext: 'static + Context + Debug + Send + Sync,
+	TMiddleware: 'static + Middleware<TContext> + Clone + Send + Sync,
+	TState: 'static + Send + Sync,
+{
+	loop {
+		// Nothing is accepted while there are too many connections open,
+		// so that new clients wait in the backlog instead
+		let permit = match &connection_limit {
+			Some(connection_limit) => Some(connection_limit.clone().acquire_owned().await),
+			None => None,
+		};
+
+		match listener.accept().await {
+			Ok((io, remote_addr)) => {
+				if let ConnectionIo::Tcp(stream) = &io {
+					if let Err(err) = stream.set_nodelay(server.config.tcp_nodelay) {
+						log::debug!("Unable to set TCP_NODELAY for {}: {}", remote_addr, err);
+					}
+				}
+				tokio::spawn(server.clone().serve_connection(
+					io,
+					remote_addr,
+					permit,
+					drain.clone(),
+				));
+			}
+			Err(err) => {
+				// Most likely out of file descriptors, so give it some time
+				log::error!("Unable to accept connection: {}", err);
+				tokio::time::delay_for(Duration::from_secs(1)).await;
+			}
+		}
+	}
+}
+
+impl<TContext, TMiddleware, TState> Server<TContext, TMiddleware, TState>
+where
+	TContext: 'static + Context + Debug + Send + Sync,
+	TMiddleware: 'static + Middleware<TContext> + Clone + Send + Sync,
+	TState: 'static + Send + Sync,
+{
+	async fn serve_connection(
+		self: Arc<Self>,
+		io: ConnectionIo,
+		remote_addr: RemoteAddr,
+		permit: Option<OwnedSemaphorePermit>,
+		drain: mpsc::Sender<()>,
+	) {
+		let mut http = self.http.clone();
+
+		#[cfg(feature = "tls")]
+		let io = match &self.tls {
+			Some(tls) => {
+				match tokio::time::timeout(crate::tls::HANDSHAKE_TIMEOUT, tls.accept(io)).await {
+					Ok(Ok(stream)) => {
+						use tokio_rustls::rustls::Session;
+
+						match stream.get_ref().1.get_alpn_protocol() {
+							Some(b"h2") => {
+								http.http2_only(true);
+							}
+							Some(_) => {
+								http.http1_only(true);
+							}
+							None => (),
+						}
+						ConnectionIo::Tls(Box::new(stream))
+					}
+					Ok(Err(err)) => {
+						log::debug!("TLS handshake with {} failed: {}", remote_addr, err);
+						return;
+					}
+					Err(_) => {
+						log::debug!("TLS handshake with {} timed out", remote_addr);
+						return;
+					}
+				}
+			}
+			None => io,
+		};
+
+		let secure = io.is_secure();
+		if !secure && !self.config.h2c && self.config.protocols == HttpProtocols::Http1AndHttp2 {
+			http.http1_only(true);
+		}
+
+		let tracker = ConnectionTracker::new();
+		let connection = Connection::new(
+			io,
+			tracker.clone(),
+			self.config.header_read_timeout,
+			self.config.keep_alive_timeout,
+			permit,
+		);
+
+		let server = self.clone();
+		let client_addr = remote_addr.clone();
+		let drain_guard = DrainGuard::new(drain, self.abort.clone());
+		let service = service_fn(move |req: HyperRequest<Body>| {
+			let guard = tracker.start_request(req.version() == Version::HTTP_2);
+			let server = server.clone();
+			let remote_addr = client_addr.clone();
+			let tracker = tracker.clone();
+			let drain_guard = drain_guard.clone();
+			async move {
+				Ok::<_, Infallible>(
+					server
+						.serve_request(req, remote_addr, secure, tracker, guard, drain_guard)
+						.await,
+				)
+			}
+		});
+
+		// Once the server is shutting down, the connection is closed as soon as it's done with the
+		// requests it's already serving
+		let mut connection = Box::pin(http.serve_connection(connection, service).with_upgrades());
+		let result = match future::select(connection.as_mut(), self.shutdown.clone()).await {
+			Either::Left((result, _)) => result,
+			Either::Right(_) => {
+				connection.as_mut().graceful_shutdown();
+				match future::select(connection, self.abort.clone()).await {
+					Either::Left((result, _)) => result,
+					Either::Right(_) => {
+						log::debug!("Aborted connection from {}", remote_addr);
+						return;
+					}
+				}
+			}
+		};
+		if let Err(err) = result {
+			log::debug!("Error serving connection from {}: {}", remote_addr, err);
+		}
+	}
+
+	async fn serve_request(
+		&self,
+		req: HyperRequest<Body>,
+		remote_addr: RemoteAddr,
+		secure: bool,
+		tracker: ConnectionTracker,
+		guard: RequestGuard,
+		drain_guard: DrainGuard,
+	) -> HyperResponse<Body> {
+		if self.has_too_many_headers(&req) {
+			let mut response = Response::new();
+			response.set_status(431);
+			response.set_body("Request header fields too large");
+			return into_hyper_response(response, guard, &self.config.stats);
+		}
+
+		let method = req.method().clone();
+		let path = req.uri().path().to_string();
+		let mut request = match AssertUnwindSafe(Request::from_hyper(remote_addr, req))
+			.catch_unwind()
+			.await
+		{
+			Ok(request) => request,
+			Err(panic) => {
+				// There's no request to give to the error handler
+				on_panic(&self.config.stats, method.as_str(), &path, panic);
+				let mut response = Response::new();
+				response.set_error(500, "Internal Server Error", None);
+				return into_hyper_response(response, guard, &self.config.stats);
+			}
+		};
+		request.secure = secure;
+		request.drain_guard = Some(drain_guard);
+
+		let response = if let Some(request_timeout) = self.config.request_timeout {
+			match tokio::time::timeout(request_timeout, self.resolve(request)).await {
+				Ok(response) => response,
+				Err(_) => {
+					let mut response = Response::new();
+					response.set_status(408);
+					response.set_body("Request timed out");
+					response
+				}
+			}
+		} else {
+			self.resolve(request).await
+		};
+
+		if response.status == 101 {
+			tracker.set_upgraded();
+		}
+		into_hyper_response(response, guard, &self.config.stats)
+	}
+
+	fn has_too_many_headers(&self, req: &HyperRequest<Body>) -> bool {
+		let headers = req.headers();
+		if let Some(max_headers) = self.config.max_headers {
+			if headers.len() > max_headers {
+				return true;
+			}
+		}
+		if let Some(max_header_size) = self.config.max_header_size {
+			// Each header line is also terminated by a ": " and a CRLF
+			let header_size = headers
+				.iter()
+				.map(|(key, value)| key.as_str().len() + value.len() + 4)
+				.sum::<usize>();
+			if header_size > max_header_size {
+				return true;
+			}
+		}
+		false
+	}
+
+	async fn resolve(&self, request: Request) -> Response {
+		let method = request.get_method().to_string();
+		let path = request.get_path();
+
+		// execute app's middlewares. A panic in any of them only takes down this request
+		let result = AssertUnwindSafe(async {
+			// The context might not make it back with the error, so hold on to the request
+			let mut context = self.app.generate_context(request.clone());
+			context.header("Server", "Eve");
+			self.app.resolve(context).await
+		})
+		.catch_unwind()
+		.await;
+		let err = match result {
+			Ok(Ok(context)) => return context.take_response(),
+			Ok(Err(err)) => err,
+			Err(panic) => {
+				on_panic(&self.config.stats, &method, &path, panic);
+				Error::internal_server_error()
+			}
+		};
+
+		let mut response = match AssertUnwindSafe(self.app.handle_error(request, err))
+			.catch_unwind()
+			.await
+		{
+			Ok(response) => response,
+			Err(panic) => {
+				on_panic(&self.config.stats, &method, &path, panic);
+				let mut response = Response::new();
+				response.set_error(500, "Internal Server Error", None);
+				response
+			}
+		};
+		if response.get_header("Server").is_none() {
+			response.set_header("Server", "Eve");
+		}
+		response
+	}
+}
+
+fn on_panic(stats: &ServerStats, method: &str, path: &str, panic: Box<dyn Any + Send>) {
+	stats.add_panic();
+	let message = if let Some(message) = panic.downcast_ref::<&str>() {
+		message
+	} else if let Some(message) = panic.downcast_ref::<String>() {
+		message.as_str()
+	} else {
+		"unknown panic"
+	};
+	log::error!("Panic while handling {} {}: {}", method, path, message);
+}
+
+fn get_http(config: &ServerConfig) -> Http {
+	let mut http = Http::new();
+	match config.protocols {
+		HttpProtocols::Http1Only => {
+			http.http1_only(true);
+		}
+		HttpProtocols::Http2Only => {
+			http.http2_only(true);
+		}
+		HttpProtocols::Http1AndHttp2 => (),
+	}
+	http.http1_keep_alive(config.keep_alive)
+		.http2_initial_stream_window_size(config.http2_initial_stream_window_size)
+		.http2_initial_connection_window_size(config.http2_initial_connection_window_size)
+		.http2_max_frame_size(config.http2_max_frame_size)
+		.http2_max_concurrent_streams(config.http2_max_concurrent_streams);
+	if let Some(max_header_size) = config.max_header_size {
+		// Leave some room for the request line
+		http.max_buf_size(max_header_size.saturating_add(MIN_BUFFER_SIZE));
+	}
+	http
+}
+
+// The connection is busy until the response body has been sent, which for streams can take a while.
+// A response hyper won't take, like one with a newline in a header, is counted and replaced with
+// a plain 500, instead of taking the connection down
+fn into_hyper_response(
+	response: Response,
+	guard: RequestGuard,
+	stats: &ServerStats,
+) -> HyperResponse<Body> {
+	let mut hyper_response = HyperResponse::builder().status(response.status);
+
+	// Set the appropriate headers
+	for (key, values) in &response.headers {
+		for value in values {
+			hyper_response = hyper_response.header(key, value);
+		}
+	}
+
+	let body = match response.body {
+		ResponseBody::Bytes(bytes) => Body::from(bytes),
+		body => Body::wrap_stream(body.into_stream().map(move |chunk| {
+			let _ = &guard;
+			chunk
+		})),
+	};
+	match hyper_response.body(body) {
+		Ok(hyper_response) => hyper_response,
+		Err(err) => {
+			stats.add_rejected_response();
+			log::error!("Unable to send response: {}", err);
+			let mut hyper_response = HyperResponse::new(Body::from("Internal Server Error"));
+			*hyper_response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+			hyper_response
+		}
+	}
+}
@@ -0,0 +1,180 @@
+use crate::server_stats::ServerStats;
+#[cfg(feature = "tls")]
+use crate::TlsConfig;
+use std::time::Duration;
+
+// Which versions of HTTP the server talks
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum HttpProtocols {
+	Http1Only,
+	Http2Only,
+	Http1AndHttp2,
+}
+
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+	pub(crate) header_read_timeout: Option<Duration>,
+	pub(crate) request_timeout: Option<Duration>,
+	pub(crate) keep_alive: bool,
+	pub(crate) keep_alive_timeout: Option<Duration>,
+	pub(crate) max_headers: Option<usize>,
+	pub(crate) max_header_size: Option<usize>,
+	pub(crate) protocols: HttpProtocols,
+	pub(crate) h2c: bool,
+	pub(crate) http2_initial_stream_window_size: Option<u32>,
+	pub(crate) http2_initial_connection_window_size: Option<u32>,
+	pub(crate) http2_max_frame_size: Option<u32>,
+	pub(crate) http2_max_concurrent_streams: Option<u32>,
+	pub(crate) tcp_nodelay: bool,
+	pub(crate) max_connections: Option<usize>,
+	pub(crate) drain_timeout: Option<Duration>,
+	#[cfg(feature = "tls")]
+	pub(crate) tls: Option<TlsConfig>,
+	pub(crate) stats: ServerStats,
+}
+
+impl ServerConfig {
+	pub fn new() -> Self {
+		ServerConfig::default()
+	}
+
+	// How long a client gets to send the headers of a request, once it has started sending it.
+	// Connections that take any longer are closed
+	pub fn header_read_timeout(mut self, timeout: Duration) -> Self {
+		self.header_read_timeout = Some(timeout);
+		self
+	}
+
+	// How long the app gets to respond to a request, after which the client gets a 408
+	pub fn request_timeout(mut self, timeout: Duration) -> Self {
+		self.request_timeout = Some(timeout);
+		self
+	}
+
+	// Whether HTTP/1 connections are kept open for more requests after a response
+	pub fn keep_alive(mut self, keep_alive: bool) -> Self {
+		self.keep_alive = keep_alive;
+		self
+	}
+
+	// How long a connection can sit idle between requests before it's closed
+	pub fn keep_alive_timeout(mut self, timeout: Duration) -> Self {
+		self.keep_alive_timeout = Some(timeout);
+		self
+	}
+
+	// Requests with more headers than this get a 431
+	pub fn max_headers(mut self, max_headers: usize) -> Self {
+		self.max_headers = Some(max_headers);
+		self
+	}
+
+	// Requests whose headers add up to more bytes than this get a 431
+	pub fn max_header_size(mut self, max_header_size: usize) -> Self {
+		self.max_header_size = Some(max_header_size);
+		self
+	}
+
+	pub fn http1_only(mut self) -> Self {
+		self.protocols = HttpProtocols::Http1Only;
+		self
+	}
+
+	pub fn http2_only(mut self) -> Self {
+		self.protocols = HttpProtocols::Http2Only;
+		self
+	}
+
+	// Whether HTTP/2 with prior knowledge is accepted on connections without TLS, when both
+	// versions are enabled. HTTP/2 over TLS is always negotiated through ALPN instead
+	pub fn h2c(mut self, h2c: bool) -> Self {
+		self.h2c = h2c;
+		self
+	}
+
+	pub fn http2_initial_stream_window_size(mut self, size: u32) -> Self {
+		self.http2_initial_stream_window_size = Some(size);
+		self
+	}
+
+	pub fn http2_initial_connection_window_size(mut self, size: u32) -> Self {
+		self.http2_initial_connection_window_size = Some(size);
+		self
+	}
+
+	pub fn http2_max_frame_size(mut self, size: u32) -> Self {
+		self.http2_max_frame_size = Some(size);
+		self
+	}
+
+	pub fn http2_max_concurrent_streams(mut self, max_streams: u32) -> Self {
+		self.http2_max_concurrent_streams = Some(max_streams);
+		self
+	}
+
+	pub fn tcp_nodelay(mut self, nodelay: bool) -> Self {
+		self.tcp_nodelay = nodelay;
+		self
+	}
+
+	// Once this many connections are open, no more are accepted until one of them closes.
+	// Clients wait in the listen backlog of the OS in the meantime
+	pub fn max_connections(mut self, max_connections: usize) -> Self {
+		self.max_connections = Some(max_connections);
+		self
+	}
+
+	// How long open connections get to finish their requests once the server is shutting down.
+	// Whatever is still in flight after that is aborted. Without it, the server waits indefinitely
+	pub fn drain_timeout(mut self, timeout: Duration) -> Self {
+		self.drain_timeout = Some(timeout);
+		self
+	}
+
+	#[cfg(feature = "tls")]
+	pub fn tls(mut self, tls_config: TlsConfig) -> Self {
+		self.tls = Some(tls_config);
+		self
+	}
+
+	// Counts what goes wrong on the server the config is used for. Clones of the config share the
+	// stats, so servers that should be counted separately each need a config of their own
+	pub fn get_stats(&self) -> &ServerStats {
+		&self.stats
+	}
+
+	// The protocols offered to clients through ALPN, most preferred first
+	#[cfg(feature = "tls")]
+	pub(crate) fn get_alpn_protocols(&self) -> Vec<Vec<u8>> {
+		match self.protocols {
+			HttpProtocols::Http1Only => vec![b"http/1.1".to_vec()],
+			HttpProtocols::Http2Only => vec![b"h2".to_vec()],
+			HttpProtocols::Http1AndHttp2 => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+		}
+	}
+}
+
+impl Default for ServerConfig {
+	fn default() -> Self {
+		ServerConfig {
+			header_read_timeout: None,
+			request_timeout: None,
+			keep_alive: true,
+			keep_alive_timeout: None,
+			max_headers: None,
+			max_header_size: None,
+			protocols: HttpProtocols::Http1AndHttp2,
+			h2c: true,
+			http2_initial_stream_window_size: None,
+			http2_initial_connection_window_size: None,
+			http2_max_frame_size: None,
+			http2_max_concurrent_streams: None,
+			tcp_nodelay: false,
+			max_connections: None,
+			drain_timeout: None,
+			#[cfg(feature = "tls")]
+			tls: None,
+			stats: ServerStats::new(),
+		}
+	}
+}
@@ -0,0 +1,37 @@
+use std::sync::{
+	atomic::{AtomicU64, Ordering},
+	Arc,
+};
+
+// What went wrong while a server was running, see ServerConfig::get_stats. Clones share the
+// same counts
+#[derive(Clone, Debug, Default)]
+pub struct ServerStats {
+	panics: Arc<AtomicU64>,
+	rejected_responses: Arc<AtomicU64>,
+}
+
+impl ServerStats {
+	pub fn new() -> Self {
+		ServerStats::default()
+	}
+
+	// How many requests panicked, either in a middleware or in the error handler
+	pub fn get_panic_count(&self) -> u64 {
+		self.panics.load(Ordering::Relaxed)
+	}
+
+	// How many responses hyper wouldn't take, like ones with a newline in a header,
+	// and that were replaced with a plain 500
+	pub fn get_rejected_response_count(&self) -> u64 {
+		self.rejected_responses.load(Ordering::Relaxed)
+	}
+
+	pub(crate) fn add_panic(&self) {
+		self.panics.fetch_add(1, Ordering::Relaxed);
+	}
+
+	pub(crate) fn add_rejected_response(&self) {
+		self.rejected_responses.fetch_add(1, Ordering::Relaxed);
+	}
+}
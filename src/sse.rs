@@ -0,0 +1,165 @@
+use futures::{
+	channel::mpsc::{self, Receiver, Sender},
+	future::{select, Either},
+	stream::{self, Stream, StreamExt},
+	SinkExt,
+};
+use hyper::body::Bytes;
+use serde::Serialize;
+use std::{
+	error::Error as StdError,
+	fmt::{Display, Formatter, Result as FmtResult},
+	io::Error as IoError,
+	time::Duration,
+};
+use tokio::time::{interval, Interval};
+
+pub const DEFAULT_KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+const EVENT_BUFFER_SIZE: usize = 16;
+
+#[derive(Clone, Debug, Default)]
+pub struct SseEvent {
+	id: Option<String>,
+	event: Option<String>,
+	data: String,
+	retry: Option<Duration>,
+}
+
+impl SseEvent {
+	pub fn new(data: &str) -> Self {
+		SseEvent {
+			data: data.to_string(),
+			..Default::default()
+		}
+	}
+
+	pub fn json<TData>(data: &TData) -> Result<Self, serde_json::Error>
+	where
+		TData: Serialize,
+	{
+		Ok(SseEvent::new(&serde_json::to_string(data)?))
+	}
+
+	pub fn id(mut self, id: &str) -> Self {
+		self.id = Some(id.to_string());
+		self
+	}
+
+	pub fn event(mut self, event: &str) -> Self {
+		self.event = Some(event.to_string());
+		self
+	}
+
+	pub fn retry(mut self, retry: Duration) -> Self {
+		self.retry = Some(retry);
+		self
+	}
+}
+
+impl Display for SseEvent {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		// Fields can't span multiple lines, except for data, which is split into multiple fields
+		if let Some(id) = &self.id {
+			writeln!(f, "id: {}", id.replace(['\r', '\n'], ""))?;
+		}
+		if let Some(event) = &self.event {
+			writeln!(f, "event: {}", event.replace(['\r', '\n'], ""))?;
+		}
+		if let Some(retry) = &self.retry {
+			writeln!(f, "retry: {}", retry.as_millis())?;
+		}
+		for line in split_lines(&self.data) {
+			writeln!(f, "data: {}", line)?;
+		}
+		if self.data.is_empty() {
+			writeln!(f, "data:")?;
+		}
+		writeln!(f)
+	}
+}
+
+// Clients end a line at a \r\n, a \r or a \n, while str::lines doesn't split on a lone \r
+fn split_lines(text: &str) -> Vec<&str> {
+	if text.is_empty() {
+		return vec![];
+	}
+	let mut lines = text
+		.split("\r\n")
+		.flat_map(|line| line.split(['\r', '\n']))
+		.collect::<Vec<_>>();
+	if text.ends_with(['\r', '\n']) {
+		lines.pop();
+	}
+	lines
+}
+
+#[derive(Debug)]
+pub struct ClientDisconnected;
+
+impl Display for ClientDisconnected {
+	fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+		write!(f, "the client has disconnected from the event stream")
+	}
+}
+
+impl StdError for ClientDisconnected {}
+
+// Sends events to a client. The response stream is dropped once the client disconnects,
+// after which every send fails
+#[derive(Clone)]
+pub struct SseSender {
+	sender: Sender<Bytes>,
+}
+
+impl SseSender {
+	pub async fn send(&mut self, event: SseEvent) -> Result<(), ClientDisconnected> {
+		self.sender
+			.send(Bytes::from(event.to_string()))
+			.await
+			.map_err(|_| ClientDisconnected)
+	}
+
+	pub async fn comment(&mut self, comment: &str) -> Result<(), ClientDisconnected> {
+		let comment = split_lines(comment)
+			.into_iter()
+			.map(|line| format!(": {}\n", line))
+			.collect::<String>();
+		self.sender
+			.send(Bytes::from(format!("{}\n", comment)))
+			.await
+			.map_err(|_| ClientDisconnected)
+	}
+
+	pub fn is_closed(&self) -> bool {
+		self.sender.is_closed()
+	}
+}
+
+pub(crate) fn event_stream(
+	keep_alive: Option<Duration>,
+) -> (
+	SseSender,
+	impl Stream<Item = Result<Bytes, IoError>> + Send + 'static,
+) {
+	let (sender, receiver) = mpsc::channel(EVENT_BUFFER_SIZE);
+	let keep_alive = keep_alive.map(interval);
+
+	let stream = stream::unfold(
+		(receiver, keep_alive),
+		|(mut receiver, mut keep_alive): (Receiver<Bytes>, Option<Interval>)| async move {
+			let chunk = if let Some(keep_alive) = keep_alive.as_mut() {
+				match select(receiver.next(), Box::pin(keep_alive.tick())).await {
+					Either::Left((chunk, _)) => chunk?,
+					// Comments are ignored by clients, but keep proxies from closing the connection
+					Either::Right(_) => Bytes::from_static(b":\n\n"),
+				}
+			} else {
+				receiver.next().await?
+			};
+			Some((Ok(chunk), (receiver, keep_alive)))
+		},
+	);
+
+	(SseSender { sender }, stream)
+}
@@ -0,0 +1,69 @@
+use crate::{
+	context::Context,
+	error_handler::BoundErrorHandler,
+	middleware_handler::{parse_path, PathSegment},
+	router::Route,
+	Request,
+};
+use std::{fmt::Debug, sync::Arc};
+
+// A context generator along with the state of the app it came from
+pub(crate) type BoundContextGenerator<TContext> = Arc<dyn Fn(Request) -> TContext + Send + Sync>;
+
+// What a mounted sub app brings along besides its routes. Requests under the sub app's
+// base path get their context, and have their errors handled, by the sub app
+pub(crate) struct SubAppRoute<TContext>
+where
+	TContext: Context + Debug + Send + Sync,
+{
+	pub(crate) mounted_url: String,
+	pub(crate) segments: Vec<PathSegment>,
+	pub(crate) context_generator: BoundContextGenerator<TContext>,
+	pub(crate) error_handler: Option<Arc<dyn BoundErrorHandler<TContext> + Send + Sync>>,
+}
+
+impl<TContext> Clone for SubAppRoute<TContext>
+where
+	TContext: Context + Debug + Send + Sync,
+{
+	fn clone(&self) -> Self {
+		SubAppRoute {
+			mounted_url: self.mounted_url.clone(),
+			segments: self.segments.clone(),
+			context_generator: self.context_generator.clone(),
+			error_handler: self.error_handler.clone(),
+		}
+	}
+}
+
+impl<TContext> SubAppRoute<TContext>
+where
+	TContext: Context + Debug + Send + Sync,
+{
+	pub(crate) fn new(
+		path: &str,
+		context_generator: BoundContextGenerator<TContext>,
+		error_handler: Option<Arc<dyn BoundErrorHandler<TContext> + Send + Sync>>,
+	) -> Self {
+		let (mounted_url, segments) = parse_path(path);
+		SubAppRoute {
+			mounted_url,
+			segments,
+			context_generator,
+			error_handler,
+		}
+	}
+}
+
+impl<TContext> Route for SubAppRoute<TContext>
+where
+	TContext: Context + Debug + Send + Sync,
+{
+	fn get_segments(&self) -> &[PathSegment] {
+		&self.segments
+	}
+
+	fn is_endpoint(&self) -> bool {
+		false
+	}
+}
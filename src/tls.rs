@@ -0,0 +1,198 @@
+use futures::{
+	future::{select, Either},
+	stream::{self, StreamExt},
+};
+use std::{
+	fs::{self, File},
+	io::{BufReader, Error as IoError, ErrorKind},
+	path::{Path, PathBuf},
+	sync::{Arc, RwLock},
+	time::{Duration, SystemTime},
+};
+use tokio_rustls::{
+	rustls::{
+		internal::pemfile,
+		sign::{self, CertifiedKey},
+		ClientHello,
+		NoClientAuth,
+		ResolvesServerCert,
+		ServerConfig,
+	},
+	TlsAcceptor,
+};
+
+pub const DEFAULT_CERT_WATCH_INTERVAL: Duration = Duration::from_secs(10);
+
+pub(crate) const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+	cert_path: PathBuf,
+	key_path: PathBuf,
+	watch_interval: Option<Duration>,
+}
+
+impl TlsConfig {
+	// Takes the paths to a PEM encoded certificate chain and its private key (PKCS#8 or RSA)
+	pub fn new<TCertPath, TKeyPath>(cert_path: TCertPath, key_path: TKeyPath) -> Self
+	where
+		TCertPath: AsRef<Path>,
+		TKeyPath: AsRef<Path>,
+	{
+		TlsConfig {
+			cert_path: cert_path.as_ref().to_path_buf(),
+			key_path: key_path.as_ref().to_path_buf(),
+			watch_interval: Some(DEFAULT_CERT_WATCH_INTERVAL),
+		}
+	}
+
+	// How often the certificate files are checked for changes. None only reloads them on SIGHUP
+	pub fn watch_interval(mut self, watch_interval: Option<Duration>) -> Self {
+		self.watch_interval = watch_interval;
+		self
+	}
+
+	pub fn get_cert_path(&self) -> &Path {
+		&self.cert_path
+	}
+
+	pub fn get_key_path(&self) -> &Path {
+		&self.key_path
+	}
+
+	fn load_certified_key(&self) -> Result<CertifiedKey, IoError> {
+		let certs = pemfile::certs(&mut BufReader::new(File::open(&self.cert_path)?))
+			.map_err(|_| invalid_data("unable to parse the certificate file"))?;
+		if certs.is_empty() {
+			return Err(invalid_data(
+				"no certificates found in the certificate file",
+			));
+		}
+
+		let mut keys =
+			pemfile::pkcs8_private_keys(&mut BufReader::new(File::open(&self.key_path)?))
+				.map_err(|_| invalid_data("unable to parse the key file"))?;
+		if keys.is_empty() {
+			keys = pemfile::rsa_private_keys(&mut BufReader::new(File::open(&self.key_path)?))
+				.map_err(|_| invalid_data("unable to parse the key file"))?;
+		}
+		let key = keys
+			.first()
+			.ok_or_else(|| invalid_data("no private key found in the key file"))?;
+		let key = sign::any_supported_type(key)
+			.map_err(|_| invalid_data("unsupported private key type"))?;
+
+		Ok(CertifiedKey::new(certs, Arc::new(key)))
+	}
+
+	fn get_modified_times(&self) -> Option<(SystemTime, SystemTime)> {
+		Some((
+			fs::metadata(&self.cert_path).ok()?.modified().ok()?,
+			fs::metadata(&self.key_path).ok()?.modified().ok()?,
+		))
+	}
+}
+
+// Hands out whichever certificate was loaded last, so that a reload only
+// affects handshakes from then on, and established connections are left alone
+struct CertResolver {
+	certified_key: RwLock<CertifiedKey>,
+}
+
+impl ResolvesServerCert for CertResolver {
+	fn resolve(&self, _: ClientHello) -> Option<CertifiedKey> {
+		self.certified_key.read().ok().map(|key| key.clone())
+	}
+}
+
+pub(crate) struct TlsServer {
+	config: TlsConfig,
+	resolver: Arc<CertResolver>,
+	acceptor: TlsAcceptor,
+}
+
+impl TlsServer {
+	pub(crate) fn new(config: TlsConfig, alpn_protocols: Vec<Vec<u8>>) -> Result<Self, IoError> {
+		let resolver = Arc::new(CertResolver {
+			certified_key: RwLock::new(config.load_certified_key()?),
+		});
+
+		let mut server_config = ServerConfig::new(NoClientAuth::new());
+		server_config.cert_resolver = resolver.clone();
+		server_config.set_protocols(&alpn_protocols);
+
+		Ok(TlsServer {
+			config,
+			resolver,
+			acceptor: TlsAcceptor::from(Arc::new(server_config)),
+		})
+	}
+
+	pub(crate) fn get_acceptor(&self) -> TlsAcceptor {
+		self.acceptor.clone()
+	}
+
+	fn reload(&self) {
+		match self.config.load_certified_key() {
+			Ok(certified_key) => {
+				if let Ok(mut current) = self.resolver.certified_key.write() {
+					*current = certified_key;
+					log::info!("Reloaded TLS certificate from {:?}", self.config.cert_path);
+				}
+			}
+			// Keep serving the old certificate until the files are fixed
+			Err(err) => log::error!("Unable to reload TLS certificate: {}", err),
+		}
+	}
+
+	// Reloads the certificate whenever a SIGHUP is received or the files change. Never returns
+	pub(crate) async fn watch(self) {
+		let mut modified = self.config.get_modified_times();
+		let mut hangups = hangup_signals();
+		let mut ticks = match self.config.watch_interval {
+			Some(watch_interval) => tokio::time::interval(watch_interval)
+				.map(|_| ())
+				.left_stream(),
+			None => stream::pending().right_stream(),
+		};
+
+		loop {
+			match select(hangups.next(), ticks.next()).await {
+				Either::Left((Some(_), _)) => {
+					modified = self.config.get_modified_times();
+					self.reload();
+				}
+				Either::Left((None, _)) => hangups = stream::pending().boxed(),
+				Either::Right(_) => {
+					let current = self.config.get_modified_times();
+					if current.is_some() && current != modified {
+						modified = current;
+						self.reload();
+					}
+				}
+			}
+		}
+	}
+}
+
+#[cfg(unix)]
+fn hangup_signals() -> stream::BoxStream<'static, ()> {
+	use tokio::signal::unix::{signal, SignalKind};
+
+	match signal(SignalKind::hangup()) {
+		Ok(signal) => signal.boxed(),
+		Err(err) => {
+			log::error!("Unable to listen for SIGHUP: {}", err);
+			stream::pending().boxed()
+		}
+	}
+}
+
+#[cfg(not(unix))]
+fn hangup_signals() -> stream::BoxStream<'static, ()> {
+	stream::pending().boxed()
+}
+
+fn invalid_data(message: &str) -> IoError {
+	IoError::new(ErrorKind::InvalidData, message)
+}
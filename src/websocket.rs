@@ -0,0 +1,159 @@
+use crate::{
+	body::BodyStream,
+	middleware_handler::{parse_path, PathSegment},
+	router::Route,
+	Context,
+	Request,
+};
+use futures::{future::select, SinkExt, StreamExt};
+use hyper::upgrade::Upgraded;
+use sha1::{Digest, Sha1};
+use std::{borrow::Cow, future::Future, pin::Pin};
+use tokio_tungstenite::{
+	tungstenite::protocol::{CloseFrame, Role},
+	WebSocketStream,
+};
+
+pub use tokio_tungstenite::tungstenite::{
+	protocol::frame::coding::CloseCode as WebSocketCloseCode,
+	Error as WebSocketError,
+	Message as WebSocketMessage,
+};
+
+pub type WebSocketHandler = fn(WebSocket, Request) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+#[derive(Clone)]
+pub(crate) struct WebSocketRoute {
+	pub(crate) mounted_url: String,
+	pub(crate) segments: Vec<PathSegment>,
+	pub(crate) handler: WebSocketHandler,
+}
+
+impl WebSocketRoute {
+	pub(crate) fn new(path: &str, handler: WebSocketHandler) -> Self {
+		let (mounted_url, segments) = parse_path(path);
+		WebSocketRoute {
+			mounted_url,
+			segments,
+			handler,
+		}
+	}
+}
+
+impl Route for WebSocketRoute {
+	fn get_segments(&self) -> &[PathSegment] {
+		&self.segments
+	}
+
+	fn is_endpoint(&self) -> bool {
+		true
+	}
+}
+
+// A message oriented socket. Pings from the client are answered automatically while receiving
+pub struct WebSocket {
+	stream: WebSocketStream<Upgraded>,
+}
+
+impl WebSocket {
+	pub async fn recv(&mut self) -> Option<Result<WebSocketMessage, WebSocketError>> {
+		self.stream.next().await
+	}
+
+	pub async fn send(&mut self, message: WebSocketMessage) -> Result<(), WebSocketError> {
+		self.stream.send(message).await
+	}
+
+	pub async fn ping(&mut self, data: Vec<u8>) -> Result<(), WebSocketError> {
+		self.stream.send(WebSocketMessage::Ping(data)).await
+	}
+
+	pub async fn close(
+		&mut self,
+		code: WebSocketCloseCode,
+		reason: &str,
+	) -> Result<(), WebSocketError> {
+		self.stream
+			.close(Some(CloseFrame {
+				code,
+				reason: Cow::Owned(reason.to_string()),
+			}))
+			.await
+	}
+}
+
+// Accepts the handshake and hands the socket over to the handler once the connection is upgraded
+pub(crate) fn upgrade<TContext>(mut context: TContext, handler: WebSocketHandler) -> TContext
+where
+	TContext: Context,
+{
+	let is_upgrade = context
+		.get_header("Upgrade")
+		.map(|upgrade| upgrade.eq_ignore_ascii_case("websocket"))
+		.unwrap_or(false) &&
+		context
+			.get_header("Connection")
+			.map(|connection| {
+				connection
+					.split(',')
+					.any(|token| token.trim().eq_ignore_ascii_case("upgrade"))
+			})
+			.unwrap_or(false);
+	if !is_upgrade {
+		context
+			.status(426)
+			.header("Upgrade", "websocket")
+			.body("Expected a websocket upgrade");
+		return context;
+	}
+
+	if context.get_header("Sec-WebSocket-Version").as_deref() != Some("13") {
+		context
+			.status(400)
+			.header("Sec-WebSocket-Version", "13")
+			.body("Unsupported websocket version");
+		return context;
+	}
+
+	let key = context.get_header("Sec-WebSocket-Key");
+	let body = context.take_body_stream().map(BodyStream::into_inner);
+	let (key, body) = match (key, body) {
+		(Some(key), Some(body)) => (key, body),
+		_ => {
+			context.status(400).body("Invalid websocket handshake");
+			return context;
+		}
+	};
+
+	// The server doesn't finish draining while the websocket is open, and cuts it off once the
+	// drain timeout is up
+	let drain_guard = context.get_request_mut().drain_guard.take();
+	let request = context.get_request().clone();
+	tokio::spawn(async move {
+		match body.on_upgrade().await {
+			Ok(upgraded) => {
+				let stream = WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await;
+				let handling = handler(WebSocket { stream }, request);
+				if let Some(drain_guard) = drain_guard {
+					select(handling, Box::pin(drain_guard.aborted())).await;
+				} else {
+					handling.await;
+				}
+			}
+			Err(err) => log::error!("Unable to upgrade connection to a websocket: {}", err),
+		}
+	});
+
+	let mut hasher = Sha1::new();
+	hasher.update(key.trim().as_bytes());
+	hasher.update(WEBSOCKET_GUID.as_bytes());
+
+	context
+		.status(101)
+		.header("Upgrade", "websocket")
+		.header("Connection", "Upgrade")
+		.header("Sec-WebSocket-Accept", &base64::encode(hasher.finalize()));
+	context
+}
@@ -0,0 +1,69 @@
+use eve_rs::{App, BoundAddr, Context, Middleware};
+use futures::{
+	channel::oneshot,
+	future::{self, Either},
+};
+use std::{
+	fmt::Debug,
+	future::Future,
+	io::Error as IoError,
+	pin::Pin,
+	sync::{Mutex, OnceLock},
+};
+use tokio::sync::Mutex as AsyncMutex;
+
+// on_start can't capture anything, so the addresses it's given are passed on through here.
+// Servers are started one at a time, so that they get to the test that started them
+static STARTING: OnceLock<AsyncMutex<()>> = OnceLock::new();
+static STARTED: Mutex<Option<oneshot::Sender<Vec<BoundAddr>>>> = Mutex::new(None);
+
+fn started<'a, TState>(
+	addrs: &'a [BoundAddr],
+	_: &'a TState,
+) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+	if let Some(sender) = STARTED.lock().unwrap().take() {
+		let _ = sender.send(addrs.to_vec());
+	}
+	Box::pin(async {})
+}
+
+// Starts a server for the app with the given function, which should have it listen on port 0,
+// and returns the port it ended up on
+#[allow(dead_code)]
+pub async fn start<TContext, TMiddleware, TState, TServer>(
+	app: App<TContext, TMiddleware, TState>,
+	serve: impl FnOnce(App<TContext, TMiddleware, TState>) -> TServer,
+) -> u16
+where
+	TContext: 'static + Context + Debug + Send + Sync,
+	TMiddleware: 'static + Middleware<TContext> + Clone + Send + Sync,
+	TState: 'static + Send + Sync,
+	TServer: 'static + Future<Output = Result<(), IoError>> + Send,
+{
+	start_on_all(app, serve).await[0]
+		.get_socket_addr()
+		.unwrap()
+		.port()
+}
+
+// Like start, but returns every address the server ended up listening on
+pub async fn start_on_all<TContext, TMiddleware, TState, TServer>(
+	mut app: App<TContext, TMiddleware, TState>,
+	serve: impl FnOnce(App<TContext, TMiddleware, TState>) -> TServer,
+) -> Vec<BoundAddr>
+where
+	TContext: 'static + Context + Debug + Send + Sync,
+	TMiddleware: 'static + Middleware<TContext> + Clone + Send + Sync,
+	TState: 'static + Send + Sync,
+	TServer: 'static + Future<Output = Result<(), IoError>> + Send,
+{
+	let _starting = STARTING.get_or_init(|| AsyncMutex::new(())).lock().await;
+	let (sender, receiver) = oneshot::channel();
+	*STARTED.lock().unwrap() = Some(sender);
+	app.on_start(started);
+
+	match future::select(receiver, tokio::spawn(serve(app))).await {
+		Either::Left((addrs, _)) => addrs.unwrap(),
+		Either::Right((result, _)) => panic!("the server stopped before it started: {:?}", result),
+	}
+}
@@ -0,0 +1,83 @@
+use eve_rs::{default_middlewares::compression::compression, Context, DefaultContext, Request};
+use flate2::write::{GzDecoder, ZlibDecoder};
+use futures::{channel::mpsc, SinkExt, StreamExt};
+use hyper::{body::Bytes, Body, Request as HyperRequest};
+use std::{
+	io::{Error as IoError, Write},
+	net::SocketAddr,
+};
+
+async fn context(accept_encoding: &str) -> DefaultContext {
+	let request = HyperRequest::get("/")
+		.header("Accept-Encoding", accept_encoding)
+		.body(Body::empty())
+		.unwrap();
+	DefaultContext::new(Request::from_hyper(SocketAddr::from(([127, 0, 0, 1], 0)), request).await)
+}
+
+// Decompresses as much as can be made out of what the decoder has been given so far
+fn feed<TWriter>(decoder: &mut TWriter, compressed: &[u8])
+where
+	TWriter: Write,
+{
+	decoder.write_all(compressed).unwrap();
+	decoder.flush().unwrap();
+}
+
+#[tokio::test]
+async fn streamed_chunks_are_flushed_as_they_come() {
+	for encoding in ["gzip", "deflate"] {
+		let mut context = context(encoding).await;
+		let (mut sender, receiver) = mpsc::channel::<Result<Bytes, IoError>>(1);
+		context.stream(receiver);
+		compression().compress(&mut context);
+		assert_eq!(
+			context.get_response().get_header("Content-Encoding"),
+			Some(encoding.to_string())
+		);
+		let mut body = context.get_response_mut().take_body().into_stream();
+
+		let mut gzip = GzDecoder::new(vec![]);
+		let mut zlib = ZlibDecoder::new(vec![]);
+		let mut sent = vec![];
+		for chunk in ["first chunk\n", "second chunk\n", "third chunk\n"] {
+			sender.send(Ok(Bytes::from(chunk))).await.unwrap();
+			sent.extend_from_slice(chunk.as_bytes());
+
+			// Each chunk can be decompressed as soon as it's sent, without waiting for the rest
+			let compressed = body.next().await.unwrap().unwrap();
+			let received = if encoding == "gzip" {
+				feed(&mut gzip, &compressed);
+				gzip.get_ref().clone()
+			} else {
+				feed(&mut zlib, &compressed);
+				zlib.get_ref().clone()
+			};
+			assert_eq!(received, sent, "encoding {}", encoding);
+		}
+
+		drop(sender);
+		let trailer = body.next().await.unwrap().unwrap();
+		if encoding == "gzip" {
+			gzip.write_all(&trailer).unwrap();
+			assert_eq!(gzip.finish().unwrap(), sent);
+		} else {
+			zlib.write_all(&trailer).unwrap();
+			assert_eq!(zlib.finish().unwrap(), sent);
+		}
+		assert!(body.next().await.is_none());
+	}
+}
+
+#[tokio::test]
+async fn streams_are_left_alone_without_a_known_encoding() {
+	let mut context = context("br").await;
+	context.stream(futures::stream::iter(vec![Ok(Bytes::from("plain"))]));
+	compression().compress(&mut context);
+	assert!(context
+		.get_response()
+		.get_header("Content-Encoding")
+		.is_none());
+	let mut body = context.get_response_mut().take_body().into_stream();
+	assert_eq!(&body.next().await.unwrap().unwrap()[..], b"plain");
+}
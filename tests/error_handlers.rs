@@ -0,0 +1,289 @@
+use eve_rs::{
+	default_context_generator,
+	App,
+	Context,
+	DefaultContext,
+	DefaultMiddleware,
+	Error,
+	ErrorHandlerFn,
+	Request,
+	Response,
+};
+use hyper::{Body, Request as HyperRequest};
+use serde_json::{json, Value};
+use std::{future::Future, io::Error as IoError, net::SocketAddr, pin::Pin};
+
+type TestApp<TState> = App<DefaultContext, DefaultMiddleware<()>, TState>;
+
+fn fail() -> DefaultMiddleware<()> {
+	DefaultMiddleware::new(|_, _| Box::pin(async { Err(Error::conflict("taken")) }))
+}
+
+// Answers with the name of the app it belongs to, which is its state
+fn named_handler<'a>(
+	_: Request,
+	error: Error<DefaultContext>,
+	name: &'a &'static str,
+) -> Pin<Box<dyn Future<Output = Response> + Send + 'a>> {
+	Box::pin(async move {
+		let mut response = Response::new();
+		response.set_status(error.get_status());
+		response.set_body(&format!("{}: {}", name, error.get_message()));
+		response
+	})
+}
+
+fn app_named(name: &'static str) -> TestApp<&'static str> {
+	let mut app = TestApp::create(default_context_generator, name);
+	app.get("/fail", &[fail()]);
+	app
+}
+
+async fn error_response(app: &TestApp<&'static str>, path: &str) -> Response {
+	let request = HyperRequest::get(path).body(Body::empty()).unwrap();
+	let request = Request::from_hyper(SocketAddr::from(([127, 0, 0, 1], 0)), request).await;
+	let err = app
+		.resolve(DefaultContext::new(request.clone()))
+		.await
+		.unwrap_err();
+	app.handle_error(request, err).await
+}
+
+#[tokio::test]
+async fn sub_app_error_handler_takes_precedence() {
+	let mut api = app_named("api");
+	api.set_error_handler(named_handler as ErrorHandlerFn<DefaultContext, &'static str>);
+	// Without an error handler of its own, the closest one it's mounted under is used
+	api.use_sub_app("/v2", app_named("v2"));
+
+	let mut admin = app_named("admin");
+	admin.use_sub_app("/reports", app_named("reports"));
+
+	let mut app = app_named("root");
+	app.set_error_handler(named_handler as ErrorHandlerFn<DefaultContext, &'static str>);
+	app.use_sub_app("/api", api);
+	app.use_sub_app("/admin", admin);
+
+	for (path, body) in [
+		("/fail", "root: taken"),
+		("/api/fail", "api: taken"),
+		("/api/v2/fail", "api: taken"),
+		("/admin/fail", "root: taken"),
+		("/admin/reports/fail", "root: taken"),
+	] {
+		let response = error_response(&app, path).await;
+		assert_eq!(response.get_status(), 409, "path {}", path);
+		assert_eq!(response.get_body(), body.as_bytes(), "path {}", path);
+	}
+}
+
+#[tokio::test]
+async fn sub_apps_at_the_root_only_handle_the_errors_of_their_own_routes() {
+	let mut sub_app = TestApp::create(default_context_generator, "sub");
+	sub_app.get("/sub-fail", &[fail()]);
+	sub_app.set_error_handler(named_handler as ErrorHandlerFn<DefaultContext, &'static str>);
+
+	let mut app = TestApp::create(default_context_generator, "root");
+	app.get("/parent-fail", &[fail()]);
+	app.set_error_handler(named_handler as ErrorHandlerFn<DefaultContext, &'static str>);
+	app.use_sub_app("/", sub_app);
+
+	for (path, body) in [("/parent-fail", "root: taken"), ("/sub-fail", "sub: taken")] {
+		let response = error_response(&app, path).await;
+		assert_eq!(response.get_body(), body.as_bytes(), "path {}", path);
+	}
+}
+
+async fn default_error_response(
+	error: fn() -> Error<DefaultContext>,
+	accept: Option<&str>,
+) -> Response {
+	let mut app = TestApp::create(default_context_generator, ());
+	app.get(
+		"/fail",
+		&[DefaultMiddleware::from_fn(move |_, _| {
+			Box::pin(async move { Err(error()) })
+		})],
+	);
+
+	let mut request = HyperRequest::get("/fail");
+	if let Some(accept) = accept {
+		request = request.header("Accept", accept);
+	}
+	let request = Request::from_hyper(
+		SocketAddr::from(([127, 0, 0, 1], 0)),
+		request.body(Body::empty()).unwrap(),
+	)
+	.await;
+	let err = app
+		.resolve(DefaultContext::new(request.clone()))
+		.await
+		.unwrap_err();
+	app.handle_error(request, err).await
+}
+
+fn body_json(response: &Response) -> Value {
+	serde_json::from_slice(response.get_body()).unwrap()
+}
+
+#[tokio::test]
+async fn http_errors_prefer_problem_json() {
+	let unauthorized = || Error::unauthorized("Bearer realm=\"api\"");
+
+	for accept in [None, Some("*/*"), Some("application/problem+json")] {
+		let response = default_error_response(unauthorized, accept).await;
+		assert_eq!(response.get_status(), 401);
+		assert_eq!(response.get_content_type(), "application/problem+json");
+		assert_eq!(
+			response.get_header("WWW-Authenticate").unwrap(),
+			"Bearer realm=\"api\""
+		);
+		assert_eq!(
+			body_json(&response),
+			json!({
+				"type": "about:blank",
+				"title": "Unauthorized",
+				"status": 401,
+				"instance": "/fail",
+			})
+		);
+	}
+
+	let response = default_error_response(unauthorized, Some("application/json")).await;
+	assert_eq!(response.get_content_type(), "application/json");
+	assert_eq!(body_json(&response)["status"], 401);
+
+	let response = default_error_response(
+		|| Error::bad_request("Missing name"),
+		Some("text/html, text/plain;q=0.5"),
+	)
+	.await;
+	assert_eq!(response.get_status(), 400);
+	assert!(response.get_content_type().starts_with("text/plain"));
+	assert_eq!(
+		response.get_body(),
+		Error::<DefaultContext>::bad_request("Missing name")
+			.get_message()
+			.as_bytes()
+	);
+}
+
+#[tokio::test]
+async fn other_errors_prefer_text() {
+	let io_error = || {
+		Error::new(
+			None,
+			"disk full".to_string(),
+			507,
+			Box::new(IoError::other("no space left")),
+		)
+	};
+
+	for accept in [None, Some("*/*"), Some("text/plain")] {
+		let response = default_error_response(io_error, accept).await;
+		assert_eq!(response.get_status(), 507);
+		assert!(response.get_content_type().starts_with("text/plain"));
+		assert_eq!(response.get_body(), b"disk full");
+	}
+
+	let response = default_error_response(io_error, Some("application/problem+json")).await;
+	assert_eq!(response.get_content_type(), "application/problem+json");
+	assert_eq!(
+		body_json(&response),
+		json!({
+			"type": "about:blank",
+			"title": "Insufficient Storage",
+			"status": 507,
+			"detail": "disk full",
+		})
+	);
+
+	let response = default_error_response(io_error, Some("application/json")).await;
+	assert_eq!(response.get_content_type(), "application/json");
+	assert_eq!(
+		body_json(&response),
+		json!({"status": 507, "message": "disk full"})
+	);
+}
+
+// A context whose response got some way along before the error happened
+async fn context_with_cors() -> DefaultContext {
+	let request = HyperRequest::get("/fail").body(Body::empty()).unwrap();
+	let request = Request::from_hyper(SocketAddr::from(([127, 0, 0, 1], 0)), request).await;
+	let mut context = DefaultContext::new(request);
+	context
+		.header("Access-Control-Allow-Origin", "https://example.com")
+		.status(200)
+		.body("half a resp");
+	context
+}
+
+#[tokio::test]
+async fn error_response_keeps_the_headers_with_the_errors_status() {
+	let mut error = Error::conflict("taken").with_context(context_with_cors().await);
+	assert!(error.get_context().is_some());
+
+	let response = error.take_response();
+	assert_eq!(response.get_status(), 409);
+	assert_eq!(
+		response.get_header("Access-Control-Allow-Origin").unwrap(),
+		"https://example.com"
+	);
+	assert!(response.get_body().is_empty());
+	assert_eq!(response.get_header("Content-Length"), None);
+
+	// The context is gone once its response has been taken
+	assert!(error.get_context().is_none());
+	let response = error.take_response();
+	assert_eq!(response.get_status(), 409);
+	assert_eq!(response.get_header("Access-Control-Allow-Origin"), None);
+}
+
+// Fails with a conflict, or with some other error for any other status
+fn fail_with_cors(status: u16) -> DefaultMiddleware<u16> {
+	DefaultMiddleware::new_with_data(
+		|mut context, _, status| {
+			let status = *status;
+			Box::pin(async move {
+				context.header("Access-Control-Allow-Origin", "https://example.com");
+				let error = if status == 409 {
+					Error::conflict("taken")
+				} else {
+					Error::new(
+						None,
+						"disk full".to_string(),
+						status,
+						Box::new(IoError::other("no space left")),
+					)
+				};
+				Err(error.with_context(context))
+			})
+		},
+		status,
+	)
+}
+
+#[tokio::test]
+async fn default_error_handler_keeps_the_headers_of_the_context() {
+	for status in [409, 507] {
+		let mut app = App::<DefaultContext, DefaultMiddleware<u16>, ()>::create(
+			default_context_generator,
+			(),
+		);
+		app.get("/fail", &[fail_with_cors(status)]);
+
+		let request = HyperRequest::get("/fail").body(Body::empty()).unwrap();
+		let request = Request::from_hyper(SocketAddr::from(([127, 0, 0, 1], 0)), request).await;
+		let err = app
+			.resolve(DefaultContext::new(request.clone()))
+			.await
+			.unwrap_err();
+		let response = app.handle_error(request, err).await;
+		assert_eq!(response.get_status(), status);
+		assert_eq!(
+			response.get_header("Access-Control-Allow-Origin").unwrap(),
+			"https://example.com"
+		);
+		assert!(!response.get_body().is_empty());
+	}
+}
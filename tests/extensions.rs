@@ -0,0 +1,84 @@
+use eve_rs::{
+	default_context_generator,
+	default_middlewares::json,
+	App,
+	Context,
+	DefaultContext,
+	DefaultMiddleware,
+	Extensions,
+	Request,
+};
+use hyper::{Body, Request as HyperRequest};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+
+#[derive(Clone, Debug, PartialEq)]
+struct UserId(u64);
+
+#[derive(Clone, Debug, PartialEq)]
+struct Role(&'static str);
+
+#[test]
+fn values_are_kept_by_type() {
+	let mut extensions = Extensions::new();
+	assert!(extensions.is_empty());
+
+	assert_eq!(extensions.insert(UserId(1)), None);
+	assert_eq!(extensions.insert(Role("admin")), None);
+	assert_eq!(extensions.insert(UserId(2)), Some(UserId(1)));
+	assert_eq!(extensions.len(), 2);
+	assert_eq!(extensions.get::<UserId>(), Some(&UserId(2)));
+	assert_eq!(extensions.get::<Role>(), Some(&Role("admin")));
+	assert_eq!(extensions.get::<u64>(), None);
+
+	extensions.get_mut::<UserId>().unwrap().0 += 1;
+	assert_eq!(extensions.get::<UserId>(), Some(&UserId(3)));
+
+	// Clones don't share their values
+	let cloned = extensions.clone();
+	assert_eq!(extensions.remove::<Role>(), Some(Role("admin")));
+	assert!(!extensions.contains::<Role>());
+	assert!(cloned.contains::<Role>());
+
+	extensions.clear();
+	assert!(extensions.is_empty());
+	assert_eq!(cloned.len(), 2);
+}
+
+#[tokio::test]
+async fn middlewares_pass_values_on_through_extensions() {
+	let mut app =
+		App::<DefaultContext, DefaultMiddleware<()>, ()>::create(default_context_generator, ());
+	app.use_middleware("/", &[json::default_parser()]);
+	app.post(
+		"/users",
+		&[
+			DefaultMiddleware::new(|mut context, next| {
+				Box::pin(async move {
+					context.get_extensions_mut().insert(UserId(7));
+					next(context).await
+				})
+			}),
+			DefaultMiddleware::new(|mut context, _| {
+				Box::pin(async move {
+					let user_id = context.get_extensions().get::<UserId>().cloned();
+					let body = context.get_body_object().cloned();
+					context.json(json!({
+						"userId": user_id.map(|user_id| user_id.0),
+						"body": body,
+					}));
+					Ok(context)
+				})
+			}),
+		],
+	);
+
+	let request = HyperRequest::post("/users")
+		.header("Content-Type", "application/json")
+		.body(Body::from(r#"{"name":"eve"}"#))
+		.unwrap();
+	let request = Request::from_hyper(SocketAddr::from(([127, 0, 0, 1], 0)), request).await;
+	let context = app.resolve(DefaultContext::new(request)).await.unwrap();
+	let response = serde_json::from_slice::<Value>(context.get_response().get_body()).unwrap();
+	assert_eq!(response, json!({"userId": 7, "body": {"name": "eve"}}));
+}
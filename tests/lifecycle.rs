@@ -0,0 +1,113 @@
+use eve_rs::{default_context_generator, listen, App, Context, DefaultContext, DefaultMiddleware};
+use futures::channel::oneshot;
+use hyper::{client::conn::handshake, Body, Request as HyperRequest};
+use std::{
+	net::TcpListener,
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+use tokio::{net::TcpStream, time::delay_for};
+
+// What happened to the server, in order, and who to tell the port it ended up on
+#[derive(Default)]
+struct Lifecycle {
+	events: Mutex<Vec<String>>,
+	started: Mutex<Option<oneshot::Sender<u16>>>,
+}
+
+impl Lifecycle {
+	fn record(&self, event: &str) {
+		self.events.lock().unwrap().push(event.to_string());
+	}
+
+	fn get_events(&self) -> Vec<String> {
+		self.events.lock().unwrap().clone()
+	}
+}
+
+type TestApp = App<DefaultContext, DefaultMiddleware<()>, Arc<Lifecycle>>;
+
+fn app(lifecycle: Arc<Lifecycle>) -> TestApp {
+	let mut app = TestApp::create(default_context_generator, lifecycle);
+	app.get(
+		"/slow",
+		&[DefaultMiddleware::new(|mut context, _| {
+			Box::pin(async move {
+				delay_for(Duration::from_millis(300)).await;
+				context
+					.state::<Arc<Lifecycle>>()
+					.unwrap()
+					.record("responded");
+				context.body("slow");
+				Ok(context)
+			})
+		})],
+	);
+	app.on_start(|addrs, lifecycle| {
+		Box::pin(async move {
+			let port = addrs[0].get_socket_addr().unwrap().port();
+			lifecycle.record(&format!("started on {} address", addrs.len()));
+			if let Some(started) = lifecycle.started.lock().unwrap().take() {
+				let _ = started.send(port);
+			}
+		})
+	});
+	app.on_shutdown(|lifecycle| Box::pin(async move { lifecycle.record("shutdown") }));
+	app.on_drain_complete(|lifecycle| Box::pin(async move { lifecycle.record("drained") }));
+	app
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn hooks_run_around_the_drain_in_order() {
+	let lifecycle = Arc::new(Lifecycle::default());
+	let (started, port) = oneshot::channel();
+	*lifecycle.started.lock().unwrap() = Some(started);
+	let (stop, stopped) = oneshot::channel::<()>();
+	let server = tokio::spawn(listen(
+		app(lifecycle.clone()),
+		([127, 0, 0, 1], 0),
+		Some(async move {
+			let _ = stopped.await;
+		}),
+	));
+
+	// on_start is given the port the OS picked, before anything is accepted
+	let port = port.await.unwrap();
+	assert_ne!(port, 0);
+	assert_eq!(lifecycle.get_events(), ["started on 1 address"]);
+
+	let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+	let (mut sender, connection) = handshake(stream).await.unwrap();
+	tokio::spawn(connection);
+	let response =
+		tokio::spawn(sender.send_request(HyperRequest::get("/slow").body(Body::empty()).unwrap()));
+	delay_for(Duration::from_millis(100)).await;
+
+	// on_shutdown runs as soon as the signal resolves, while the request is still being handled,
+	// and on_drain_complete once it's done
+	stop.send(()).unwrap();
+	delay_for(Duration::from_millis(50)).await;
+	assert_eq!(lifecycle.get_events(), ["started on 1 address", "shutdown"]);
+	assert_eq!(response.await.unwrap().unwrap().status(), 200);
+	server.await.unwrap().unwrap();
+	assert_eq!(
+		lifecycle.get_events(),
+		["started on 1 address", "shutdown", "responded", "drained"]
+	);
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn listen_fails_when_the_address_is_taken() {
+	let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+	let lifecycle = Arc::new(Lifecycle::default());
+
+	let result = listen(
+		app(lifecycle.clone()),
+		taken.local_addr().unwrap(),
+		None::<futures::future::Pending<()>>,
+	)
+	.await;
+	assert!(result.is_err());
+	// Nothing was started, so there's nothing to shut down either
+	assert!(lifecycle.get_events().is_empty());
+}
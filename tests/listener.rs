@@ -0,0 +1,260 @@
+#![cfg(unix)]
+
+mod common;
+
+use eve_rs::{
+	default_context_generator,
+	listen,
+	App,
+	BoundAddr,
+	Context,
+	DefaultContext,
+	DefaultMiddleware,
+	Listener,
+};
+use futures::channel::oneshot;
+use hyper::{client::conn::handshake, Body, Request as HyperRequest};
+use std::{
+	fs,
+	io::ErrorKind,
+	net::{SocketAddr, TcpListener as StdTcpListener, UdpSocket},
+	os::unix::{fs::PermissionsExt, io::OwnedFd, net::UnixListener as StdUnixListener},
+	path::PathBuf,
+	time::Duration,
+};
+use tokio::{
+	io::{AsyncRead, AsyncWrite},
+	net::{TcpStream, UnixStream},
+	time::delay_for,
+};
+
+fn app() -> App<DefaultContext, DefaultMiddleware<()>, ()> {
+	let mut app =
+		App::<DefaultContext, DefaultMiddleware<()>, ()>::create(default_context_generator, ());
+	app.get(
+		"/",
+		&[DefaultMiddleware::new(|mut context, _| {
+			Box::pin(async move {
+				context.body("hello");
+				Ok(context)
+			})
+		})],
+	);
+	app
+}
+
+fn socket_path(name: &str) -> PathBuf {
+	let path = std::env::temp_dir().join(format!("eve-{}-{}.sock", std::process::id(), name));
+	let _ = fs::remove_file(&path);
+	path
+}
+
+// Serves the app on the socket until the returned sender is dropped
+async fn start(path: &PathBuf, permissions: Option<u32>) -> oneshot::Sender<()> {
+	let (stop, stopped) = oneshot::channel::<()>();
+	tokio::spawn(listen(
+		app(),
+		Listener::new().unix(path.clone(), permissions),
+		Some(async move {
+			let _ = stopped.await;
+		}),
+	));
+	for _ in 0..100 {
+		if UnixStream::connect(path).await.is_ok() {
+			return stop;
+		}
+		delay_for(Duration::from_millis(20)).await;
+	}
+	panic!("unable to connect to the server on {}", path.display());
+}
+
+async fn get(path: &PathBuf) -> Vec<u8> {
+	get_over(UnixStream::connect(path).await.unwrap()).await
+}
+
+async fn get_tcp(addr: SocketAddr) -> Vec<u8> {
+	get_over(TcpStream::connect(addr).await.unwrap()).await
+}
+
+async fn get_over<TStream>(stream: TStream) -> Vec<u8>
+where
+	TStream: 'static + AsyncRead + AsyncWrite + Unpin + Send,
+{
+	let (mut sender, connection) = handshake(stream).await.unwrap();
+	tokio::spawn(connection);
+	let response = sender
+		.send_request(HyperRequest::get("/").body(Body::empty()).unwrap())
+		.await
+		.unwrap();
+	hyper::body::to_bytes(response.into_body())
+		.await
+		.unwrap()
+		.to_vec()
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn unix_socket_has_its_permissions_when_it_shows_up() {
+	let path = socket_path("permissions");
+	let _stop = start(&path, Some(0o600)).await;
+
+	let mode = fs::metadata(&path).unwrap().permissions().mode();
+	assert_eq!(mode & 0o777, 0o600);
+	assert_eq!(get(&path).await, b"hello");
+
+	// Nothing is left behind from binding the socket elsewhere first
+	let leftovers = fs::read_dir(std::env::temp_dir())
+		.unwrap()
+		.filter_map(Result::ok)
+		.filter(|entry| {
+			entry
+				.file_name()
+				.to_string_lossy()
+				.starts_with(&format!(".eve-{}-permissions", std::process::id()))
+		})
+		.count();
+	assert_eq!(leftovers, 0);
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn stale_unix_socket_is_replaced() {
+	let path = socket_path("stale");
+	drop(StdUnixListener::bind(&path).unwrap());
+	assert!(path.exists());
+
+	let _stop = start(&path, None).await;
+	assert_eq!(get(&path).await, b"hello");
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn unix_socket_in_use_is_left_alone() {
+	let path = socket_path("in-use");
+	let _stop = start(&path, None).await;
+
+	let err = listen(
+		app(),
+		Listener::new().unix(path.clone(), None),
+		None::<futures::future::Pending<()>>,
+	)
+	.await
+	.unwrap_err();
+	assert_eq!(err.kind(), ErrorKind::AddrInUse);
+	assert_eq!(get(&path).await, b"hello");
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn unix_socket_path_that_isnt_a_socket_is_left_alone() {
+	let path = socket_path("not-a-socket");
+	fs::write(&path, "keep me").unwrap();
+
+	for permissions in [None, Some(0o600)] {
+		let err = listen(
+			app(),
+			Listener::new().unix(path.clone(), permissions),
+			None::<futures::future::Pending<()>>,
+		)
+		.await
+		.unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+		assert_eq!(fs::read_to_string(&path).unwrap(), "keep me");
+	}
+	fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn ipv6_addresses_are_listened_on() {
+	let port = common::start(app(), |app| {
+		listen(
+			app,
+			([0, 0, 0, 0, 0, 0, 0, 1], 0),
+			None::<futures::future::Pending<()>>,
+		)
+	})
+	.await;
+	assert_eq!(
+		get_tcp(SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 1], port))).await,
+		b"hello"
+	);
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn every_address_is_listened_on() {
+	let addrs = common::start_on_all(app(), |app| {
+		listen(
+			app,
+			Listener::new()
+				.tcp(([127, 0, 0, 1], 0))
+				.tcp(([0, 0, 0, 0, 0, 0, 0, 1], 0)),
+			None::<futures::future::Pending<()>>,
+		)
+	})
+	.await;
+
+	assert_eq!(addrs.len(), 2);
+	assert!(addrs[0].get_socket_addr().unwrap().is_ipv4());
+	assert!(addrs[1].get_socket_addr().unwrap().is_ipv6());
+	for addr in addrs {
+		assert_eq!(get_tcp(addr.get_socket_addr().unwrap()).await, b"hello");
+	}
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn listening_sockets_are_taken_over_from_their_fds() {
+	let tcp = StdTcpListener::bind("127.0.0.1:0").unwrap();
+	let tcp_addr = tcp.local_addr().unwrap();
+	let path = socket_path("fd");
+	let unix = StdUnixListener::bind(&path).unwrap();
+
+	let addrs = common::start_on_all(app(), |app| {
+		listen(
+			app,
+			Listener::new()
+				.fd(OwnedFd::from(tcp))
+				.fd(OwnedFd::from(unix)),
+			None::<futures::future::Pending<()>>,
+		)
+	})
+	.await;
+
+	assert_eq!(
+		addrs,
+		[BoundAddr::Ip(tcp_addr), BoundAddr::Unix(Some(path.clone()))]
+	);
+	assert_eq!(get_tcp(tcp_addr).await, b"hello");
+	assert_eq!(get(&path).await, b"hello");
+	fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn fds_that_arent_listening_sockets_are_refused() {
+	let file = fs::File::open("/dev/null").unwrap();
+	let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+	// A stream socket, but one that's connected instead of listening
+	let (not_listening, _) = std::os::unix::net::UnixStream::pair().unwrap();
+
+	for fd in [
+		OwnedFd::from(file),
+		OwnedFd::from(udp),
+		OwnedFd::from(not_listening),
+	] {
+		let err = listen(
+			app(),
+			Listener::new().fd(fd),
+			None::<futures::future::Pending<()>>,
+		)
+		.await
+		.unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::InvalidInput);
+	}
+}
+
+#[test]
+fn systemd_clears_the_listen_variables() {
+	std::env::set_var("LISTEN_PID", std::process::id().to_string());
+	std::env::set_var("LISTEN_FDS", "0");
+	std::env::set_var("LISTEN_FDNAMES", "");
+
+	assert!(Listener::new().systemd().is_empty());
+	for name in &["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+		assert!(std::env::var(name).is_err(), "{} is still set", name);
+	}
+}
@@ -0,0 +1,146 @@
+use eve_rs::{
+	default_context_generator,
+	App,
+	Context,
+	DefaultContext,
+	DefaultMiddleware,
+	HttpMethod,
+	Request,
+	Response,
+	RouteError,
+};
+use hyper::{Body, Request as HyperRequest};
+use std::net::SocketAddr;
+
+fn respond_with(body: &'static str) -> DefaultMiddleware<()> {
+	DefaultMiddleware::from_fn(move |mut context: DefaultContext, _| {
+		Box::pin(async move {
+			context.body(body);
+			Ok(context)
+		})
+	})
+}
+
+fn app() -> App<DefaultContext, DefaultMiddleware<()>, ()> {
+	let mut app =
+		App::<DefaultContext, DefaultMiddleware<()>, ()>::create(default_context_generator, ());
+	app.get("/users", &[respond_with("users")]);
+	app.post("/users", &[respond_with("created")]);
+	app.delete("/users/:id", &[respond_with("deleted")]);
+	app
+}
+
+async fn send(
+	app: &App<DefaultContext, DefaultMiddleware<()>, ()>,
+	method: &str,
+	path: &str,
+) -> Response {
+	let request = HyperRequest::builder()
+		.method(method)
+		.uri(path)
+		.body(Body::empty())
+		.unwrap();
+	let request = Request::from_hyper(SocketAddr::from(([127, 0, 0, 1], 0)), request).await;
+	let context = app.resolve(DefaultContext::new(request)).await.unwrap();
+	context.get_response().clone()
+}
+
+#[tokio::test]
+async fn other_methods_get_405_with_an_allow_header() {
+	let app = app();
+
+	let response = send(&app, "PUT", "/users").await;
+	assert_eq!(response.get_status(), 405);
+	assert_eq!(
+		response.get_header("Allow").unwrap(),
+		"GET, POST, HEAD, OPTIONS"
+	);
+
+	let response = send(&app, "GET", "/users/42").await;
+	assert_eq!(response.get_status(), 405);
+	assert_eq!(response.get_header("Allow").unwrap(), "DELETE, OPTIONS");
+
+	// Paths that don't exist under any method are still a 404
+	let response = send(&app, "PUT", "/posts").await;
+	assert_eq!(response.get_status(), 404);
+	assert!(response.get_header("Allow").is_none());
+}
+
+#[tokio::test]
+async fn head_is_answered_by_get_endpoints_without_a_body() {
+	let mut app = app();
+
+	let response = send(&app, "HEAD", "/users").await;
+	assert_eq!(response.get_status(), 200);
+	assert_eq!(response.get_header("Content-Length").unwrap(), "5");
+	assert!(response.get_body().is_empty());
+
+	// An explicit HEAD endpoint takes over from the GET one
+	app.head("/users", &[respond_with("head")]);
+	let response = send(&app, "HEAD", "/users").await;
+	assert_eq!(response.get_header("Content-Length").unwrap(), "4");
+	assert!(response.get_body().is_empty());
+
+	let response = send(&app, "HEAD", "/users/42").await;
+	assert_eq!(response.get_status(), 405);
+}
+
+#[tokio::test]
+async fn options_lists_the_allowed_methods_with_204() {
+	let mut app = app();
+
+	let response = send(&app, "OPTIONS", "/users").await;
+	assert_eq!(response.get_status(), 204);
+	assert_eq!(
+		response.get_header("Allow").unwrap(),
+		"GET, POST, HEAD, OPTIONS"
+	);
+	assert!(response.get_body().is_empty());
+
+	assert_eq!(send(&app, "OPTIONS", "/posts").await.get_status(), 404);
+
+	// An explicit OPTIONS endpoint takes over
+	app.options("/users", &[respond_with("options")]);
+	let response = send(&app, "OPTIONS", "/users").await;
+	assert_eq!(response.get_status(), 200);
+	assert_eq!(response.get_body(), b"options");
+}
+
+#[tokio::test]
+async fn trace_routes_are_not_registered_for_get() {
+	let mut app = app();
+	app.trace("/trace", &[respond_with("trace")]);
+
+	assert_eq!(send(&app, "TRACE", "/trace").await.get_body(), b"trace");
+	let response = send(&app, "GET", "/trace").await;
+	assert_eq!(response.get_status(), 405);
+	assert_eq!(response.get_header("Allow").unwrap(), "TRACE, OPTIONS");
+}
+
+#[tokio::test]
+async fn methods_are_case_sensitive_tokens() {
+	assert_eq!("GET".parse::<HttpMethod>(), Ok(HttpMethod::Get));
+	assert_eq!(
+		"get".parse::<HttpMethod>(),
+		Ok(HttpMethod::Custom("get".to_string()))
+	);
+	assert!("BAD METHOD".parse::<HttpMethod>().is_err());
+
+	let mut app = app();
+	app.method("PURGE", "/cache", &[respond_with("purged")])
+		.unwrap();
+	app.method("PATCH", "/users", &[respond_with("patched")])
+		.unwrap();
+	app.method("patch", "/users", &[respond_with("lowercase")])
+		.unwrap();
+	assert!(matches!(
+		app.method("BAD METHOD", "/users", &[respond_with("bad")]),
+		Err(RouteError::InvalidMethod(method)) if method == "BAD METHOD"
+	));
+
+	assert_eq!(send(&app, "PURGE", "/cache").await.get_body(), b"purged");
+	assert_eq!(send(&app, "PATCH", "/users").await.get_body(), b"patched");
+	assert_eq!(send(&app, "patch", "/users").await.get_body(), b"lowercase");
+	// Other casings of a method are unrelated to it
+	assert_eq!(send(&app, "purge", "/cache").await.get_status(), 501);
+}
@@ -0,0 +1,88 @@
+use eve_rs::{default_context_generator, App, Context, DefaultContext, DefaultMiddleware, Request};
+use hyper::{Body, Request as HyperRequest};
+use std::{
+	net::SocketAddr,
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc,
+	},
+};
+
+type TestApp = App<DefaultContext, DefaultMiddleware<String>, ()>;
+
+async fn get(app: &TestApp, path: &str) -> DefaultContext {
+	let request = HyperRequest::get(path).body(Body::empty()).unwrap();
+	let request = Request::from_hyper(SocketAddr::from(([127, 0, 0, 1], 0)), request).await;
+	app.resolve(DefaultContext::new(request)).await.unwrap()
+}
+
+#[tokio::test]
+async fn closures_can_capture_what_they_need() {
+	let hits = Arc::new(AtomicUsize::new(0));
+	let counter = hits.clone();
+
+	let mut app = TestApp::create(default_context_generator, ());
+	app.get(
+		"/",
+		&[DefaultMiddleware::from_fn(move |mut context, _| {
+			let hits = counter.fetch_add(1, Ordering::SeqCst) + 1;
+			Box::pin(async move {
+				context.body(&hits.to_string());
+				Ok(context)
+			})
+		})],
+	);
+
+	assert_eq!(get(&app, "/").await.get_response().get_body(), b"1");
+	assert_eq!(get(&app, "/").await.get_response().get_body(), b"2");
+	assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn handlers_get_their_data_every_time_they_run() {
+	let greeting = DefaultMiddleware::new_with_data(
+		|mut context, next, data| {
+			context.header("X-Greeting", data);
+			next(context)
+		},
+		"hello".to_string(),
+	);
+	assert_eq!(greeting.get_data(), "hello");
+
+	let prefix = Arc::new("user ".to_string());
+	let mut app = TestApp::create(default_context_generator, ());
+	app.get(
+		"/users/:id",
+		&[
+			greeting,
+			DefaultMiddleware::from_fn_with_data(
+				move |mut context, _, data| {
+					let body = format!(
+						"{}{} ({})",
+						prefix,
+						context.get_request().get_params()["id"],
+						data
+					);
+					Box::pin(async move {
+						context.body(&body);
+						Ok(context)
+					})
+				},
+				"from data".to_string(),
+			),
+		],
+	);
+
+	let context = get(&app, "/users/42").await;
+	assert_eq!(
+		context.get_response().get_header("X-Greeting").unwrap(),
+		"hello"
+	);
+	assert_eq!(context.get_response().get_body(), b"user 42 (from data)");
+
+	// The default data is used when there's none given
+	assert_eq!(
+		DefaultMiddleware::<String>::from_fn(|context, next| next(context)).get_data(),
+		""
+	);
+}
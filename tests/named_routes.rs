@@ -0,0 +1,191 @@
+use eve_rs::{
+	default_context_generator,
+	App,
+	Context,
+	DefaultContext,
+	DefaultMiddleware,
+	NamedRoutes,
+	UrlForError,
+};
+
+type TestApp = App<DefaultContext, DefaultMiddleware<()>, ()>;
+
+fn ok() -> DefaultMiddleware<()> {
+	DefaultMiddleware::new(|mut context, _| {
+		Box::pin(async move {
+			context.body("ok");
+			Ok(context)
+		})
+	})
+}
+
+// A sub app with an index and a page of its own
+fn sub_app() -> TestApp {
+	let mut app = TestApp::create(default_context_generator, ());
+	app.get_named("index", "/", &[ok()]);
+	app.get_named("page", "/pages/:page", &[ok()]);
+	app
+}
+
+#[test]
+fn urls_are_built_from_the_route() {
+	let mut app = TestApp::create(default_context_generator, ());
+	app.get_named("user.show", "/users/:id(\\d+)/:tab?", &[ok()]);
+	app.get_named("file", "/files/*path", &[ok()]);
+
+	assert_eq!(
+		app.url_for("user.show", &[("id", "42")]).unwrap(),
+		"/users/42"
+	);
+	assert_eq!(
+		app.url_for("user.show", &[("id", "42"), ("tab", "posts")])
+			.unwrap(),
+		"/users/42/posts"
+	);
+	assert_eq!(
+		app.url_for("file", &[("path", "a b/c.txt"), ("download", "1")])
+			.unwrap(),
+		"/files/a%20b/c.txt?download=1"
+	);
+	assert!(matches!(
+		app.url_for("user.show", &[]),
+		Err(UrlForError::MissingParam(param)) if param == "id"
+	));
+	assert!(matches!(
+		app.url_for("user.show", &[("id", "me")]),
+		Err(UrlForError::InvalidParam(param)) if param == "id"
+	));
+	assert!(matches!(
+		app.url_for("missing", &[]),
+		Err(UrlForError::UnknownRoute(name)) if name == "missing"
+	));
+}
+
+#[test]
+fn mounted_names_are_prefixed_with_the_base_path() {
+	let mut api = sub_app();
+	api.use_sub_app("/v1", sub_app());
+
+	let mut app = TestApp::create(default_context_generator, ());
+	app.get_named("index", "/", &[ok()]);
+	app.use_sub_app("/api", api);
+	app.use_sub_app("/admin/", sub_app());
+
+	for (name, url) in [
+		("index", "/"),
+		("api.index", "/api"),
+		("api.v1.index", "/api/v1"),
+		("admin.index", "/admin"),
+	] {
+		assert_eq!(app.url_for(name, &[]).unwrap(), url, "route {}", name);
+	}
+	for (name, url) in [
+		("api.page", "/api/pages/about"),
+		("api.v1.page", "/api/v1/pages/about"),
+		("admin.page", "/admin/pages/about"),
+	] {
+		assert_eq!(
+			app.url_for(name, &[("page", "about")]).unwrap(),
+			url,
+			"route {}",
+			name
+		);
+	}
+
+	// The route list has them under the same names
+	let mut names = app
+		.routes()
+		.filter_map(|route| route.get_name().map(str::to_string))
+		.collect::<Vec<_>>();
+	names.sort();
+	assert_eq!(
+		names,
+		vec![
+			"admin.index",
+			"admin.page",
+			"api.index",
+			"api.page",
+			"api.v1.index",
+			"api.v1.page",
+			"index",
+		]
+	);
+}
+
+#[test]
+fn mounting_leaves_the_sub_apps_routes_alone() {
+	// Both sub apps share their named routes, like they would with a common handlebars registry
+	let shared = NamedRoutes::new();
+	let mut first = sub_app();
+	first.set_named_routes(shared.clone());
+	let mut second = TestApp::create(default_context_generator, ());
+	second.set_named_routes(shared.clone());
+
+	let mut app = TestApp::create(default_context_generator, ());
+	app.use_sub_app("/first", first);
+	app.use_sub_app("/second", second);
+
+	assert_eq!(shared.url_for("index", &[]).unwrap(), "/");
+	assert_eq!(app.url_for("first.index", &[]).unwrap(), "/first");
+	assert_eq!(app.url_for("second.index", &[]).unwrap(), "/second");
+	assert_eq!(
+		app.get_named_routes().get_path("second.page").unwrap(),
+		"/second/pages/:page"
+	);
+}
+
+#[test]
+fn names_taken_at_the_root_keep_their_first_route() {
+	let mut other = TestApp::create(default_context_generator, ());
+	other.get_named("index", "/other", &[ok()]);
+
+	let mut app = TestApp::create(default_context_generator, ());
+	app.get_named("index", "/", &[ok()]);
+	app.use_sub_app("/", other);
+
+	assert_eq!(app.url_for("index", &[]).unwrap(), "/");
+}
+
+#[test]
+fn sub_apps_mounted_under_params_are_named_after_them() {
+	let mut app = TestApp::create(default_context_generator, ());
+	app.use_sub_app("/users/:id(\\d+)", sub_app());
+	app.use_sub_app("/:lang?/docs", sub_app());
+	app.use_sub_app("/files/*/raw", sub_app());
+
+	assert_eq!(
+		app.url_for("users.id.page", &[("id", "42"), ("page", "about")])
+			.unwrap(),
+		"/users/42/pages/about"
+	);
+	assert_eq!(
+		app.url_for("lang.docs.index", &[("lang", "en")]).unwrap(),
+		"/en/docs"
+	);
+	assert_eq!(
+		app.get_named_routes().get_path("files.raw.index").unwrap(),
+		"/files/*/raw"
+	);
+}
+
+#[test]
+fn clones_have_named_routes_of_their_own() {
+	let mut app = TestApp::create(default_context_generator, ());
+	app.get_named("index", "/", &[ok()]);
+
+	let mut clone = app.clone();
+	clone.get_named("about", "/about", &[ok()]);
+	app.get_named("contact", "/contact", &[ok()]);
+
+	assert_eq!(clone.url_for("index", &[]).unwrap(), "/");
+	assert_eq!(clone.url_for("about", &[]).unwrap(), "/about");
+	assert!(clone.url_for("contact", &[]).is_err());
+	assert!(app.url_for("about", &[]).is_err());
+
+	// Unless they're shared on purpose
+	let shared = NamedRoutes::new();
+	app.set_named_routes(shared.clone());
+	clone.set_named_routes(shared.clone());
+	assert_eq!(shared.url_for("about", &[]).unwrap(), "/about");
+	assert_eq!(app.url_for("about", &[]).unwrap(), "/about");
+}
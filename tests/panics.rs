@@ -0,0 +1,117 @@
+mod common;
+
+use eve_rs::{
+	default_context_generator,
+	listen_with_config,
+	App,
+	Context,
+	DefaultContext,
+	DefaultMiddleware,
+	ServerConfig,
+	ServerStats,
+};
+use hyper::{
+	client::conn::{handshake, SendRequest},
+	Body,
+	Request as HyperRequest,
+};
+use tokio::net::TcpStream;
+
+fn app() -> App<DefaultContext, DefaultMiddleware<()>, ()> {
+	let mut app =
+		App::<DefaultContext, DefaultMiddleware<()>, ()>::create(default_context_generator, ());
+	app.get(
+		"/",
+		&[DefaultMiddleware::new(|mut context, _| {
+			Box::pin(async move {
+				context.body("hello");
+				Ok(context)
+			})
+		})],
+	);
+	app.get(
+		"/panic",
+		&[DefaultMiddleware::new(|_, _| {
+			Box::pin(async move { panic!("handler panicked") })
+		})],
+	);
+	app.get(
+		"/invalid-header",
+		&[DefaultMiddleware::new(|mut context, _| {
+			Box::pin(async move {
+				context.header("X-Invalid", "first\nsecond").body("hello");
+				Ok(context)
+			})
+		})],
+	);
+	app.get(
+		"/invalid-status",
+		&[DefaultMiddleware::new(|mut context, _| {
+			Box::pin(async move {
+				context.status(1000).body("hello");
+				Ok(context)
+			})
+		})],
+	);
+	app
+}
+
+// Each server counts on its own
+async fn start() -> (SendRequest<Body>, ServerStats) {
+	let config = ServerConfig::new();
+	let stats = config.get_stats().clone();
+	let port = common::start(app(), |app| {
+		listen_with_config(
+			app,
+			([127, 0, 0, 1], 0),
+			config,
+			None::<futures::future::Pending<()>>,
+		)
+	})
+	.await;
+
+	let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+	let (sender, connection) = handshake(stream).await.unwrap();
+	tokio::spawn(connection);
+	(sender, stats)
+}
+
+async fn get(sender: &mut SendRequest<Body>, path: &str) -> (u16, Vec<u8>) {
+	futures::future::poll_fn(|cx| sender.poll_ready(cx))
+		.await
+		.unwrap();
+	let response = sender
+		.send_request(HyperRequest::get(path).body(Body::empty()).unwrap())
+		.await
+		.unwrap();
+	let status = response.status().as_u16();
+	let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+	(status, body.to_vec())
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn panics_get_500_and_are_counted() {
+	let (mut sender, stats) = start().await;
+
+	for count in 1..=2 {
+		assert_eq!(get(&mut sender, "/panic").await.0, 500);
+		assert_eq!(stats.get_panic_count(), count);
+		assert_eq!(stats.get_rejected_response_count(), 0);
+
+		// The connection is still good for the next request
+		assert_eq!(get(&mut sender, "/").await, (200, b"hello".to_vec()));
+	}
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn rejected_responses_get_500_and_are_counted_apart_from_panics() {
+	let (mut sender, stats) = start().await;
+
+	for (count, path) in (1..).zip(["/invalid-header", "/invalid-status"]) {
+		assert_eq!(get(&mut sender, path).await.0, 500, "path {}", path);
+		assert_eq!(stats.get_rejected_response_count(), count, "path {}", path);
+		assert_eq!(stats.get_panic_count(), 0, "path {}", path);
+
+		assert_eq!(get(&mut sender, "/").await, (200, b"hello".to_vec()));
+	}
+}
@@ -0,0 +1,129 @@
+use eve_rs::{
+	default_context_generator,
+	App,
+	Context,
+	DefaultContext,
+	DefaultMiddleware,
+	Error,
+	Request,
+};
+use hyper::{Body, Request as HyperRequest};
+use std::{collections::HashMap, net::SocketAddr};
+
+type TestApp = App<DefaultContext, DefaultMiddleware<()>, ()>;
+
+async fn request(path: &str) -> Request {
+	let request = HyperRequest::get(path).body(Body::empty()).unwrap();
+	Request::from_hyper(SocketAddr::from(([127, 0, 0, 1], 0)), request).await
+}
+
+async fn resolve(app: &TestApp, path: &str) -> Result<DefaultContext, Error<DefaultContext>> {
+	app.resolve(DefaultContext::new(request(path).await)).await
+}
+
+// The params the request to the path ended up with
+async fn params(route: &str, path: &str) -> HashMap<String, String> {
+	let mut app = TestApp::create(default_context_generator, ());
+	app.get(
+		route,
+		&[DefaultMiddleware::new(|mut context, _| {
+			Box::pin(async move {
+				let params = serde_json::to_string(context.get_request().get_params()).unwrap();
+				context.body(&params);
+				Ok(context)
+			})
+		})],
+	);
+	let context = resolve(&app, path).await.unwrap();
+	serde_json::from_slice(context.get_response().get_body()).unwrap_or_default()
+}
+
+fn map(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+	pairs
+		.iter()
+		.map(|(key, value)| (key.to_string(), value.to_string()))
+		.collect()
+}
+
+#[tokio::test]
+async fn params_are_percent_decoded() {
+	assert_eq!(
+		params("/users/:name", "/users/a%20b").await,
+		map(&[("name", "a b")])
+	);
+	assert_eq!(
+		params("/users/:name", "/users/%C3%A9ve").await,
+		map(&[("name", "éve")])
+	);
+	// An encoded / is part of the value, and doesn't split the segment
+	assert_eq!(
+		params("/users/:name", "/users/a%2Fb").await,
+		map(&[("name", "a/b")])
+	);
+	assert_eq!(
+		params("/files/:name.json", "/files/my%20report.json").await,
+		map(&[("name", "my report")])
+	);
+	assert_eq!(
+		params("/docs/*page", "/docs/getting%20started/intro").await,
+		map(&[("page", "getting started/intro")])
+	);
+	// Constraints are checked against the value as it was sent
+	assert_eq!(params("/users/:id(\\d+)", "/users/%31").await, map(&[]));
+}
+
+#[tokio::test]
+async fn query_values_are_decoded_and_repeated_keys_kept() {
+	let request = request("/search?q=hello+world&tag=a%26b&tag=c&empty&tag=d").await;
+	assert_eq!(request.get_query()["q"], "hello world");
+	assert_eq!(request.get_query()["empty"], "");
+	// The first value is the one kept when asking for a single one
+	assert_eq!(request.get_query()["tag"], "a&b");
+	assert_eq!(request.get_query_all("tag"), vec!["a&b", "c", "d"]);
+	assert!(request.get_query_all("missing").is_empty());
+}
+
+#[tokio::test]
+async fn query_and_params_can_be_parsed_into_types() {
+	let request = request("/?page=2&limit=20").await;
+	assert_eq!(
+		request.get_query_as::<HashMap<String, u32>>().unwrap(),
+		map(&[("page", "2"), ("limit", "20")])
+			.into_iter()
+			.map(|(key, value)| (key, value.parse().unwrap()))
+			.collect()
+	);
+	assert!(request.get_query_as::<HashMap<String, bool>>().is_err());
+
+	let mut app = TestApp::create(default_context_generator, ());
+	app.get(
+		"/users/:id/posts/:post",
+		&[DefaultMiddleware::new(|mut context, _| {
+			Box::pin(async move {
+				let params = context.params::<HashMap<String, u64>>()?;
+				let query = context.query::<HashMap<String, u32>>()?;
+				let body = format!(
+					"{} {} {}",
+					params["id"],
+					params["post"],
+					query.get("page").copied().unwrap_or(1)
+				);
+				context.body(&body);
+				Ok(context)
+			})
+		})],
+	);
+
+	let context = resolve(&app, "/users/42/posts/7?page=3").await.unwrap();
+	assert_eq!(context.get_response().get_body(), b"42 7 3");
+	let context = resolve(&app, "/users/42/posts/7").await.unwrap();
+	assert_eq!(context.get_response().get_body(), b"42 7 1");
+
+	// What doesn't fit the type is a bad request
+	for path in ["/users/abc/posts/7", "/users/42/posts/7?page=first"] {
+		match resolve(&app, path).await {
+			Err(err) => assert_eq!(err.get_status(), 400, "path {}", path),
+			Ok(_) => panic!("path {} was accepted", path),
+		}
+	}
+}
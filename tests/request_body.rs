@@ -0,0 +1,147 @@
+use eve_rs::{
+	default_context_generator,
+	default_middlewares::{json, url_encoded},
+	App,
+	BodyError,
+	Context,
+	DefaultContext,
+	DefaultMiddleware,
+	Request,
+};
+use futures::{stream, TryStreamExt};
+use hyper::{Body, Request as HyperRequest};
+use std::{io::Error as IoError, net::SocketAddr};
+use tokio::io::AsyncReadExt;
+
+async fn request(body: &'static [u8]) -> Request {
+	let request = HyperRequest::post("/").body(Body::from(body)).unwrap();
+	Request::from_hyper(SocketAddr::from(([127, 0, 0, 1], 0)), request).await
+}
+
+// A request whose body arrives in the given chunks
+async fn chunked_request(chunks: &[&'static str]) -> Request {
+	let chunks = chunks
+		.iter()
+		.map(|chunk| Ok::<_, IoError>(*chunk))
+		.collect::<Vec<_>>();
+	let request = HyperRequest::post("/")
+		.body(Body::wrap_stream(stream::iter(chunks)))
+		.unwrap();
+	Request::from_hyper(SocketAddr::from(([127, 0, 0, 1], 0)), request).await
+}
+
+#[tokio::test]
+async fn body_is_only_there_once_loaded() {
+	let mut request = request(b"hello").await;
+	assert!(request.get_body_bytes().is_none());
+	assert!(matches!(request.get_body(), Err(BodyError::NotLoaded)));
+
+	assert_eq!(request.load_body().await.unwrap(), b"hello");
+	assert_eq!(request.get_body_bytes(), Some(&b"hello"[..]));
+	assert_eq!(request.get_body().unwrap(), "hello");
+
+	// Loading again keeps what was read the first time
+	assert_eq!(request.load_body().await.unwrap(), b"hello");
+}
+
+#[tokio::test]
+async fn body_that_is_not_utf8_is_an_error() {
+	let mut request = request(&[0xff, 0xfe]).await;
+	request.load_body().await.unwrap();
+	assert_eq!(request.get_body_bytes(), Some(&[0xff, 0xfe][..]));
+	assert!(matches!(request.get_body(), Err(BodyError::InvalidUtf8(_))));
+}
+
+#[tokio::test]
+async fn body_cant_be_loaded_once_the_stream_is_taken() {
+	let mut request = request(b"hello").await;
+	assert!(request.take_body_stream().is_some());
+	assert!(request.take_body_stream().is_none());
+	assert!(matches!(
+		request.load_body().await,
+		Err(BodyError::StreamTaken)
+	));
+	assert!(matches!(request.get_body(), Err(BodyError::NotLoaded)));
+}
+
+#[tokio::test]
+async fn body_stream_gives_the_chunks_as_they_arrive() {
+	let mut request = chunked_request(&["hel", "lo ", "world"]).await;
+	let chunks = request
+		.take_body_stream()
+		.unwrap()
+		.map_ok(|chunk| String::from_utf8(chunk.to_vec()).unwrap())
+		.try_collect::<Vec<_>>()
+		.await
+		.unwrap();
+	assert_eq!(chunks, ["hel", "lo ", "world"]);
+	assert!(request.get_body_reader().is_none());
+}
+
+#[tokio::test]
+async fn body_reader_reads_across_chunks() {
+	let mut request = chunked_request(&["hel", "lo ", "world"]).await;
+	let mut reader = request.get_body_reader().unwrap();
+
+	// Reads don't have to line up with the chunks
+	let mut start = [0; 5];
+	reader.read_exact(&mut start).await.unwrap();
+	assert_eq!(&start, b"hello");
+	let mut rest = String::new();
+	reader.read_to_string(&mut rest).await.unwrap();
+	assert_eq!(rest, " world");
+	assert!(request.take_body_stream().is_none());
+}
+
+// Streams whatever body it gets back, or says that it was already read
+fn echo_stream() -> DefaultMiddleware<()> {
+	DefaultMiddleware::new(|mut context, _| {
+		Box::pin(async move {
+			let body = match context.take_body_stream() {
+				Some(stream) => {
+					let body = stream
+						.map_ok(|chunk| chunk.to_vec())
+						.try_concat()
+						.await
+						.unwrap();
+					String::from_utf8(body).unwrap()
+				}
+				None => "already read".to_string(),
+			};
+			context.body(&body);
+			Ok(context)
+		})
+	})
+}
+
+async fn parsed(content_type: &str, body: &'static str) -> String {
+	let mut app =
+		App::<DefaultContext, DefaultMiddleware<()>, ()>::create(default_context_generator, ());
+	app.use_middleware(
+		"/",
+		&[json::default_parser(), url_encoded::default_parser()],
+	);
+	app.post("/", &[echo_stream()]);
+
+	let request = HyperRequest::post("/")
+		.header("Content-Type", content_type)
+		.body(Body::from(body))
+		.unwrap();
+	let request = Request::from_hyper(SocketAddr::from(([127, 0, 0, 1], 0)), request).await;
+	let context = app.resolve(DefaultContext::new(request)).await.unwrap();
+	String::from_utf8(context.get_response().get_body().to_vec()).unwrap()
+}
+
+#[tokio::test]
+async fn parsers_only_read_the_bodies_they_parse() {
+	assert_eq!(parsed("application/json", "{}").await, "already read");
+	assert_eq!(
+		parsed("application/x-www-form-urlencoded", "a=b").await,
+		"already read"
+	);
+	assert_eq!(
+		parsed("application/octet-stream", "raw bytes").await,
+		"raw bytes"
+	);
+	assert_eq!(parsed("text/plain", "some text").await, "some text");
+}
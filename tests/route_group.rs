@@ -0,0 +1,91 @@
+use eve_rs::{default_context_generator, App, Context, DefaultContext, DefaultMiddleware, Request};
+use hyper::{Body, Request as HyperRequest};
+use std::net::SocketAddr;
+
+type TestApp = App<DefaultContext, DefaultMiddleware<String>, ()>;
+
+// Adds its name to the ones that ran, then passes the request on
+fn record(name: &str) -> DefaultMiddleware<String> {
+	DefaultMiddleware::new_with_data(
+		|mut context, next, name| {
+			context.append_header("X-Ran", name);
+			next(context)
+		},
+		name.to_string(),
+	)
+}
+
+// The names of what ran for the request, in order
+async fn ran(app: &TestApp, method: &str, path: &str) -> Vec<String> {
+	let request = HyperRequest::builder()
+		.method(method)
+		.uri(path)
+		.body(Body::empty())
+		.unwrap();
+	let request = Request::from_hyper(SocketAddr::from(([127, 0, 0, 1], 0)), request).await;
+	let context = app.resolve(DefaultContext::new(request)).await.unwrap();
+	context
+		.get_response()
+		.get_headers()
+		.get("X-Ran")
+		.cloned()
+		.unwrap_or_default()
+}
+
+fn app() -> TestApp {
+	let mut app = TestApp::create(default_context_generator, ());
+	app.use_middleware("/", &[record("global")]);
+	app.group("/admin", |group| {
+		group.get("/health", &[record("health")]);
+		group.use_middleware(&[record("auth"), record("log")]);
+		group.get("/users", &[record("users")]);
+		group.group("/reports", |group| {
+			group.use_middleware(&[record("audit")]);
+			group.get("/", &[record("reports")]);
+		});
+		group.post("/users", &[record("create")]);
+	});
+	app.get("/admin/public", &[record("public")]);
+	app.get("/home", &[record("home")]);
+	app
+}
+
+#[tokio::test]
+async fn group_middlewares_run_in_order_before_the_endpoint() {
+	let app = app();
+	assert_eq!(
+		ran(&app, "GET", "/admin/users").await,
+		["global", "auth", "log", "users"]
+	);
+	assert_eq!(
+		ran(&app, "GET", "/admin/reports").await,
+		["global", "auth", "log", "audit", "reports"]
+	);
+	assert_eq!(
+		ran(&app, "POST", "/admin/users").await,
+		["global", "auth", "log", "create"]
+	);
+}
+
+#[tokio::test]
+async fn group_middlewares_dont_run_outside_the_group() {
+	let app = app();
+	for (method, path, expected) in [
+		// Registered before the group's middlewares were added
+		("GET", "/admin/health", &["global", "health"][..]),
+		// Under the base path, but not registered on the group
+		("GET", "/admin/public", &["global", "public"]),
+		("GET", "/home", &["global", "home"]),
+		// Not found, or not allowed, with nothing of the group's run
+		("GET", "/admin/missing", &["global"]),
+		("DELETE", "/admin/users", &["global"]),
+	] {
+		assert_eq!(
+			ran(&app, method, path).await,
+			expected,
+			"{} {}",
+			method,
+			path
+		);
+	}
+}
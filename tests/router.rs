@@ -0,0 +1,531 @@
+use eve_rs::{
+	default_context_generator,
+	App,
+	Context,
+	DefaultContext,
+	Error,
+	Middleware,
+	NextHandler,
+	PathErrorKind,
+	Request,
+	RouteError,
+};
+use hyper::{Body, Request as HyperRequest};
+use regex::Regex;
+use std::net::SocketAddr;
+
+// Records the order in which middlewares ran, along with the params they saw
+#[derive(Clone)]
+struct Recorder(usize);
+
+#[async_trait::async_trait]
+impl Middleware<DefaultContext> for Recorder {
+	async fn run_middleware(
+		&self,
+		mut context: DefaultContext,
+		next: NextHandler<DefaultContext>,
+	) -> Result<DefaultContext, Error<DefaultContext>> {
+		let mut params = context
+			.get_request()
+			.get_params()
+			.iter()
+			.map(|(key, value)| format!("{}={}", key, value))
+			.collect::<Vec<_>>();
+		params.sort();
+		context.append_header("X-Matched", &format!("{} {}", self.0, params.join(",")));
+		next(context).await
+	}
+}
+
+// The reference semantics of a mounted path, expressed as a regex
+fn reference_regex(path: &str, is_endpoint: bool) -> Regex {
+	let path = path.trim_end_matches('/');
+	let variable = Regex::new(":([a-zA-Z0-9_]+)").unwrap();
+	let param = Regex::new(r"^:([a-zA-Z0-9_]+)(?:\((.+)\)|<(alpha|uint)>)?$").unwrap();
+	let mut regex_path = String::from("^");
+	for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+		let (segment, optional) = match segment.strip_suffix('?') {
+			Some(segment) => (segment, true),
+			None => (segment, false),
+		};
+		let pattern = match segment {
+			"*" => "[^/]+".to_string(),
+			"**" => "[^/].*".to_string(),
+			_ if segment.starts_with('*') => format!("(?P<{}>[^/].*)", &segment[1..]),
+			_ => match param.captures(segment) {
+				Some(captures) => format!(
+					"(?P<{}>{})",
+					&captures[1],
+					match (captures.get(2), captures.get(3)) {
+						(Some(constraint), _) => format!("(?:{})", constraint.as_str()),
+						(_, Some(kind)) if kind.as_str() == "alpha" => "[a-zA-Z]+".to_string(),
+						(_, Some(_)) => "[0-9]+".to_string(),
+						_ => "[a-zA-Z0-9_\\.-]+".to_string(),
+					}
+				),
+				None => {
+					let escaped = regex::escape(segment).replace("\\*", "[^/]*");
+					variable
+						.replace_all(&escaped, "(?P<$1>[a-zA-Z0-9_\\.-]+)")
+						.to_string()
+				}
+			},
+		};
+		if optional {
+			regex_path.push_str(&format!("(?:/{})?", pattern));
+		} else {
+			regex_path.push('/');
+			regex_path.push_str(&pattern);
+		}
+	}
+	if is_endpoint {
+		regex_path.push('$');
+	} else {
+		regex_path.push_str("(?:/|$)");
+	}
+	Regex::new(&regex_path).unwrap()
+}
+
+fn reference_matches(regexes: &[Regex], path: &str) -> Vec<String> {
+	regexes
+		.iter()
+		.enumerate()
+		.filter_map(|(id, regex)| {
+			// A trailing slash is never part of what was matched
+			let captures = regex.captures(path.strip_suffix('/').unwrap_or(path))?;
+			let mut params = regex
+				.capture_names()
+				.flatten()
+				.filter_map(|name| Some(format!("{}={}", name, captures.name(name)?.as_str())))
+				.collect::<Vec<_>>();
+			params.sort();
+			Some(format!("{} {}", id, params.join(",")))
+		})
+		.collect()
+}
+
+async fn router_matches(app: &App<DefaultContext, Recorder, ()>, path: &str) -> Vec<String> {
+	let request = HyperRequest::get(path).body(Body::empty()).unwrap();
+	let request = Request::from_hyper(SocketAddr::from(([127, 0, 0, 1], 0)), request).await;
+	let context = app.resolve(DefaultContext::new(request)).await.unwrap();
+	context
+		.get_response()
+		.get_headers()
+		.get("X-Matched")
+		.cloned()
+		.unwrap_or_default()
+}
+
+async fn assert_same_matches(routes: &[(bool, &str)], paths: &[&str]) {
+	let mut app = App::<DefaultContext, Recorder, ()>::create(default_context_generator, ());
+	for (id, (is_endpoint, route)) in routes.iter().enumerate() {
+		if *is_endpoint {
+			app.get(route, &[Recorder(id)]);
+		} else {
+			app.use_middleware(route, &[Recorder(id)]);
+		}
+	}
+
+	let regexes = routes
+		.iter()
+		.map(|(is_endpoint, route)| reference_regex(route, *is_endpoint))
+		.collect::<Vec<_>>();
+
+	for path in paths {
+		assert_eq!(
+			router_matches(&app, path).await,
+			reference_matches(&regexes, path),
+			"routes {:?} disagree on path {}",
+			routes,
+			path
+		);
+	}
+}
+
+const PATHS: &[&str] = &[
+	"/",
+	"/users",
+	"/users/",
+	"/users/42",
+	"/users/42/",
+	"/users/42/posts",
+	"/users/42/posts/7",
+	"/users/a.b-c_d",
+	"/users//",
+	"/usersx",
+	"/api",
+	"/api/v1",
+	"/api/v1/users/42",
+	"/static/css/site.css",
+	"/static/",
+	"/static//a",
+	"/files/report.json",
+	"/files/report.tar.gz",
+	"/files/report",
+	"/a/b/c/d",
+	"/a//b",
+];
+
+#[tokio::test]
+async fn matches_fixed_routes() {
+	let routes = [
+		(false, "/"),
+		(false, "/api"),
+		(true, "/"),
+		(true, "/users"),
+		(true, "/users/:id"),
+		(false, "/users/:id"),
+		(true, "/users/:userId/posts/:postId"),
+		(true, "/users/:id/posts"),
+		(true, "/users/*"),
+		(false, "/static/**"),
+		(true, "/static/**"),
+		(true, "/files/:name.json"),
+		(true, "/files/:name.:ext"),
+		(true, "/files/report*"),
+		(true, "/a/**/d"),
+		(true, "/**"),
+		(true, "/api/v1/users/:id"),
+		(false, "/api/v1/"),
+		(true, "users"),
+	];
+	assert_same_matches(&routes, PATHS).await;
+}
+
+#[tokio::test]
+async fn matches_generated_routes() {
+	const SEGMENTS: &[&str] = &[
+		"users",
+		"api",
+		"v1",
+		"42",
+		"posts",
+		":id",
+		":name",
+		"*",
+		"**",
+		":file.json",
+		"static",
+		":id(\\d+)",
+		":name<alpha>",
+		":lang?",
+		"*rest",
+		"*rest?",
+	];
+
+	// A small xorshift generator, so that the routes are the same on every run
+	let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+	let numbered = Regex::new("([:*][a-zA-Z_]+)").unwrap();
+	let mut next = move || {
+		seed ^= seed << 13;
+		seed ^= seed >> 7;
+		seed ^= seed << 17;
+		seed
+	};
+
+	let paths = [
+		PATHS,
+		&[
+			"/api/v1/42",
+			"/users/42/42",
+			"/v1/posts/data.json",
+			"/static/users/api",
+			"/posts/42/v1/users",
+			"/users/abc",
+			"/users/abc/posts",
+			"/en/42/v1",
+			"/42/42/api",
+		],
+	]
+	.concat();
+
+	for _ in 0..50 {
+		let routes = (0..8)
+			.map(|_| {
+				let depth = next() % 4;
+				let path = (0..depth)
+					.map(|_| SEGMENTS[(next() % SEGMENTS.len() as u64) as usize])
+					.collect::<Vec<_>>();
+				// Number the params by position, so that a route never has two of the same name
+				let path = path
+					.iter()
+					.enumerate()
+					.map(|(index, segment)| {
+						numbered
+							.replace_all(segment, format!("${{1}}{}", index).as_str())
+							.to_string()
+					})
+					.collect::<Vec<_>>();
+				(next() % 2 == 0, format!("/{}", path.join("/")))
+			})
+			.collect::<Vec<_>>();
+		let routes = routes
+			.iter()
+			.map(|(is_endpoint, path)| (*is_endpoint, path.as_str()))
+			.collect::<Vec<_>>();
+		assert_same_matches(&routes, &paths).await;
+	}
+}
+
+// What each path matches when every route is registered as an endpoint
+async fn assert_endpoint_matches(routes: &[&str], expected: &[(&str, &[&str])]) {
+	let mut app = App::<DefaultContext, Recorder, ()>::create(default_context_generator, ());
+	for (id, route) in routes.iter().enumerate() {
+		app.get(route, &[Recorder(id)]);
+	}
+	for (path, matches) in expected {
+		assert_eq!(
+			router_matches(&app, path).await,
+			matches.iter().map(|m| m.to_string()).collect::<Vec<_>>(),
+			"routes {:?} on path {}",
+			routes,
+			path
+		);
+	}
+}
+
+#[tokio::test]
+async fn params_with_a_constraint_only_match_what_it_allows() {
+	assert_endpoint_matches(
+		&[
+			r"/users/:id(\d+)",
+			r"/tags/:tag([a-z]+(?:-[a-z]+)*)",
+			r"/codes/:code([A-Z]{2}|\d{3})",
+			r"/raw/:rest((?:[^/]|/)+)",
+		],
+		&[
+			("/users/42", &["0 id=42"]),
+			("/users/4a", &[]),
+			("/tags/hello-world", &["1 tag=hello-world"]),
+			("/tags/hello-", &[]),
+			("/codes/NL", &["2 code=NL"]),
+			("/codes/123", &["2 code=123"]),
+			("/codes/12", &[]),
+			// A constraint never reaches past its own segment
+			("/raw/a", &["3 rest=a"]),
+			("/raw/a/b", &[]),
+		],
+	)
+	.await;
+}
+
+#[tokio::test]
+async fn params_with_a_type_only_match_that_type() {
+	assert_endpoint_matches(
+		&[
+			"/int/:value<int>",
+			"/uint/:value<uint>",
+			"/alpha/:value<alpha>",
+			"/alnum/:value<alnum>",
+			"/hex/:value<hex>",
+			"/slug/:value<slug>",
+			"/uuid/:value<uuid>",
+		],
+		&[
+			("/int/-12", &["0 value=-12"]),
+			("/int/1.5", &[]),
+			("/uint/12", &["1 value=12"]),
+			("/uint/-12", &[]),
+			("/alpha/abc", &["2 value=abc"]),
+			("/alpha/abc1", &[]),
+			("/alnum/abc1", &["3 value=abc1"]),
+			("/alnum/abc_1", &[]),
+			("/hex/00ff", &["4 value=00ff"]),
+			("/hex/00fg", &[]),
+			("/slug/hello-world-2", &["5 value=hello-world-2"]),
+			("/slug/Hello", &[]),
+			(
+				"/uuid/67e55044-10b1-426f-9247-bb680e5fe0c8",
+				&["6 value=67e55044-10b1-426f-9247-bb680e5fe0c8"],
+			),
+			("/uuid/67e55044", &[]),
+		],
+	)
+	.await;
+}
+
+#[tokio::test]
+async fn optional_params_can_be_left_out() {
+	assert_endpoint_matches(
+		&["/:lang?/docs", "/posts/:year(\\d{4})?/:slug?"],
+		&[
+			("/docs", &["0 "]),
+			("/en/docs", &["0 lang=en"]),
+			("/en/fr/docs", &[]),
+			("/posts", &["1 "]),
+			("/posts/2020", &["1 year=2020"]),
+			("/posts/2020/hello", &["1 slug=hello,year=2020"]),
+			("/posts/hello", &["1 slug=hello"]),
+		],
+	)
+	.await;
+}
+
+#[tokio::test]
+async fn named_catch_alls_keep_what_they_matched() {
+	assert_endpoint_matches(
+		&["/files/*path", "/assets/*path?", "/docs/*page/edit"],
+		&[
+			("/files/a", &["0 path=a"]),
+			("/files/a/b/c.txt", &["0 path=a/b/c.txt"]),
+			("/files", &[]),
+			("/assets", &["1 "]),
+			("/assets/css/site.css", &["1 path=css/site.css"]),
+			("/docs/guide/intro/edit", &["2 page=guide/intro"]),
+			("/docs/edit", &[]),
+		],
+	)
+	.await;
+}
+
+#[test]
+fn invalid_paths_are_returned_as_errors() {
+	let cases = [
+		("/users/:(\\d+)", PathErrorKind::MissingName),
+		(
+			"/users/:id(\\d+",
+			PathErrorKind::MissingConstraintEnd {
+				param: "id".to_string(),
+			},
+		),
+		(
+			"/users/:id()",
+			PathErrorKind::EmptyConstraint {
+				param: "id".to_string(),
+			},
+		),
+		(
+			"/users/:id<int",
+			PathErrorKind::MissingTypeEnd {
+				param: "id".to_string(),
+			},
+		),
+		(
+			"/users/:id<number>",
+			PathErrorKind::UnknownType {
+				param: "id".to_string(),
+				kind: "number".to_string(),
+			},
+		),
+		(
+			"/users?",
+			PathErrorKind::NotOptional {
+				segment: "users".to_string(),
+			},
+		),
+		(
+			"/users/:id/posts/:id",
+			PathErrorKind::DuplicateParam {
+				param: "id".to_string(),
+			},
+		),
+	];
+
+	let mut app = App::<DefaultContext, Recorder, ()>::create(default_context_generator, ());
+	for (path, kind) in cases.iter() {
+		match app.method("GET", path, &[Recorder(0)]) {
+			Err(RouteError::InvalidPath(err)) => {
+				assert_eq!(err.get_path(), *path);
+				assert_eq!(err.get_kind(), kind, "path {}", path);
+			}
+			result => panic!("path {} gave {:?}", path, result),
+		}
+	}
+
+	// The regex crate has its say about constraints and patterns
+	match app.method("GET", "/users/:id(a{2,1})", &[Recorder(0)]) {
+		Err(RouteError::InvalidPath(err)) => assert!(
+			matches!(err.get_kind(), PathErrorKind::InvalidConstraint { param, .. } if param == "id"),
+			"{:?}",
+			err
+		),
+		result => panic!("{:?}", result),
+	}
+	match app.method("GET", "/files/:name(a{2,1}).json", &[Recorder(0)]) {
+		Err(RouteError::InvalidPath(err)) => assert!(
+			matches!(err.get_kind(), PathErrorKind::InvalidPattern { .. }),
+			"{:?}",
+			err
+		),
+		result => panic!("{:?}", result),
+	}
+
+	// Nothing was registered for any of them
+	assert_eq!(app.routes().count(), 0);
+}
+
+#[test]
+fn every_way_of_registering_an_endpoint_can_fail_instead_of_panicking() {
+	let mut app = App::<DefaultContext, Recorder, ()>::create(default_context_generator, ());
+	let path = "/users/:id<number>";
+	let results = vec![
+		app.try_get(path, &[Recorder(0)]),
+		app.try_post(path, &[Recorder(0)]),
+		app.try_put(path, &[Recorder(0)]),
+		app.try_delete(path, &[Recorder(0)]),
+		app.try_head(path, &[Recorder(0)]),
+		app.try_options(path, &[Recorder(0)]),
+		app.try_connect(path, &[Recorder(0)]),
+		app.try_patch(path, &[Recorder(0)]),
+		app.try_trace(path, &[Recorder(0)]),
+		app.try_get_named("user", path, &[Recorder(0)]),
+		app.try_post_named("user", path, &[Recorder(0)]),
+		app.try_put_named("user", path, &[Recorder(0)]),
+		app.try_delete_named("user", path, &[Recorder(0)]),
+		app.try_patch_named("user", path, &[Recorder(0)]),
+	];
+	let mut group_results = vec![];
+	app.group("/admin", |group| {
+		group.use_middleware(&[Recorder(1)]);
+		group_results = vec![
+			group.try_get(path, &[Recorder(0)]),
+			group.try_post(path, &[Recorder(0)]),
+			group.try_put(path, &[Recorder(0)]),
+			group.try_delete(path, &[Recorder(0)]),
+			group.try_head(path, &[Recorder(0)]),
+			group.try_options(path, &[Recorder(0)]),
+			group.try_connect(path, &[Recorder(0)]),
+			group.try_patch(path, &[Recorder(0)]),
+			group.try_trace(path, &[Recorder(0)]),
+			group.try_get_named("admin.user", path, &[Recorder(0)]),
+			group.try_post_named("admin.user", path, &[Recorder(0)]),
+			group.try_put_named("admin.user", path, &[Recorder(0)]),
+			group.try_delete_named("admin.user", path, &[Recorder(0)]),
+			group.try_patch_named("admin.user", path, &[Recorder(0)]),
+		];
+	});
+
+	for result in results.into_iter().chain(group_results) {
+		assert!(
+			matches!(
+				&result,
+				Err(RouteError::InvalidPath(err)) if matches!(err.get_kind(), PathErrorKind::UnknownType { .. })
+			),
+			"{:?}",
+			result
+		);
+	}
+	// Neither the routes nor their names were registered
+	assert_eq!(app.routes().count(), 0);
+	assert!(app.url_for("user", &[("id", "1")]).is_err());
+	assert!(app.url_for("admin.user", &[("id", "1")]).is_err());
+
+	app.try_get("/users/:id", &[Recorder(0)]).unwrap();
+	app.group("/admin", |group| {
+		group
+			.try_get_named("admin.user", "/users/:id", &[Recorder(0)])
+			.unwrap();
+	});
+	assert_eq!(app.routes().count(), 2);
+	assert_eq!(
+		app.url_for("admin.user", &[("id", "1")]).unwrap(),
+		"/admin/users/1"
+	);
+}
+
+#[test]
+#[should_panic(expected = "/users/:id<number>")]
+fn registering_an_invalid_path_panics() {
+	let mut app = App::<DefaultContext, Recorder, ()>::create(default_context_generator, ());
+	app.get("/users/:id<number>", &[Recorder(0)]);
+}
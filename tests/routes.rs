@@ -0,0 +1,162 @@
+use eve_rs::{default_context_generator, App, DefaultContext, DefaultMiddleware};
+
+type TestApp = App<DefaultContext, DefaultMiddleware<()>, ()>;
+
+fn next() -> DefaultMiddleware<()> {
+	DefaultMiddleware::new(|context, next| next(context))
+}
+
+fn app() -> TestApp {
+	let mut api = TestApp::create(default_context_generator, ());
+	api.use_middleware("/", &[next()]);
+	api.get_named("status", "/status", &[next()]);
+
+	let mut app = TestApp::create(default_context_generator, ());
+	app.use_middleware("/", &[next()]);
+	app.get_named("home", "/", &[next()]);
+	app.post("/users", &[next(), next()]);
+	app.method("PURGE", "/cache", &[next()]).unwrap();
+	app.use_sub_app("/api", api);
+	app
+}
+
+// What the shadowed routes look like when logged
+fn shadowed(app: &TestApp) -> Vec<String> {
+	app.get_shadowed_routes()
+		.iter()
+		.map(ToString::to_string)
+		.collect()
+}
+
+#[test]
+fn routes_list_middlewares_first_then_endpoints_by_method() {
+	let app = app();
+	let routes = app
+		.routes()
+		.map(|route| format!("{} {}", route, route.get_sub_app().unwrap_or("-")))
+		.collect::<Vec<_>>();
+	assert_eq!(
+		routes,
+		vec![
+			"middleware / -",
+			"middleware /api /api",
+			"GET / (home) -",
+			"GET /api/status (api.status) /api",
+			"POST /users -",
+			"POST /users -",
+			"PURGE /cache -",
+		]
+	);
+
+	let home = app
+		.routes()
+		.find(|route| route.get_name() == Some("home"))
+		.unwrap();
+	assert_eq!(
+		home.get_method().map(ToString::to_string),
+		Some("GET".to_string())
+	);
+	assert_eq!(home.get_path(), "/");
+	assert!(home.is_endpoint());
+	assert!(!app.routes().next().unwrap().is_endpoint());
+}
+
+#[test]
+fn route_table_lines_up_in_columns() {
+	assert_eq!(
+		app().get_route_table(),
+		[
+			"METHOD  PATH         KIND        NAME        SUB APP",
+			"ALL     /            middleware  -           -",
+			"ALL     /api         middleware  -           /api",
+			"GET     /            endpoint    home        -",
+			"GET     /api/status  endpoint    api.status  /api",
+			"POST    /users       endpoint    -           -",
+			"POST    /users       endpoint    -           -",
+			"PURGE   /cache       endpoint    -           -",
+		]
+		.join("\n")
+	);
+}
+
+#[test]
+fn endpoints_covered_by_an_earlier_one_are_shadowed() {
+	let mut app = TestApp::create(default_context_generator, ());
+	app.get("/users/:id", &[next()]);
+	app.get("/users/me", &[next()]);
+	app.get("/files/**", &[next()]);
+	app.get("/files/:name/raw", &[next()]);
+	app.get("/docs/:page?", &[next()]);
+	app.get("/docs", &[next()]);
+	assert_eq!(
+		shadowed(&app),
+		vec![
+			"GET /users/me is unreachable, since GET /users/:id was registered before it and \
+			 matches everything it does",
+			"GET /files/:name/raw is unreachable, since GET /files/** was registered before it \
+			 and matches everything it does",
+			"GET /docs is unreachable, since GET /docs/:page? was registered before it and \
+			 matches everything it does",
+		]
+	);
+
+	let shadowed_route = &app.get_shadowed_routes()[0];
+	assert_eq!(shadowed_route.get_route().get_path(), "/users/me");
+	assert_eq!(shadowed_route.get_shadowed_by().get_path(), "/users/:id");
+}
+
+#[test]
+fn endpoints_that_can_still_be_reached_are_not_shadowed() {
+	let mut app = TestApp::create(default_context_generator, ());
+	// The more specific one first is how it's meant to be done
+	app.get("/users/me", &[next()]);
+	app.get("/users/:id", &[next()]);
+	// A constraint leaves room for what it doesn't match
+	app.get("/posts/:id(\\d+)", &[next()]);
+	app.get("/posts/latest", &[next()]);
+	// Chained on the same path on purpose
+	app.get("/chained", &[next(), next()]);
+	app.get("/chained", &[next()]);
+	// Other methods have routes of their own
+	app.post("/users/me", &[next()]);
+	// Middlewares run before endpoints, and don't take the request away from them
+	app.use_middleware("/admin", &[next()]);
+	app.get("/admin/users", &[next()]);
+	// `*` needs a segment that `/docs` doesn't have
+	app.get("/docs/*", &[next()]);
+	app.get("/docs", &[next()]);
+
+	assert!(shadowed(&app).is_empty(), "{:?}", shadowed(&app));
+}
+
+#[test]
+fn group_middlewares_are_listed_as_middlewares_of_their_endpoint() {
+	let mut app = TestApp::create(default_context_generator, ());
+	app.group("/admin", |group| {
+		group.use_middleware(&[next()]);
+		group.get("/users/:id", &[next()]);
+		group.post_named("user.create", "/users", &[next()]);
+	});
+	app.get("/admin/users/me", &[next()]);
+
+	let routes = app
+		.routes()
+		.map(|route| route.to_string())
+		.collect::<Vec<_>>();
+	assert_eq!(
+		routes,
+		vec![
+			"GET middleware /admin/users/:id",
+			"GET /admin/users/:id",
+			"GET /admin/users/me",
+			"POST middleware /admin/users (user.create)",
+			"POST /admin/users (user.create)",
+		]
+	);
+
+	// The endpoint is what shadows, never the middleware in front of it
+	let shadowed_routes = app.get_shadowed_routes();
+	assert_eq!(shadowed_routes.len(), 1);
+	assert_eq!(shadowed_routes[0].get_route().get_path(), "/admin/users/me");
+	assert!(shadowed_routes[0].get_shadowed_by().is_endpoint());
+}
@@ -0,0 +1,355 @@
+mod common;
+
+use eve_rs::{
+	default_context_generator,
+	listen_with_config,
+	App,
+	Context,
+	DefaultContext,
+	DefaultMiddleware,
+	ServerConfig,
+};
+use futures::FutureExt;
+use hyper::{
+	client::conn::{Builder, SendRequest},
+	Body,
+	Request as HyperRequest,
+	Version,
+};
+use std::time::{Duration, Instant};
+use tokio::{
+	io::{AsyncReadExt, AsyncWriteExt},
+	net::TcpStream,
+	time::delay_for,
+};
+
+fn app() -> App<DefaultContext, DefaultMiddleware<()>, ()> {
+	let mut app =
+		App::<DefaultContext, DefaultMiddleware<()>, ()>::create(default_context_generator, ());
+	app.get(
+		"/",
+		&[DefaultMiddleware::new(|mut context, _| {
+			Box::pin(async move {
+				context.body("hello");
+				Ok(context)
+			})
+		})],
+	);
+	app.get(
+		"/slow",
+		&[DefaultMiddleware::new(|mut context, _| {
+			Box::pin(async move {
+				delay_for(Duration::from_millis(300)).await;
+				context.body("slow");
+				Ok(context)
+			})
+		})],
+	);
+	app.get(
+		"/large",
+		&[DefaultMiddleware::new(|mut context, _| {
+			Box::pin(async move {
+				context.body_bytes(&vec![b'a'; 1024 * 1024]);
+				Ok(context)
+			})
+		})],
+	);
+	app
+}
+
+async fn start(config: ServerConfig) -> u16 {
+	common::start(app(), |app| {
+		listen_with_config(
+			app,
+			([127, 0, 0, 1], 0),
+			config,
+			None::<futures::future::Pending<()>>,
+		)
+	})
+	.await
+}
+
+async fn client(port: u16, http2: bool) -> Result<SendRequest<Body>, hyper::Error> {
+	let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+	let (sender, connection) = Builder::new().http2_only(http2).handshake(stream).await?;
+	tokio::spawn(connection);
+	Ok(sender)
+}
+
+async fn get(
+	sender: &mut SendRequest<Body>,
+	path: &str,
+) -> Result<(u16, Version, Vec<u8>), hyper::Error> {
+	futures::future::poll_fn(|cx| sender.poll_ready(cx)).await?;
+	let response = sender
+		.send_request(HyperRequest::get(path).body(Body::empty()).unwrap())
+		.await?;
+	let (status, version) = (response.status().as_u16(), response.version());
+	let body = hyper::body::to_bytes(response.into_body()).await?;
+	Ok((status, version, body.to_vec()))
+}
+
+// Sends raw bytes and returns everything the server sends back before closing the connection
+async fn raw_exchange(stream: &mut TcpStream, request: &[u8]) -> String {
+	stream.write_all(request).await.unwrap();
+	let mut response = vec![0; 4096];
+	let read = stream.read(&mut response).await.unwrap_or(0);
+	String::from_utf8_lossy(&response[..read]).to_string()
+}
+
+async fn is_closed(stream: &mut TcpStream) -> bool {
+	let mut buffer = [0; 16];
+	matches!(
+		tokio::time::timeout(Duration::from_secs(2), stream.read(&mut buffer)).await,
+		Ok(Ok(0)) | Ok(Err(_))
+	)
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn header_read_timeout_closes_slow_clients() {
+	let port = start(ServerConfig::new().header_read_timeout(Duration::from_millis(100))).await;
+
+	let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+	stream.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+	delay_for(Duration::from_millis(300)).await;
+	let _ = stream.write_all(b"Host: localhost\r\n\r\n").await;
+	assert!(is_closed(&mut stream).await);
+
+	// A client that sends its headers in time is served, and can take its time with the next request
+	let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+	let response = raw_exchange(&mut stream, b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+	assert!(response.starts_with("HTTP/1.1 200"));
+	delay_for(Duration::from_millis(300)).await;
+	let response = raw_exchange(&mut stream, b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+	assert!(response.starts_with("HTTP/1.1 200"));
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn request_timeout_responds_with_408() {
+	let port = start(ServerConfig::new().request_timeout(Duration::from_millis(100))).await;
+
+	let mut sender = client(port, false).await.unwrap();
+	assert_eq!(get(&mut sender, "/slow").await.unwrap().0, 408);
+	assert_eq!(get(&mut sender, "/").await.unwrap().0, 200);
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn keep_alive_timeout_closes_idle_connections() {
+	let port = start(ServerConfig::new().keep_alive_timeout(Duration::from_millis(200))).await;
+
+	let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+	let response = raw_exchange(&mut stream, b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+	assert!(response.starts_with("HTTP/1.1 200"));
+	delay_for(Duration::from_millis(50)).await;
+	let response = raw_exchange(&mut stream, b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+	assert!(response.starts_with("HTTP/1.1 200"));
+
+	let started = Instant::now();
+	assert!(is_closed(&mut stream).await);
+	assert!(started.elapsed() >= Duration::from_millis(150));
+
+	// Requests that take longer than the timeout aren't cut off
+	let mut sender = client(port, false).await.unwrap();
+	assert_eq!(get(&mut sender, "/slow").await.unwrap().0, 200);
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn keep_alive_can_be_disabled() {
+	let port = start(ServerConfig::new().keep_alive(false)).await;
+
+	let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+	let response = raw_exchange(&mut stream, b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+	assert!(response.starts_with("HTTP/1.1 200"));
+	assert!(is_closed(&mut stream).await);
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn max_headers_responds_with_431() {
+	let port = start(ServerConfig::new().max_headers(4)).await;
+
+	let mut sender = client(port, false).await.unwrap();
+	let mut request = HyperRequest::get("/");
+	for i in 0..10 {
+		request = request.header(format!("x-header-{}", i).as_str(), "value");
+	}
+	let response = sender
+		.send_request(request.body(Body::empty()).unwrap())
+		.await
+		.unwrap();
+	assert_eq!(response.status().as_u16(), 431);
+	assert_eq!(get(&mut sender, "/").await.unwrap().0, 200);
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn max_header_size_responds_with_431() {
+	let port = start(ServerConfig::new().max_header_size(256)).await;
+
+	let mut sender = client(port, false).await.unwrap();
+	let response = sender
+		.send_request(
+			HyperRequest::get("/")
+				.header("x-large", "a".repeat(512).as_str())
+				.body(Body::empty())
+				.unwrap(),
+		)
+		.await
+		.unwrap();
+	assert_eq!(response.status().as_u16(), 431);
+	assert_eq!(get(&mut sender, "/").await.unwrap().0, 200);
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn http1_only_rejects_http2() {
+	let port = start(ServerConfig::new().http1_only()).await;
+
+	let mut sender = client(port, false).await.unwrap();
+	assert_eq!(get(&mut sender, "/").await.unwrap().1, Version::HTTP_11);
+
+	let result = match client(port, true).await {
+		Ok(mut sender) => get(&mut sender, "/").await.map(|_| ()),
+		Err(err) => Err(err),
+	};
+	assert!(result.is_err());
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn h2c_is_accepted_by_default_and_can_be_disabled() {
+	let port = start(ServerConfig::new()).await;
+	let h2c_disabled_port = start(ServerConfig::new().h2c(false)).await;
+
+	let mut sender = client(port, true).await.unwrap();
+	assert_eq!(get(&mut sender, "/").await.unwrap().1, Version::HTTP_2);
+	let mut sender = client(port, false).await.unwrap();
+	assert_eq!(get(&mut sender, "/").await.unwrap().1, Version::HTTP_11);
+
+	let result = match client(h2c_disabled_port, true).await {
+		Ok(mut sender) => get(&mut sender, "/").await.map(|_| ()),
+		Err(err) => Err(err),
+	};
+	assert!(result.is_err());
+	let mut sender = client(h2c_disabled_port, false).await.unwrap();
+	assert_eq!(get(&mut sender, "/").await.unwrap().1, Version::HTTP_11);
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn http2_only_rejects_http1() {
+	let port = start(ServerConfig::new().http2_only()).await;
+
+	let mut sender = client(port, true).await.unwrap();
+	assert_eq!(get(&mut sender, "/").await.unwrap().1, Version::HTTP_2);
+
+	let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+	let response = raw_exchange(&mut stream, b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+	assert!(!response.starts_with("HTTP/1.1"));
+}
+
+// How long it takes for two slow requests, sent at the same time over one connection, to finish
+async fn concurrent_requests_duration(port: u16) -> Duration {
+	let mut sender = client(port, true).await.unwrap();
+	// Make sure the server's settings have been received before sending anything
+	get(&mut sender, "/").await.unwrap();
+
+	let started = Instant::now();
+	let mut responses = vec![];
+	for _ in 0..2 {
+		futures::future::poll_fn(|cx| sender.poll_ready(cx))
+			.await
+			.unwrap();
+		responses
+			.push(sender.send_request(HyperRequest::get("/slow").body(Body::empty()).unwrap()));
+	}
+	for response in futures::future::join_all(responses).await {
+		assert_eq!(response.unwrap().status().as_u16(), 200);
+	}
+	started.elapsed()
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn http2_max_concurrent_streams_limits_streams() {
+	let limited_port = start(ServerConfig::new().http2_max_concurrent_streams(1)).await;
+	let port = start(ServerConfig::new()).await;
+
+	assert!(concurrent_requests_duration(limited_port).await >= Duration::from_millis(550));
+	assert!(concurrent_requests_duration(port).await < Duration::from_millis(550));
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn http2_window_and_frame_sizes_are_applied() {
+	let port = start(
+		ServerConfig::new()
+			.http2_initial_stream_window_size(16 * 1024)
+			.http2_initial_connection_window_size(32 * 1024)
+			.http2_max_frame_size(16 * 1024),
+	)
+	.await;
+
+	let mut sender = client(port, true).await.unwrap();
+	let (status, version, body) = get(&mut sender, "/large").await.unwrap();
+	assert_eq!((status, version), (200, Version::HTTP_2));
+	assert_eq!(body.len(), 1024 * 1024);
+}
+
+// Finds the socket the server accepted for the given client among the fds of this process,
+// since the server runs in the same process as the test
+#[cfg(target_os = "linux")]
+fn accepted_nodelay(server_port: u16, client_addr: std::net::SocketAddr) -> bool {
+	use std::{
+		mem::ManuallyDrop,
+		net::TcpStream as StdTcpStream,
+		os::unix::io::{FromRawFd, RawFd},
+	};
+
+	std::fs::read_dir("/proc/self/fd")
+		.unwrap()
+		.filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<RawFd>().ok())
+		.find_map(|fd| {
+			// Borrowed, so it must not be closed when done with
+			let stream = ManuallyDrop::new(unsafe { StdTcpStream::from_raw_fd(fd) });
+			let is_accepted = stream.local_addr().ok()?.port() == server_port &&
+				stream.peer_addr().ok()? == client_addr;
+			if is_accepted {
+				stream.nodelay().ok()
+			} else {
+				None
+			}
+		})
+		.expect("the accepted connection wasn't found")
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test(threaded_scheduler)]
+async fn tcp_nodelay_is_set_on_accepted_connections() {
+	let nodelay_port = start(ServerConfig::new().tcp_nodelay(true)).await;
+	let port = start(ServerConfig::new()).await;
+
+	for (port, nodelay) in [(nodelay_port, true), (port, false)] {
+		let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+		let client_addr = stream.local_addr().unwrap();
+		let (mut sender, connection) = Builder::new().handshake(stream).await.unwrap();
+		tokio::spawn(connection);
+		assert_eq!(get(&mut sender, "/").await.unwrap().2, b"hello");
+		assert_eq!(accepted_nodelay(port, client_addr), nodelay);
+	}
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn max_connections_holds_back_new_connections() {
+	let port = start(ServerConfig::new().max_connections(1)).await;
+
+	let mut first = client(port, false).await.unwrap();
+	assert_eq!(get(&mut first, "/").await.unwrap().0, 200);
+
+	let mut waiting = tokio::spawn(async move {
+		let mut second = client(port, false).await.unwrap();
+		get(&mut second, "/").await.unwrap().0
+	});
+	delay_for(Duration::from_millis(300)).await;
+	assert!((&mut waiting).now_or_never().is_none());
+
+	drop(first);
+	let status = tokio::time::timeout(Duration::from_secs(2), waiting)
+		.await
+		.expect("the waiting connection wasn't accepted")
+		.unwrap();
+	assert_eq!(status, 200);
+}
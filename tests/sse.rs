@@ -0,0 +1,167 @@
+mod common;
+
+use eve_rs::{
+	default_context_generator,
+	listen,
+	App,
+	Bytes,
+	ChunkStream,
+	ClientDisconnected,
+	Context,
+	DefaultContext,
+	DefaultMiddleware,
+	Request,
+	SseEvent,
+};
+use futures::{channel::oneshot, StreamExt};
+use hyper::{client::conn::handshake, Body, Request as HyperRequest};
+use std::{net::SocketAddr, sync::Mutex, time::Duration};
+use tokio::{
+	net::TcpStream,
+	time::{delay_for, timeout},
+};
+
+async fn context(last_event_id: Option<&str>) -> DefaultContext {
+	let mut request = HyperRequest::get("/events");
+	if let Some(last_event_id) = last_event_id {
+		request = request.header("Last-Event-ID", last_event_id);
+	}
+	let request = Request::from_hyper(
+		SocketAddr::from(([127, 0, 0, 1], 0)),
+		request.body(Body::empty()).unwrap(),
+	)
+	.await;
+	DefaultContext::new(request)
+}
+
+#[test]
+fn data_is_split_on_every_line_ending() {
+	for data in &["a\r\nb\r\nc", "a\rb\rc", "a\nb\nc", "a\r\nb\rc\n"] {
+		assert_eq!(
+			SseEvent::new(data).to_string(),
+			"data: a\ndata: b\ndata: c\n\n",
+			"data {:?}",
+			data
+		);
+	}
+	assert_eq!(
+		SseEvent::new("a\r\rb").to_string(),
+		"data: a\ndata: \ndata: b\n\n"
+	);
+	assert_eq!(SseEvent::new("").to_string(), "data:\n\n");
+}
+
+#[test]
+fn id_and_event_stay_on_one_line() {
+	let event = SseEvent::new("data").id("1\r2\n3").event("a\r\nb");
+	assert_eq!(event.to_string(), "id: 123\nevent: ab\ndata: data\n\n");
+}
+
+#[tokio::test]
+async fn comments_are_split_on_every_line_ending() {
+	let mut context = context(None).await;
+	let mut sender = context.sse_with_keep_alive(None);
+	let mut stream = context.get_response_mut().take_body().into_stream();
+
+	for comment in &["a\r\nb", "a\rb", "a\nb"] {
+		sender.comment(comment).await.unwrap();
+		let chunk = stream.next().await.unwrap().unwrap();
+		assert_eq!(&chunk[..], b": a\n: b\n\n", "comment {:?}", comment);
+	}
+}
+
+async fn next_chunk(stream: &mut ChunkStream) -> Bytes {
+	timeout(Duration::from_secs(1), stream.next())
+		.await
+		.unwrap()
+		.unwrap()
+		.unwrap()
+}
+
+#[tokio::test]
+async fn keep_alive_comments_are_sent_while_idle() {
+	let mut context = context(None).await;
+	let mut sender = context.sse_with_keep_alive(Some(Duration::from_millis(100)));
+	let mut stream = context.get_response_mut().take_body().into_stream();
+	assert_eq!(&next_chunk(&mut stream).await[..], b":\n\n");
+	sender.send(SseEvent::new("hello")).await.unwrap();
+	assert_eq!(&next_chunk(&mut stream).await[..], b"data: hello\n\n");
+	assert_eq!(&next_chunk(&mut stream).await[..], b":\n\n");
+}
+
+#[tokio::test]
+async fn last_event_id_is_taken_from_the_request() {
+	assert_eq!(
+		context(Some("41")).await.get_last_event_id().as_deref(),
+		Some("41")
+	);
+	assert_eq!(context(None).await.get_last_event_id(), None);
+}
+
+// Whoever's waiting to hear that the client went away
+type Disconnected = Mutex<Option<oneshot::Sender<ClientDisconnected>>>;
+
+#[tokio::test(threaded_scheduler)]
+async fn sending_fails_once_the_client_is_gone() {
+	let (disconnected, client_gone) = oneshot::channel();
+	let mut app = App::<DefaultContext, DefaultMiddleware<()>, Disconnected>::create(
+		default_context_generator,
+		Mutex::new(Some(disconnected)),
+	);
+	app.get(
+		"/events",
+		&[DefaultMiddleware::new(|mut context, _| {
+			Box::pin(async move {
+				let mut sender = context.sse_with_keep_alive(None);
+				let disconnected = context
+					.state::<Disconnected>()
+					.unwrap()
+					.lock()
+					.unwrap()
+					.take()
+					.unwrap();
+				tokio::spawn(async move {
+					for id in 0.. {
+						let event = SseEvent::new("tick").id(&id.to_string());
+						if let Err(err) = sender.send(event).await {
+							assert!(sender.is_closed());
+							let _ = disconnected.send(err);
+							return;
+						}
+						delay_for(Duration::from_millis(20)).await;
+					}
+				});
+				Ok(context)
+			})
+		})],
+	);
+	let port = common::start(app, |app| {
+		listen(
+			app,
+			([127, 0, 0, 1], 0),
+			None::<futures::future::Pending<()>>,
+		)
+	})
+	.await;
+
+	let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+	let (mut sender, connection) = handshake(stream).await.unwrap();
+	let connection = tokio::spawn(connection);
+	let response = sender
+		.send_request(HyperRequest::get("/events").body(Body::empty()).unwrap())
+		.await
+		.unwrap();
+	let mut body = response.into_body();
+	assert_eq!(
+		&body.next().await.unwrap().unwrap()[..],
+		b"id: 0\ndata: tick\n\n"
+	);
+
+	drop(body);
+	drop(sender);
+	let _ = connection.await;
+	timeout(Duration::from_secs(2), client_gone)
+		.await
+		.expect("sending didn't fail after the client left")
+		.unwrap();
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use eve_rs::{
+	default_context_generator,
+	listen,
+	App,
+	Context,
+	DefaultContext,
+	DefaultMiddleware,
+	Request,
+};
+use hyper::{client::conn::handshake, Body, Request as HyperRequest};
+use std::net::SocketAddr;
+use tokio::net::TcpStream;
+
+type TestApp<TState> = App<DefaultContext, DefaultMiddleware<()>, TState>;
+
+// Tells which state the middleware got, by type
+fn describe_state() -> DefaultMiddleware<()> {
+	DefaultMiddleware::new(|mut context, _| {
+		Box::pin(async move {
+			let state = match (context.state::<&'static str>(), context.state::<u32>()) {
+				(Some(name), None) => format!("name {}", name),
+				(None, Some(number)) => format!("number {}", number),
+				_ => "none".to_string(),
+			};
+			context.body(&state);
+			Ok(context)
+		})
+	})
+}
+
+fn numbered_context_generator(request: Request, number: &u32) -> DefaultContext {
+	let mut context = DefaultContext::new(request);
+	context.header("X-Generated-By", &format!("number {}", number));
+	context
+}
+
+async fn get(port: u16, path: &str) -> (Option<String>, String) {
+	let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+	let (mut sender, connection) = handshake(stream).await.unwrap();
+	tokio::spawn(connection);
+	let response = sender
+		.send_request(HyperRequest::get(path).body(Body::empty()).unwrap())
+		.await
+		.unwrap();
+	let generated_by = response
+		.headers()
+		.get("X-Generated-By")
+		.map(|value| value.to_str().unwrap().to_string());
+	let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+	(generated_by, String::from_utf8(body.to_vec()).unwrap())
+}
+
+async fn start<TState>(app: TestApp<TState>) -> u16
+where
+	TState: 'static + Send + Sync,
+{
+	common::start(app, |app| {
+		listen(
+			app,
+			([127, 0, 0, 1], 0),
+			None::<futures::future::Pending<()>>,
+		)
+	})
+	.await
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn sub_apps_keep_their_state_and_context_generator() {
+	let mut sub_app = TestApp::create(numbered_context_generator, 42_u32);
+	sub_app.get("/state", &[describe_state()]);
+
+	let mut app = TestApp::create(default_context_generator, "root");
+	app.get("/state", &[describe_state()]);
+	app.use_sub_app("/sub", sub_app);
+
+	let port = start(app).await;
+
+	assert_eq!(get(port, "/state").await, (None, "name root".to_string()));
+	assert_eq!(
+		get(port, "/sub/state").await,
+		(Some("number 42".to_string()), "number 42".to_string())
+	);
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn sub_apps_at_the_root_only_generate_the_context_of_their_own_routes() {
+	let mut first = TestApp::create(numbered_context_generator, 1_u32);
+	first.get("/first", &[describe_state()]);
+	let mut second = TestApp::create(numbered_context_generator, 2_u32);
+	second.get("/second", &[describe_state()]);
+
+	let mut app = TestApp::create(default_context_generator, "root");
+	app.get("/parent-route", &[describe_state()]);
+	app.use_sub_app("/", first);
+	app.use_sub_app("/", second);
+
+	let port = start(app).await;
+
+	assert_eq!(
+		get(port, "/parent-route").await,
+		(None, "name root".to_string())
+	);
+	assert_eq!(
+		get(port, "/first").await,
+		(Some("number 1".to_string()), "number 1".to_string())
+	);
+	assert_eq!(
+		get(port, "/second").await,
+		(Some("number 2".to_string()), "number 2".to_string())
+	);
+}
+
+#[tokio::test]
+async fn middlewares_get_the_state_of_the_app_they_were_registered_on() {
+	let mut sub_app = TestApp::create(default_context_generator, 42_u32);
+	sub_app.get("/state", &[describe_state()]);
+
+	let mut app = TestApp::create(default_context_generator, "root");
+	app.use_middleware(
+		"/",
+		&[DefaultMiddleware::new(|mut context, next| {
+			Box::pin(async move {
+				let name = context.state::<&'static str>().copied().unwrap_or("none");
+				context.header("X-Outer-State", name);
+				next(context).await
+			})
+		})],
+	);
+	app.use_sub_app("/sub", sub_app);
+
+	let request = HyperRequest::get("/sub/state").body(Body::empty()).unwrap();
+	let request = Request::from_hyper(SocketAddr::from(([127, 0, 0, 1], 0)), request).await;
+	let context = app.resolve(DefaultContext::new(request)).await.unwrap();
+	assert_eq!(
+		context.get_response().get_header("X-Outer-State").unwrap(),
+		"root"
+	);
+	assert_eq!(context.get_response().get_body(), b"number 42");
+	assert_eq!(app.get_state(), &"root");
+}
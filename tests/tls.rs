@@ -0,0 +1,206 @@
+#![cfg(feature = "tls")]
+
+mod common;
+
+use eve_rs::{
+	default_context_generator,
+	listen_tls,
+	App,
+	Context,
+	DefaultContext,
+	DefaultMiddleware,
+	TlsConfig,
+};
+use hyper::{
+	client::conn::{Builder, SendRequest},
+	Body,
+	Request as HyperRequest,
+	Version,
+};
+use std::{fs, io::Error as IoError, path::PathBuf, sync::Arc, time::Duration};
+use tokio::net::TcpStream;
+use tokio_rustls::{
+	rustls::{Certificate, ClientConfig, Session},
+	webpki::DNSNameRef,
+	TlsConnector,
+};
+
+struct TestCert {
+	cert_pem: String,
+	key_pem: String,
+	der: Vec<u8>,
+}
+
+fn generate_cert() -> TestCert {
+	let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+	TestCert {
+		cert_pem: cert.serialize_pem().unwrap(),
+		key_pem: cert.serialize_private_key_pem(),
+		der: cert.serialize_der().unwrap(),
+	}
+}
+
+fn write_cert(name: &str, cert: &TestCert) -> (PathBuf, PathBuf) {
+	let dir = std::env::temp_dir().join(format!("eve-tls-{}-{}", std::process::id(), name));
+	fs::create_dir_all(&dir).unwrap();
+	let (cert_path, key_path) = (dir.join("cert.pem"), dir.join("key.pem"));
+	fs::write(&cert_path, &cert.cert_pem).unwrap();
+	fs::write(&key_path, &cert.key_pem).unwrap();
+	(cert_path, key_path)
+}
+
+fn app() -> App<DefaultContext, DefaultMiddleware<()>, ()> {
+	let mut app =
+		App::<DefaultContext, DefaultMiddleware<()>, ()>::create(default_context_generator, ());
+	app.get(
+		"/",
+		&[DefaultMiddleware::new(|mut context, _| {
+			Box::pin(async move {
+				let body = format!("{} {}", context.get_protocol(), context.is_secure());
+				context.body(&body);
+				Ok(context)
+			})
+		})],
+	);
+	app
+}
+
+async fn connect(
+	port: u16,
+	trusted: &TestCert,
+	protocol: &[u8],
+) -> Result<(SendRequest<Body>, Option<Vec<u8>>), IoError> {
+	let mut config = ClientConfig::new();
+	config
+		.root_store
+		.add(&Certificate(trusted.der.clone()))
+		.unwrap();
+	config.set_protocols(&[protocol.to_vec()]);
+
+	let stream = TcpStream::connect(("127.0.0.1", port)).await?;
+	let stream = TlsConnector::from(Arc::new(config))
+		.connect(DNSNameRef::try_from_ascii_str("localhost").unwrap(), stream)
+		.await?;
+	let alpn = stream.get_ref().1.get_alpn_protocol().map(<[u8]>::to_vec);
+
+	let (sender, connection) = Builder::new()
+		.http2_only(protocol == b"h2")
+		.handshake(stream)
+		.await
+		.map_err(IoError::other)?;
+	tokio::spawn(connection);
+	Ok((sender, alpn))
+}
+
+async fn get(sender: &mut SendRequest<Body>) -> (Version, String) {
+	let response = sender
+		.send_request(
+			HyperRequest::get("https://localhost/")
+				.body(Body::empty())
+				.unwrap(),
+		)
+		.await
+		.unwrap();
+	let version = response.version();
+	let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+	(version, String::from_utf8(body.to_vec()).unwrap())
+}
+
+async fn start(tls_config: TlsConfig) -> u16 {
+	common::start(app(), |app| {
+		listen_tls(
+			app,
+			([127, 0, 0, 1], 0),
+			tls_config,
+			None::<futures::future::Pending<()>>,
+		)
+	})
+	.await
+}
+
+// Waits for a reloaded certificate to be served
+async fn wait_for(port: u16, trusted: &TestCert) -> SendRequest<Body> {
+	for _ in 0..100 {
+		if let Ok((sender, _)) = connect(port, trusted, b"http/1.1").await {
+			return sender;
+		}
+		tokio::time::delay_for(Duration::from_millis(50)).await;
+	}
+	panic!("unable to connect to the server on port {}", port);
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn serves_http1_and_http2_over_tls() {
+	let cert = generate_cert();
+	let (cert_path, key_path) = write_cert("protocols", &cert);
+	let port = start(TlsConfig::new(&cert_path, &key_path)).await;
+
+	let (mut sender, _) = connect(port, &cert, b"http/1.1").await.unwrap();
+	assert_eq!(
+		get(&mut sender).await,
+		(Version::HTTP_11, "https true".to_string())
+	);
+
+	let (mut sender, alpn) = connect(port, &cert, b"h2").await.unwrap();
+	assert_eq!(alpn.as_deref(), Some(&b"h2"[..]));
+	assert_eq!(
+		get(&mut sender).await,
+		(Version::HTTP_2, "https true".to_string())
+	);
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn fails_on_invalid_certificate_files() {
+	let dir = std::env::temp_dir().join(format!("eve-tls-{}-invalid", std::process::id()));
+	fs::create_dir_all(&dir).unwrap();
+	fs::write(dir.join("cert.pem"), "not a certificate").unwrap();
+	fs::write(dir.join("key.pem"), "not a key").unwrap();
+
+	let result = listen_tls(
+		app(),
+		([127, 0, 0, 1], 0),
+		TlsConfig::new(dir.join("cert.pem"), dir.join("key.pem")),
+		None::<futures::future::Pending<()>>,
+	)
+	.await;
+	assert!(result.is_err());
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn reloads_certificate_when_files_change() {
+	let (old_cert, new_cert) = (generate_cert(), generate_cert());
+	let (cert_path, key_path) = write_cert("watch", &old_cert);
+	let port = start(
+		TlsConfig::new(&cert_path, &key_path).watch_interval(Some(Duration::from_millis(50))),
+	)
+	.await;
+
+	let (mut established, _) = connect(port, &old_cert, b"http/1.1").await.unwrap();
+	write_cert("watch", &new_cert);
+	wait_for(port, &new_cert).await;
+
+	assert!(connect(port, &old_cert, b"http/1.1").await.is_err());
+	// Connections made with the old certificate are kept alive
+	assert_eq!(get(&mut established).await.1, "https true");
+}
+
+#[cfg(unix)]
+#[tokio::test(threaded_scheduler)]
+async fn reloads_certificate_on_sighup() {
+	let (old_cert, new_cert) = (generate_cert(), generate_cert());
+	let (cert_path, key_path) = write_cert("sighup", &old_cert);
+	let port = start(TlsConfig::new(&cert_path, &key_path).watch_interval(None)).await;
+
+	let (mut established, _) = connect(port, &old_cert, b"http/1.1").await.unwrap();
+	write_cert("sighup", &new_cert);
+	assert!(connect(port, &new_cert, b"http/1.1").await.is_err());
+
+	let status = std::process::Command::new("kill")
+		.args(["-HUP", &std::process::id().to_string()])
+		.status()
+		.unwrap();
+	assert!(status.success());
+	wait_for(port, &new_cert).await;
+
+	assert_eq!(get(&mut established).await.1, "https true");
+}
@@ -0,0 +1,302 @@
+#![cfg(feature = "websocket")]
+
+mod common;
+
+use eve_rs::{
+	default_context_generator,
+	listen_with_config,
+	App,
+	Context,
+	DefaultContext,
+	DefaultMiddleware,
+	Error,
+	Request,
+	ServerConfig,
+	WebSocket,
+	WebSocketCloseCode,
+	WebSocketError,
+	WebSocketMessage,
+};
+use futures::{channel::oneshot, SinkExt, StreamExt};
+use hyper::{client::conn::handshake, Body, Request as HyperRequest};
+use std::{
+	borrow::Cow,
+	future::Future,
+	pin::Pin,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+	},
+	time::Duration,
+};
+use tokio::{net::TcpStream, time::delay_for};
+use tokio_tungstenite::{client_async, tungstenite::protocol::CloseFrame, WebSocketStream};
+
+type DrainState = Arc<AtomicBool>;
+
+fn app(drained: DrainState) -> App<DefaultContext, DefaultMiddleware<()>, DrainState> {
+	let mut app = App::<DefaultContext, DefaultMiddleware<()>, DrainState>::create(
+		default_context_generator,
+		drained,
+	);
+	app.websocket("/echo", echo_messages);
+
+	app.use_middleware(
+		"/secure",
+		&[DefaultMiddleware::new(|context, next| {
+			Box::pin(async move {
+				if context.get_header("Authorization").as_deref() == Some("Bearer letmein") {
+					next(context).await
+				} else {
+					Err(Error::unauthorized("Bearer"))
+				}
+			})
+		})],
+	);
+	app.websocket("/secure", echo_messages);
+
+	// Pings the client, and tells it once the pong is back
+	app.websocket("/ping", |mut socket, _| {
+		Box::pin(async move {
+			socket.ping(b"are you there".to_vec()).await.unwrap();
+			while let Some(Ok(message)) = socket.recv().await {
+				if let WebSocketMessage::Pong(data) = message {
+					let data = String::from_utf8(data).unwrap();
+					let _ = socket
+						.send(WebSocketMessage::text(format!("pong: {}", data)))
+						.await;
+					break;
+				}
+			}
+		})
+	});
+
+	// Closes the socket with the reason the client sends
+	app.websocket("/close", |mut socket, _| {
+		Box::pin(async move {
+			if let Some(Ok(WebSocketMessage::Text(reason))) = socket.recv().await {
+				let _ = socket.close(WebSocketCloseCode::Policy, &reason).await;
+				while socket.recv().await.is_some() {}
+			}
+		})
+	});
+	app.on_drain_complete(|drained| {
+		Box::pin(async move {
+			drained.store(true, Ordering::SeqCst);
+		})
+	});
+	app
+}
+
+// Sends back whatever it gets, except for control frames, which are answered already. Receiving
+// after a close is what sends the reply to it, and ends the loop
+fn echo_messages(mut socket: WebSocket, _: Request) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+	Box::pin(async move {
+		while let Some(Ok(message)) = socket.recv().await {
+			if message.is_ping() || message.is_pong() || message.is_close() {
+				continue;
+			}
+			if socket.send(message).await.is_err() {
+				break;
+			}
+		}
+	})
+}
+
+// Serves the app until the returned sender is used
+async fn start(config: ServerConfig, drained: DrainState) -> (u16, oneshot::Sender<()>) {
+	let (stop, stopped) = oneshot::channel::<()>();
+	let port = common::start(app(drained), |app| {
+		listen_with_config(
+			app,
+			([127, 0, 0, 1], 0),
+			config,
+			Some(async move {
+				let _ = stopped.await;
+			}),
+		)
+	})
+	.await;
+	(port, stop)
+}
+
+async fn connect(port: u16) -> WebSocketStream<TcpStream> {
+	connect_to(port, "/echo", None).await.unwrap()
+}
+
+async fn connect_to(
+	port: u16,
+	path: &str,
+	authorization: Option<&str>,
+) -> Result<WebSocketStream<TcpStream>, WebSocketError> {
+	let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+	let mut request = HyperRequest::get(format!("ws://127.0.0.1:{}{}", port, path));
+	if let Some(authorization) = authorization {
+		request = request.header("Authorization", authorization);
+	}
+	let (socket, _) = client_async(request.body(()).unwrap(), stream).await?;
+	Ok(socket)
+}
+
+async fn echo(socket: &mut WebSocketStream<TcpStream>, text: &str) -> Option<WebSocketMessage> {
+	socket.send(WebSocketMessage::text(text)).await.ok()?;
+	socket.next().await?.ok()
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn drain_waits_for_open_websockets() {
+	let drained = DrainState::default();
+	let (port, stop) = start(ServerConfig::new(), drained.clone()).await;
+
+	let mut socket = connect(port).await;
+	assert_eq!(
+		echo(&mut socket, "before").await,
+		Some(WebSocketMessage::text("before"))
+	);
+
+	stop.send(()).unwrap();
+	delay_for(Duration::from_millis(300)).await;
+	assert!(!drained.load(Ordering::SeqCst));
+	assert_eq!(
+		echo(&mut socket, "during").await,
+		Some(WebSocketMessage::text("during"))
+	);
+
+	socket.close(None).await.unwrap();
+	for _ in 0..100 {
+		if drained.load(Ordering::SeqCst) {
+			return;
+		}
+		delay_for(Duration::from_millis(20)).await;
+	}
+	panic!("the drain didn't complete once the websocket was closed");
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn drain_timeout_cuts_off_websockets() {
+	let drained = DrainState::default();
+	let (port, stop) = start(
+		ServerConfig::new().drain_timeout(Duration::from_millis(200)),
+		drained.clone(),
+	)
+	.await;
+
+	let mut socket = connect(port).await;
+	stop.send(()).unwrap();
+	delay_for(Duration::from_millis(500)).await;
+
+	assert!(drained.load(Ordering::SeqCst));
+	assert_eq!(echo(&mut socket, "after").await, None);
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn middlewares_can_reject_the_upgrade_before_the_handshake() {
+	let (port, _stop) = start(ServerConfig::new(), DrainState::default()).await;
+
+	for authorization in [None, Some("Bearer guess")] {
+		match connect_to(port, "/secure", authorization).await {
+			Err(WebSocketError::Http(status)) => assert_eq!(status, 401),
+			Err(err) => panic!("{:?} failed with {}", authorization, err),
+			Ok(_) => panic!("{:?} was let through", authorization),
+		}
+	}
+
+	let mut socket = connect_to(port, "/secure", Some("Bearer letmein"))
+		.await
+		.unwrap();
+	assert_eq!(
+		echo(&mut socket, "let in").await,
+		Some(WebSocketMessage::text("let in"))
+	);
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn handshake_answers_with_the_accept_key() {
+	let (port, _stop) = start(ServerConfig::new(), DrainState::default()).await;
+
+	let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+	let (mut sender, connection) = handshake(stream).await.unwrap();
+	tokio::spawn(connection);
+	// The example from RFC 6455, section 1.3
+	let response = sender
+		.send_request(
+			HyperRequest::get("/echo")
+				.header("Host", "localhost")
+				.header("Upgrade", "websocket")
+				.header("Connection", "keep-alive, Upgrade")
+				.header("Sec-WebSocket-Version", "13")
+				.header("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ==")
+				.body(Body::empty())
+				.unwrap(),
+		)
+		.await
+		.unwrap();
+	assert_eq!(response.status(), 101);
+	assert_eq!(
+		response.headers()["Sec-WebSocket-Accept"],
+		"s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+	);
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn pings_are_answered_with_pongs() {
+	let (port, _stop) = start(ServerConfig::new(), DrainState::default()).await;
+
+	// The client's pings are answered without the handler having to
+	let mut socket = connect(port).await;
+	socket
+		.send(WebSocketMessage::Ping(b"hello".to_vec()))
+		.await
+		.unwrap();
+	assert_eq!(
+		socket.next().await.unwrap().unwrap(),
+		WebSocketMessage::Pong(b"hello".to_vec())
+	);
+
+	// and the server's pings are answered by the client the same way
+	let mut socket = connect_to(port, "/ping", None).await.unwrap();
+	assert_eq!(
+		socket.next().await.unwrap().unwrap(),
+		WebSocketMessage::Ping(b"are you there".to_vec())
+	);
+	assert_eq!(
+		socket.next().await.unwrap().unwrap(),
+		WebSocketMessage::text("pong: are you there")
+	);
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn close_frames_carry_their_code() {
+	let (port, _stop) = start(ServerConfig::new(), DrainState::default()).await;
+
+	let mut socket = connect_to(port, "/close", None).await.unwrap();
+	socket
+		.send(WebSocketMessage::text("not allowed"))
+		.await
+		.unwrap();
+	assert_eq!(
+		socket.next().await.unwrap().unwrap(),
+		WebSocketMessage::Close(Some(CloseFrame {
+			code: WebSocketCloseCode::Policy,
+			reason: Cow::Borrowed("not allowed"),
+		}))
+	);
+	assert!(socket.next().await.is_none());
+
+	// Closing from the client is acknowledged
+	let mut socket = connect(port).await;
+	socket
+		.close(Some(CloseFrame {
+			code: WebSocketCloseCode::Away,
+			reason: Cow::Borrowed("bye"),
+		}))
+		.await
+		.unwrap();
+	assert_eq!(
+		socket.next().await.unwrap().unwrap(),
+		WebSocketMessage::Close(Some(CloseFrame {
+			code: WebSocketCloseCode::Normal,
+			reason: Cow::Borrowed(""),
+		}))
+	);
+}